@@ -25,15 +25,14 @@ async fn main() {
         match entry.entryhash {
             Some(hash) => {
                 let entryobj = entry::entry(&client, &hash).await.expect("Fetching Entry");
-                match entryobj.result.chainid.as_str() {
-                    TX_CHAIN => match entry.ecpubkey {
+                if entryobj.result.chainid == TX_CHAIN {
+                    match entry.ecpubkey {
                         Some(key) => {
                             let ecpubbytes = hex::decode(&key).expect("Decoding String");
                             ec_addresses.push(readable(&EC_PUB, &ecpubbytes));
                         }
                         None => (),
-                    },
-                    _ => (),
+                    }
                 }
             }
             None => (),
@@ -1,5 +1,11 @@
 //! Relating to Address functions
 use super::*;
+use crate::secret::Secret;
+use ed25519_dalek::SigningKey;
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+
+const BASE58: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
 
 /// Retrieve the public and private parts of a Factoid or Entry Credit address
 ///stored in the wallet.
@@ -84,11 +90,174 @@ pub async fn remove_address(api: &Factom, address: &str) -> Result<ApiResponse<R
     parse(response).await
 }
 
+/// Encodes a 32 byte key into a human readable address with the given prefix,
+/// see the prefix constants. Public factoid addresses encode the hash of the
+/// key's RCD, every other address encodes the key itself.
+///
+/// # Example
+/// ```
+/// use factom::*;
+///
+/// let address = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+/// let key = address::decode_address(&EC_PUBLIC_PREFIX, address).unwrap();
+/// assert_eq!(address::encode_address(&EC_PUBLIC_PREFIX, &key), address);
+/// ```
+pub fn encode_address(prefix: &[u8], key: &[u8; 32]) -> String {
+    let mut bytes = [prefix, &key[..]].concat();
+    let checksum = checksum(&bytes);
+    bytes.extend_from_slice(&checksum);
+    encode_base58(&bytes)
+}
+
+/// Decodes an address with the given prefix into its 32 byte key, checking the
+/// prefix and checksum
+pub fn decode_address(prefix: &[u8], address: &str) -> Result<[u8; 32]> {
+    let bytes = decode_base58(address).ok_or("Invalid base58 address")?;
+    if bytes.len() != prefix.len() + 36 || !bytes.starts_with(prefix) {
+        return Err(format!("Invalid address: {}", address).into());
+    }
+    let (body, checksum_bytes) = bytes.split_at(bytes.len() - 4);
+    if checksum(body) != checksum_bytes {
+        return Err(format!("Invalid address checksum: {}", address).into());
+    }
+    Ok(body[prefix.len()..].try_into()?)
+}
+
+/// The kinds of ed25519 keys, each with its own address prefixes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyKind {
+    /// Factoid addresses, FA... and Fs...
+    Factoid,
+    /// Entry credit addresses, EC... and Es...
+    EntryCredit,
+    /// Identity keys, idpub... and idsec...
+    Identity,
+}
+
+impl KeyKind {
+    pub fn public_prefix(self) -> &'static [u8] {
+        match self {
+            KeyKind::Factoid => &FCT_PUBLIC_PREFIX,
+            KeyKind::EntryCredit => &EC_PUBLIC_PREFIX,
+            KeyKind::Identity => &ID_PUBLIC_PREFIX,
+        }
+    }
+
+    pub fn secret_prefix(self) -> &'static [u8] {
+        match self {
+            KeyKind::Factoid => &FCT_SECRET_PREFIX,
+            KeyKind::EntryCredit => &EC_SECRET_PREFIX,
+            KeyKind::Identity => &ID_SECRET_PREFIX,
+        }
+    }
+
+    /// The kind of a secret address, from its human readable prefix
+    pub fn of_secret(secret: &str) -> Option<KeyKind> {
+        match secret {
+            s if s.starts_with("Fs") => Some(KeyKind::Factoid),
+            s if s.starts_with("Es") => Some(KeyKind::EntryCredit),
+            s if s.starts_with("idsec") => Some(KeyKind::Identity),
+            _ => None,
+        }
+    }
+
+    /// The kind of a public address, from its human readable prefix
+    pub fn of_public(public: &str) -> Option<KeyKind> {
+        match public {
+            s if s.starts_with("FA") => Some(KeyKind::Factoid),
+            s if s.starts_with("EC") => Some(KeyKind::EntryCredit),
+            s if s.starts_with("idpub") => Some(KeyKind::Identity),
+            _ => None,
+        }
+    }
+}
+
+/// Double sha256 of the type 1 RCD of an ed25519 public key, the key encoded
+/// in public factoid addresses
+pub fn rcd_hash(public_key: &[u8; 32]) -> [u8; 32] {
+    let rcd = [&[1u8][..], &public_key[..]].concat();
+    Sha256::digest(Sha256::digest(rcd)).into()
+}
+
+/// The public address of an ed25519 public key
+pub fn public_address(kind: KeyKind, public_key: &[u8; 32]) -> String {
+    match kind {
+        KeyKind::Factoid => encode_address(kind.public_prefix(), &rcd_hash(public_key)),
+        _ => encode_address(kind.public_prefix(), public_key),
+    }
+}
+
+/// Derives the public address of a secret factoid, entry credit or identity
+/// address without the wallet.
+///
+/// # Example
+/// ```
+/// use factom::*;
+///
+/// let secret = "Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk";
+/// let public = address::public_from_secret(secret).unwrap();
+/// assert_eq!(public, "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK");
+/// ```
+pub fn public_from_secret(secret: &str) -> Result<String> {
+    let kind = KeyKind::of_secret(secret).ok_or("Unknown secret address prefix")?;
+    let seed = decode_address(kind.secret_prefix(), secret)?;
+    let key = SigningKey::from_bytes(&seed);
+    Ok(public_address(kind, key.verifying_key().as_bytes()))
+}
+
+/// First four bytes of the double sha256 of the prefixed key
+fn checksum(bytes: &[u8]) -> [u8; 4] {
+    let hash = Sha256::digest(Sha256::digest(bytes));
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn encode_base58(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    // Base 58 digits, least significant first
+    let mut digits: Vec<u8> = Vec::new();
+    for byte in &bytes[zeros..] {
+        let mut carry = u32::from(*byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let leading = std::iter::repeat_n('1', zeros);
+    let rest = digits.iter().rev().map(|d| BASE58[*d as usize] as char);
+    leading.chain(rest).collect()
+}
+
+fn decode_base58(encoded: &str) -> Option<Vec<u8>> {
+    let zeros = encoded.bytes().take_while(|c| *c == b'1').count();
+    // Bytes, least significant first
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in encoded.bytes().skip(zeros) {
+        let mut carry = BASE58.iter().position(|d| *d == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    bytes.extend(std::iter::repeat_n(0, zeros));
+    bytes.reverse();
+    Some(bytes)
+}
+
 /// address function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Address {
     pub public: String,
-    pub secret: String,
+    pub secret: Secret,
 }
 
 /// all-addresses function
@@ -102,3 +271,50 @@ pub struct AllAddresses {
 pub struct RemoveAddress {
     pub success: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_encoding() {
+        let fct = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+        let rcd_hash = decode_address(&FCT_PUBLIC_PREFIX, fct).unwrap();
+        assert_eq!(bs58::decode(fct).into_vec().unwrap()[2..34], rcd_hash);
+        assert_eq!(encode_address(&FCT_PUBLIC_PREFIX, &rcd_hash), fct);
+
+        let secret = "Fs3E9gV6DXsYzf7Fqx1fVBQPQXV695eP3k5XbmHEZVRLkMdD9qCK";
+        let key = decode_address(&FCT_SECRET_PREFIX, secret).unwrap();
+        assert_eq!(encode_address(&FCT_SECRET_PREFIX, &key), secret);
+
+        assert!(decode_address(&EC_PUBLIC_PREFIX, fct).is_err());
+        let corrupted = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1R";
+        assert!(decode_address(&FCT_PUBLIC_PREFIX, corrupted).is_err());
+        assert!(decode_address(&FCT_PUBLIC_PREFIX, "FA0").is_err());
+    }
+
+    #[test]
+    fn public_addresses() {
+        let pairs = [
+            (
+                "Fs3E9gV6DXsYzf7Fqx1fVBQPQXV695eP3k5XbmHEZVRLkMdD9qCK",
+                "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q",
+            ),
+            (
+                "Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk",
+                "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK",
+            ),
+            (
+                "idsec2rWrfNTD1x9HPPesA3fz8dmMNZdjmSBULHx8VTXE1J4D9icmAK",
+                "idpub2g25nPNZ2kf6KGTjthYdHT3nykDbwEUEPyGJ52fo55SHwtAvLA",
+            ),
+        ];
+        for (secret, public) in pairs.iter() {
+            assert_eq!(public_from_secret(secret).unwrap(), *public);
+            assert_eq!(KeyKind::of_secret(secret), KeyKind::of_public(public));
+        }
+        assert!(
+            public_from_secret("FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q").is_err()
+        );
+    }
+}
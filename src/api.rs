@@ -1,25 +1,38 @@
 //! The api client module holds the Factom struct and request client.
 use super::*;
+use crate::cache::{Cache, CacheStats};
+use crate::limit::{Endpoint, LimitMetrics, Limiters, RateLimit};
+use crate::transport::{default_transport, Transport};
 use constants::*;
 use url::Url;
 
 use http::Uri;
+use std::cell::Cell;
 use std::num::Wrapping;
 
 /// Main struct from which API requests are built
-/// * client holds the hyper http client with a https connector
+/// * transport sends the requests, by default the one enabled by the cargo
+///   features, see the [transport](../transport/index.html) module
 /// * factomd/walletd/debug hold the request builders to which a json body
 /// is added
 /// * uri is the current uri locations
 /// * id is the json-rpc id field as a wrapped usize,
 /// it can be incremented without risking overflow
+/// * cache optionally holds responses to immutable requests, shared by clones
+/// * saved_height is the highest directory block height the node reported as
+/// saved, blocks requested by height are only cached up to it
+/// * limiters hold the optional rate limit of each endpoint, shared by clones
 #[derive(Debug)]
 pub struct Factom {
-    pub client: HttpsClient,
+    pub transport: Rc<dyn Transport>,
     pub factomd_uri: Rc<Uri>,
     pub walletd_uri: Rc<Uri>,
     pub debug_uri: Rc<Uri>,
     pub id: Wrapping<usize>,
+    pub cache: Option<Rc<dyn Cache>>,
+    cache_stats: Rc<Cell<CacheStats>>,
+    pub(crate) saved_height: Rc<Cell<i64>>,
+    pub(crate) limiters: Limiters,
 }
 
 impl Factom {
@@ -40,13 +53,7 @@ impl Factom {
         let factomd_uri = parse_uri(FACTOMD_DEFAULT);
         let walletd_uri = parse_uri(WALLETD_DEFAULT);
         let debug_uri = parse_debug_uri(FACTOMD_DEFAULT);
-        Factom {
-            client: new_client(),
-            factomd_uri,
-            walletd_uri,
-            debug_uri,
-            id: Wrapping(ID),
-        }
+        Factom::with_uris(factomd_uri, walletd_uri, debug_uri)
     }
 
     /// Creates a factom struct using open node for factomd and a local wallet in
@@ -58,13 +65,7 @@ impl Factom {
         let factomd_uri = parse_uri(OPENNODE_URI);
         let walletd_uri = parse_uri(WALLETD_DEFAULT);
         let debug_uri = parse_debug_uri(OPENNODE_URI);
-        Factom {
-            client: new_client(),
-            factomd_uri,
-            walletd_uri,
-            debug_uri,
-            id: Wrapping(ID),
-        }
+        Factom::with_uris(factomd_uri, walletd_uri, debug_uri)
     }
 
     /// Creates a factom struct using the testnet open node for factomd and a
@@ -76,13 +77,7 @@ impl Factom {
         let factomd_uri = parse_uri(DEV_OPENNODE_URI);
         let walletd_uri = parse_uri(WALLETD_DEFAULT);
         let debug_uri = parse_debug_uri(DEV_OPENNODE_URI);
-        Factom {
-            client: new_client(),
-            factomd_uri,
-            walletd_uri,
-            debug_uri,
-            id: Wrapping(ID),
-        }
+        Factom::with_uris(factomd_uri, walletd_uri, debug_uri)
     }
 
     /// Creates a factom struct the provided custom hosts. Debug functions will
@@ -100,15 +95,81 @@ impl Factom {
         let factomd_uri = parse_uri(factomd);
         let walletd_uri = parse_uri(walletd);
         let debug_uri = parse_debug_uri(factomd);
+        Factom::with_uris(factomd_uri, walletd_uri, debug_uri)
+    }
+
+    fn with_uris(factomd_uri: Rc<Uri>, walletd_uri: Rc<Uri>, debug_uri: Rc<Uri>) -> Factom {
         Factom {
-            client: new_client(),
+            transport: default_transport(),
             factomd_uri,
             walletd_uri,
             debug_uri,
             id: Wrapping(ID),
+            cache: None,
+            cache_stats: Rc::new(Cell::new(CacheStats::default())),
+            saved_height: Rc::new(Cell::new(-1)),
+            limiters: Limiters::default(),
         }
     }
 
+    /// Sends requests through the given transport instead of the default one,
+    /// see the [transport](../transport/index.html) module.
+    ///
+    /// # Example
+    /// ```
+    /// use factom::*;
+    /// use factom::transport::HyperTransport;
+    ///
+    /// let client = Factom::new().with_transport(HyperTransport::new());
+    /// ```
+    pub fn with_transport(mut self, transport: impl Transport + 'static) -> Factom {
+        self.transport = Rc::new(transport);
+        self
+    }
+
+    /// Caches the responses of immutable requests, see the
+    /// [cache](../cache/index.html) module.
+    ///
+    /// # Example
+    /// ```
+    /// use factom::*;
+    /// use factom::cache::MemoryCache;
+    ///
+    /// let client = Factom::open_node().with_cache(MemoryCache::new(1000));
+    /// assert_eq!(client.cache_stats().hits, 0);
+    /// ```
+    pub fn with_cache(mut self, cache: impl Cache + 'static) -> Factom {
+        self.cache = Some(Rc::new(cache));
+        self
+    }
+
+    /// Returns the cache hits and misses of this client and its clones
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache_stats.get()
+    }
+
+    /// Limits the requests sent to an endpoint, see the
+    /// [limit](../limit/index.html) module.
+    pub fn with_rate_limit(mut self, endpoint: Endpoint, limit: RateLimit) -> Factom {
+        self.limiters.set(endpoint, limit);
+        self
+    }
+
+    /// Returns the request and queue wait metrics of a rate limited endpoint
+    pub fn rate_limit_metrics(&self, endpoint: Endpoint) -> Option<LimitMetrics> {
+        self.limiters.get(endpoint).map(|limiter| limiter.metrics())
+    }
+
+    pub(crate) fn record_cache_lookup(&self, hit: bool) {
+        let mut stats = self.cache_stats.get();
+        if hit {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+        self.cache_stats.set(stats);
+    }
+
     /// Increments the json-rpc id by one. Will wrap around to zero if it goes
     /// over [std::usize::MAX](https://doc.rust-lang.org/std/usize/constant.MAX.html)
     pub fn increment_id(mut self) {
@@ -121,23 +182,18 @@ impl Factom {
     }
 }
 
-/// Creates a https client, this is placed in the Factom struct and is responsible
-/// for making network requests
-fn new_client() -> HttpsClient {
-    let connector = HttpsConnector::new();
-    let client = Client::builder().build::<_, hyper::Body>(connector);
-    Rc::new(client)
-}
-
 impl Clone for Factom {
     fn clone(&self) -> Self {
-        let client = Rc::clone(&self.client);
         Factom {
-            client,
+            transport: Rc::clone(&self.transport),
             factomd_uri: Rc::clone(&self.factomd_uri),
             walletd_uri: Rc::clone(&self.walletd_uri),
             debug_uri: Rc::clone(&self.debug_uri),
             id: self.id,
+            cache: self.cache.clone(),
+            cache_stats: Rc::clone(&self.cache_stats),
+            saved_height: Rc::clone(&self.saved_height),
+            limiters: self.limiters.clone(),
         }
     }
 }
@@ -60,7 +60,11 @@ pub async fn ablock_by_height(
 ///    assert_eq!(response.result.ablock.header.prevbackrefhash, prevbackrefhash);
 /// }
 /// ```
-pub async fn admin_block(api: &Factom, keymr: &str) -> Result<ApiResponse<ABlockResult>> {
+pub async fn admin_block(
+    api: &Factom,
+    keymr: impl IntoHash<KeyMr>,
+) -> Result<ApiResponse<ABlockResult>> {
+    let keymr = keymr.into_hash()?;
     let mut req = ApiRequest::new("admin-block");
     req.params.insert("keymr".to_string(), json!(keymr));
     let response = factomd_call(api, req).await;
@@ -150,7 +154,11 @@ pub async fn dblock_by_height(
 ///    assert_eq!(response.result.header.prevblockkeymr, prevblockkeymr);
 /// }
 /// ```
-pub async fn directory_block(api: &Factom, keymr: &str) -> Result<ApiResponse<DBlock>> {
+pub async fn directory_block(
+    api: &Factom,
+    keymr: impl IntoHash<KeyMr>,
+) -> Result<ApiResponse<DBlock>> {
+    let keymr = keymr.into_hash()?;
     let mut req = ApiRequest::new("directory-block");
     req.params.insert("keymr".to_string(), json!(keymr));
     let response = factomd_call(api, req).await;
@@ -224,7 +232,8 @@ pub async fn ecblock_by_height(
 /// }
 
 /// ```
-pub async fn entry_block(api: &Factom, keymr: &str) -> Result<ApiResponse<EBlock>> {
+pub async fn entry_block(api: &Factom, keymr: impl IntoHash<KeyMr>) -> Result<ApiResponse<EBlock>> {
+    let keymr = keymr.into_hash()?;
     let mut req = ApiRequest::new("entry-block");
     req.params.insert("keymr".to_string(), json!(keymr));
     let response = factomd_call(api, req).await;
@@ -248,7 +257,11 @@ pub async fn entry_block(api: &Factom, keymr: &str) -> Result<ApiResponse<EBlock
 ///    assert_eq!(response.result.ecblock.header.bodyhash, bodyhash);
 /// }
 /// ```
-pub async fn entry_credit_block(api: &Factom, keymr: &str) -> Result<ApiResponse<EcBlockResult>> {
+pub async fn entry_credit_block(
+    api: &Factom,
+    keymr: impl IntoHash<KeyMr>,
+) -> Result<ApiResponse<EcBlockResult>> {
+    let keymr = keymr.into_hash()?;
     let mut req = ApiRequest::new("entrycredit-block");
     req.params.insert("keymr".to_string(), json!(keymr));
     let response = factomd_call(api, req).await;
@@ -271,7 +284,11 @@ pub async fn entry_credit_block(api: &Factom, keymr: &str) -> Result<ApiResponse
 ///    assert_eq!(response.result.fblock.bodymr, bodymr);
 /// }
 /// ```
-pub async fn factoid_block(api: &Factom, keymr: &str) -> Result<ApiResponse<FBlockResult>> {
+pub async fn factoid_block(
+    api: &Factom,
+    keymr: impl IntoHash<KeyMr>,
+) -> Result<ApiResponse<FBlockResult>> {
+    let keymr = keymr.into_hash()?;
     let mut req = ApiRequest::new("factoid-block");
     req.params.insert("keymr".to_string(), json!(keymr));
     let response = factomd_call(api, req).await;
@@ -313,8 +330,8 @@ pub struct ABlockHeightResult {
 pub struct AblockHeight {
     pub header: Header,
     pub abentries: Vec<ABHeightentry>,
-    pub backreferencehash: String,
-    pub lookuphash: String,
+    pub backreferencehash: hash::Hash,
+    pub lookuphash: KeyMr,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -336,20 +353,20 @@ pub struct ABlockResult {
 pub struct Ablock {
     pub header: Header,
     pub abentries: Vec<Abentry>,
-    pub backreferencehash: String,
-    pub lookuphash: String,
+    pub backreferencehash: hash::Hash,
+    pub lookuphash: KeyMr,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Header {
-    pub prevbackrefhash: String,
+    pub prevbackrefhash: hash::Hash,
     pub dbheight: usize,
     pub headerexpansionsize: usize,
     pub headerexpansionarea: String,
     pub messagecount: usize,
     pub bodysize: usize,
-    pub adminchainid: String,
-    pub chainid: String,
+    pub adminchainid: ChainId,
+    pub chainid: ChainId,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -370,7 +387,7 @@ pub struct Prevdbsig {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Anchor {
     pub directoryblockheight: usize,
-    pub directoryblockkeymr: String,
+    pub directoryblockkeymr: KeyMr,
     #[serde(default)]
     pub bitcoin: Bitcoin,
     pub ethereum: Ethereuem,
@@ -413,27 +430,27 @@ pub struct DBlockHeightResult {
 pub struct DblockHeight {
     pub header: DBlockHeightHeader,
     pub dbentries: Vec<Dbentry>,
-    pub dbhash: String,
-    pub keymr: String,
+    pub dbhash: hash::Hash,
+    pub keymr: KeyMr,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DBlockHeightHeader {
     pub version: u8,
     pub networkid: usize,
-    pub bodymr: String,
-    pub prevkeymr: String,
-    pub prevfullhash: String,
+    pub bodymr: hash::Hash,
+    pub prevkeymr: KeyMr,
+    pub prevfullhash: hash::Hash,
     pub timestamp: usize,
     pub dbheight: usize,
     pub blockcount: usize,
-    pub chainid: String,
+    pub chainid: ChainId,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Dbentry {
-    pub chainid: String,
-    pub keymr: String,
+    pub chainid: ChainId,
+    pub keymr: KeyMr,
 }
 
 /// directory block function
@@ -445,21 +462,21 @@ pub struct DBlock {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DBlockHeader {
-    pub prevblockkeymr: String,
+    pub prevblockkeymr: KeyMr,
     pub sequencenumber: usize,
     pub timestamp: usize,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entryblocklist {
-    pub chainid: String,
-    pub keymr: String,
+    pub chainid: ChainId,
+    pub keymr: KeyMr,
 }
 
 /// directory-block-head function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DBlockHead {
-    pub keymr: String,
+    pub keymr: KeyMr,
 }
 
 /// ecblock-by-height function
@@ -477,15 +494,15 @@ pub struct EcBlock {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ECHeightHeader {
-    pub bodyhash: String,
-    pub prevheaderhash: String,
-    pub prevfullhash: String,
+    pub bodyhash: hash::Hash,
+    pub prevheaderhash: KeyMr,
+    pub prevfullhash: hash::Hash,
     pub dbheight: usize,
     pub headerexpansionarea: String,
     pub objectcount: usize,
     pub bodysize: usize,
-    pub chainid: String,
-    pub ecchainid: String,
+    pub chainid: ChainId,
+    pub ecchainid: ChainId,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -498,7 +515,7 @@ pub struct Entry {
     pub serverindexnumber: Option<u8>,
     pub version: Option<u8>,
     pub millitime: Option<String>,
-    pub entryhash: Option<String>,
+    pub entryhash: Option<EntryHash>,
     pub credits: Option<usize>,
     pub ecpubkey: Option<String>,
     pub sig: Option<String>,
@@ -516,8 +533,8 @@ pub struct EBlock {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EBlockHeader {
     pub blocksequencenumber: usize,
-    pub chainid: String,
-    pub prevkeymr: String,
+    pub chainid: ChainId,
+    pub prevkeymr: KeyMr,
     pub timestamp: usize,
     pub dbheight: usize,
 }
@@ -525,7 +542,7 @@ pub struct EBlockHeader {
 /// entry-block function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entrylist {
-    pub entryhash: String,
+    pub entryhash: EntryHash,
     pub timestamp: usize,
 }
 
@@ -544,15 +561,15 @@ pub struct Ecblock {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EcBlockHeader {
-    pub bodyhash: String,
-    pub prevheaderhash: String,
-    pub prevfullhash: String,
+    pub bodyhash: hash::Hash,
+    pub prevheaderhash: KeyMr,
+    pub prevfullhash: hash::Hash,
     pub dbheight: usize,
     pub headerexpansionarea: String,
     pub objectcount: usize,
     pub bodysize: usize,
-    pub chainid: String,
-    pub ecchainid: String,
+    pub chainid: ChainId,
+    pub ecchainid: ChainId,
 }
 
 // factoid-block and fblock-by-height functions
@@ -564,20 +581,20 @@ pub struct FBlockResult {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Fblock {
-    pub bodymr: String,
-    pub prevkeymr: String,
-    pub prevledgerkeymr: String,
+    pub bodymr: hash::Hash,
+    pub prevkeymr: KeyMr,
+    pub prevledgerkeymr: KeyMr,
     pub exchrate: usize,
     pub dbheight: usize,
     pub transactions: Vec<Transaction>,
-    pub chainid: String,
-    pub keymr: String,
-    pub ledgerkeymr: String,
+    pub chainid: ChainId,
+    pub keymr: KeyMr,
+    pub ledgerkeymr: KeyMr,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transaction {
-    pub txid: String,
+    pub txid: TxId,
     pub blockheight: usize,
     pub millitimestamp: usize,
     pub inputs: Vec<Input>,
@@ -0,0 +1,340 @@
+//! A synchronous client for use outside of async code.
+//!
+//! [blocking::Factom](struct.Factom.html) owns a single tokio runtime and
+//! exposes the api functions as methods that block until the response
+//! arrives, so scripts and command line tools don't need to set up a runtime or
+//! use async. The methods share the names and arguments of the async functions,
+//! minus the client.
+//!
+//! The blocking client must not be used from within an async context, where
+//! blocking the thread would stall the runtime driving it.
+//!
+//! # Example
+//! ```no_run
+//! use factom::blocking;
+//!
+//! let client = blocking::Factom::open_node().expect("Starting runtime");
+//! let heights = client.heights().expect("Fetching heights");
+//! let dblock = client
+//!     .dblock_by_height(heights.result.directoryblockheight as u32)
+//!     .expect("Fetching directory block");
+//! dbg!(dblock);
+//! ```
+use super::*;
+use std::cell::RefCell;
+
+/// Synchronous client wrapping an async [Factom](../api/struct.Factom.html)
+/// client and the runtime used to drive it
+pub struct Factom {
+    client: api::Factom,
+    runtime: RefCell<Runtime>,
+}
+
+impl Factom {
+    /// Wraps an async client, starting a runtime for it
+    pub fn from_client(client: api::Factom) -> Result<Factom> {
+        let runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()?;
+        Ok(Factom {
+            client,
+            runtime: RefCell::new(runtime),
+        })
+    }
+
+    /// See [Factom::local_node](../api/struct.Factom.html#method.local_node)
+    pub fn local_node() -> Result<Factom> {
+        Factom::from_client(api::Factom::local_node())
+    }
+
+    /// See [Factom::open_node](../api/struct.Factom.html#method.open_node)
+    pub fn open_node() -> Result<Factom> {
+        Factom::from_client(api::Factom::open_node())
+    }
+
+    /// See [Factom::testnet_node](../api/struct.Factom.html#method.testnet_node)
+    pub fn testnet_node() -> Result<Factom> {
+        Factom::from_client(api::Factom::testnet_node())
+    }
+
+    /// See [Factom::custom_node](../api/struct.Factom.html#method.custom_node)
+    pub fn custom_node(factomd: &str, walletd: &str) -> Result<Factom> {
+        Factom::from_client(api::Factom::custom_node(factomd, walletd))
+    }
+
+    /// The wrapped async client
+    pub fn client(&self) -> &api::Factom {
+        &self.client
+    }
+
+    /// Runs a future to completion on the client's runtime, for async
+    /// functions without a blocking method such as the stream helpers.
+    /// Panics if called from within the future it is running.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.borrow_mut().block_on(future)
+    }
+
+    /// Blocking version of
+    /// [compose::compose_chain](../compose/fn.compose_chain.html)
+    pub fn compose_chain<E, C>(
+        &self,
+        extids: Vec<E>,
+        content: C,
+        ecpub: &str,
+    ) -> Result<ApiResponse<compose::Compose>>
+    where
+        E: AsRef<[u8]>,
+        C: AsRef<[u8]>,
+    {
+        self.block_on(compose::compose_chain(&self.client, extids, content, ecpub))
+    }
+
+    /// Blocking version of
+    /// [compose::compose_entry](../compose/fn.compose_entry.html)
+    pub fn compose_entry<E, C>(
+        &self,
+        chainid: impl IntoHash<ChainId>,
+        extids: Vec<E>,
+        content: C,
+        ecpub: &str,
+    ) -> Result<ApiResponse<compose::Compose>>
+    where
+        E: AsRef<[u8]>,
+        C: AsRef<[u8]>,
+    {
+        let compose = compose::compose_entry(&self.client, chainid, extids, content, ecpub);
+        self.block_on(compose)
+    }
+
+    /// Blocking version of
+    /// [compose::compose_id_attribute](../compose/fn.compose_id_attribute.html)
+    #[allow(clippy::too_many_arguments)]
+    pub fn compose_id_attribute<T>(
+        &self,
+        receiver_chain: &str,
+        destination_chain: &str,
+        attributes: Vec<(T, T)>,
+        signer_key: &str,
+        signer_chainid: &str,
+        ecpub: &str,
+        force: bool,
+    ) -> Result<ApiResponse<compose::Compose>>
+    where
+        T: Serialize,
+    {
+        self.block_on(compose::compose_id_attribute(
+            &self.client,
+            receiver_chain,
+            destination_chain,
+            attributes,
+            signer_key,
+            signer_chainid,
+            ecpub,
+            force,
+        ))
+    }
+
+    /// Blocking version of [utils::create_entry](../utils/fn.create_entry.html)
+    pub fn create_entry<E, C>(
+        &self,
+        chainid: impl IntoHash<ChainId>,
+        ext_ids: Vec<E>,
+        content: C,
+        ec_pub: &str,
+    ) -> Result<utils::Submission>
+    where
+        E: AsRef<[u8]>,
+        C: AsRef<[u8]>,
+    {
+        self.block_on(utils::create_entry(
+            &self.client,
+            chainid,
+            ext_ids,
+            content,
+            ec_pub,
+        ))
+    }
+
+    /// Blocking version of [utils::create_chain](../utils/fn.create_chain.html)
+    pub fn create_chain<E, C>(
+        &self,
+        ext_ids: Vec<E>,
+        content: C,
+        ec_pub: &str,
+    ) -> Result<utils::Submission>
+    where
+        E: AsRef<[u8]>,
+        C: AsRef<[u8]>,
+    {
+        self.block_on(utils::create_chain(&self.client, ext_ids, content, ec_pub))
+    }
+}
+
+/// Generates a blocking method for each async api function, the function is
+/// called with the wrapped client followed by the method's arguments
+macro_rules! blocking_api {
+    ($($module:ident::$name:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        impl Factom {
+            $(
+                #[doc = concat!(
+                    "Blocking version of [", stringify!($module), "::", stringify!($name),
+                    "](../", stringify!($module), "/fn.", stringify!($name), ".html)"
+                )]
+                pub fn $name(&self, $($arg: $ty),*) -> Result<$ret> {
+                    self.block_on($module::$name(&self.client, $($arg),*))
+                }
+            )*
+        }
+    };
+}
+
+blocking_api! {
+    address::address(address: &str) -> ApiResponse<address::Address>;
+    address::all_addresses() -> ApiResponse<address::AllAddresses>;
+    address::remove_address(address: &str) -> ApiResponse<address::RemoveAddress>;
+    balance::entry_credit_balance(address: &str) -> ApiResponse<balance::Balance>;
+    balance::factoid_balance(address: &str) -> ApiResponse<balance::Balance>;
+    balance::multiple_ec_balances(addresses: Vec<&str>) -> ApiResponse<balance::Balances>;
+    balance::multiple_fct_balances(addresses: Vec<&str>) -> ApiResponse<balance::Balances>;
+    block::ablock_by_height(height: u32) -> ApiResponse<block::ABlockHeightResult>;
+    block::admin_block(keymr: impl IntoHash<KeyMr>) -> ApiResponse<block::ABlockResult>;
+    block::anchors(target: block::AnchorType) -> ApiResponse<block::Anchor>;
+    block::dblock_by_height(height: u32) -> ApiResponse<block::DBlockHeightResult>;
+    block::directory_block(keymr: impl IntoHash<KeyMr>) -> ApiResponse<block::DBlock>;
+    block::directory_block_head() -> ApiResponse<block::DBlockHead>;
+    block::ecblock_by_height(height: u32) -> ApiResponse<block::EBlockHeightResult>;
+    block::entry_block(keymr: impl IntoHash<KeyMr>) -> ApiResponse<block::EBlock>;
+    block::entry_credit_block(keymr: impl IntoHash<KeyMr>) -> ApiResponse<block::EcBlockResult>;
+    block::factoid_block(keymr: impl IntoHash<KeyMr>) -> ApiResponse<block::FBlockResult>;
+    block::fblock_by_height(height: u32) -> ApiResponse<block::FBlockResult>;
+    chain::chain_head(chainid: impl IntoHash<ChainId>) -> ApiResponse<chain::ChainHead>;
+    chain::commit_chain(message: &str) -> ApiResponse<chain::CommitChain>;
+    chain::reveal_chain(entry: &str) -> ApiResponse<chain::RevealChain>;
+    compose::compose_transaction(tx_name: &str) -> ApiResponse<compose::ComposeTx>;
+    compose::compose_id_attribute_endorsement(destination_chain: &str, entry_hash: &str, signer_key: &str, signer_chainid: &str, ecpub: &str, force: bool) -> ApiResponse<compose::Compose>;
+    compose::compose_id_chain(name: Vec<&str>, pubkeys: Vec<&str>, ecpub: &str, force: bool) -> ApiResponse<compose::Compose>;
+    compose::compose_id_key_replacement(chain_id: &str, old_key: &str, new_key: &str, signer_key: &str, ecpub: &str, force: bool) -> ApiResponse<compose::Compose>;
+    debug::holding_queue() -> ApiResponse<debug::HoldingQueue>;
+    debug::network_info() -> ApiResponse<debug::NetworkInfo>;
+    debug::predictive_fer() -> ApiResponse<debug::PredictiveFER>;
+    debug::audit_servers() -> ApiResponse<debug::AuditServers>;
+    debug::federated_servers() -> ApiResponse<debug::FederatedServers>;
+    debug::configuration() -> ApiResponse<debug::Configuration>;
+    debug::process_list() -> ApiResponse<debug::ProcessList>;
+    debug::authorities() -> ApiResponse<debug::Authorities>;
+    debug::reload_configuration() -> ApiResponse<debug::Configuration>;
+    debug::drop_rate() -> ApiResponse<debug::DropRate>;
+    debug::set_drop_rate(drop_rate: usize) -> ApiResponse<debug::DropRate>;
+    debug::delay() -> ApiResponse<debug::Delay>;
+    debug::set_delay(delay: usize) -> ApiResponse<debug::Delay>;
+    debug::summary() -> ApiResponse<debug::Summary>;
+    debug::messages() -> ApiResponse<debug::Messages>;
+    debug::wait_for_block(height: u32) -> ApiResponse<debug::WaitFor>;
+    debug::wait_for_minute(minute: u8) -> ApiResponse<debug::WaitFor>;
+    entry::commit_entry(message: &str) -> ApiResponse<entry::CommitEntry>;
+    entry::entry(hash: impl IntoHash<EntryHash>) -> ApiResponse<entry::Entry>;
+    entry::raw_data(hash: impl IntoHash<hash::Hash>) -> ApiResponse<entry::RawData>;
+    entry::pending_entries() -> ApiResponse<Vec<entry::PendingEntry>>;
+    entry::reveal_entry(entry: &str) -> ApiResponse<entry::RevealEntry>;
+    factomd::current_minute() -> ApiResponse<factomd::CurrentMinute>;
+    factomd::diagnostics() -> ApiResponse<factomd::Diagnostics>;
+    factomd::entry_credit_rate() -> ApiResponse<factomd::EcRate>;
+    factomd::heights() -> ApiResponse<factomd::Heights>;
+    factomd::properties() -> ApiResponse<factomd::Properties>;
+    factomd::receipt(hash: impl IntoHash<EntryHash>, includerawentry: bool) -> ApiResponse<factomd::Receipt>;
+    factomd::send_raw_message(msg: &str) -> ApiResponse<factomd::Receipt>;
+    generate::ec_address() -> ApiResponse<generate::Generate>;
+    generate::factoid_address() -> ApiResponse<generate::Generate>;
+    generate::identity_key() -> ApiResponse<generate::Generate>;
+    identity::all_id_keys() -> ApiResponse<identity::IdKeys>;
+    identity::active_id_keys(chainid: impl IntoHash<ChainId>, height: Option<usize>) -> ApiResponse<identity::ActiveIdKeys>;
+    identity::id_keys_at_height(chainid: impl IntoHash<ChainId>, height: usize) -> ApiResponse<identity::ActiveIdKeys>;
+    identity::remove_id_key(public: &str) -> ApiResponse<identity::RemoveIdKey>;
+    identity::id_key(public: &str) -> ApiResponse<identity::Key>;
+    import::import_addresses(addresses: Vec<&str>) -> ApiResponse<import::Addresses>;
+    import::import_identity_keys(keys: Vec<&str>) -> ApiResponse<import::Keys>;
+    import::import_koinify(phrase: &str) -> ApiResponse<import::Address>;
+    tx::ec_ack(hash: impl IntoHash<EntryHash>, chainid: impl IntoHash<ChainId>, full_transaction: Option<&str>) -> ApiResponse<tx::EntryAck>;
+    tx::commit_ack(txid: impl IntoHash<TxId>, full_transaction: Option<&str>) -> ApiResponse<tx::EntryAck>;
+    tx::fct_ack(hash: impl IntoHash<TxId>, full_transaction: Option<&str>) -> ApiResponse<tx::FactoidAck>;
+    tx::entry_ack(hash: impl IntoHash<EntryHash>) -> ApiResponse<tx::EntryAck>;
+    tx::factoid_ack(hash: impl IntoHash<TxId>) -> ApiResponse<tx::FactoidAck>;
+    tx::factoid_submit(transaction: &str) -> ApiResponse<tx::FctSubmit>;
+    tx::transaction(hash: impl IntoHash<TxId>) -> ApiResponse<tx::Transaction>;
+    tx::pending_transactions(address: Option<&str>) -> ApiResponse<Vec<tx::PendingTx>>;
+    tx::add_ec_output(txname: &str, address: &str, amount: u64) -> ApiResponse<tx::WalletTransaction>;
+    tx::add_fee(txname: &str, address: &str) -> ApiResponse<tx::WalletTransaction>;
+    tx::add_input(txname: &str, address: &str, amount: u64) -> ApiResponse<tx::WalletTransaction>;
+    tx::add_output(txname: &str, address: &str, amount: u64) -> ApiResponse<tx::WalletTransaction>;
+    tx::delete_transaction(tx_name: &str) -> ApiResponse<tx::WalletTransaction>;
+    tx::new_transaction(tx_name: &str) -> ApiResponse<tx::WalletTransaction>;
+    tx::sign_transaction(tx_name: &str) -> ApiResponse<tx::WalletTransaction>;
+    tx::sub_fee(tx_name: &str, address: &str) -> ApiResponse<tx::WalletTransaction>;
+    tx::tmp_transactions() -> ApiResponse<tx::TmpTransactions>;
+    tx::transactions(filter: tx::SearchBy<'_>) -> ApiResponse<tx::Transactions>;
+    tx::send_fct(from: &str, to: &str, amount: u64) -> ApiResponse<tx::FctSubmit>;
+    tx::buy_ec(from: &str, ec_address: &str, amount: u64) -> ApiResponse<tx::FctSubmit>;
+    utils::create_id_chain(name: Vec<&str>, pubkeys: Vec<&str>, ec_pub: &str) -> utils::Submission;
+    utils::submit_entry(compose: &compose::Compose, options: &utils::SubmitOptions) -> utils::Submission;
+    utils::submit_chain(compose: &compose::Compose, options: &utils::SubmitOptions) -> utils::Submission;
+    utils::traverse_chain(chainid: impl IntoHash<ChainId>, depth: Option<usize>) -> Vec<entry::Entry>;
+    validate::validate_headers(checkpoint: &mut validate::Checkpoint, to_height: u32) -> ();
+    walletd::wallet_backup() -> ApiResponse<walletd::WalletBackup>;
+    walletd::wallet_balances() -> ApiResponse<walletd::WalletBalances>;
+    walletd::unlock_wallet(passphrase: &str, timeout: usize) -> ApiResponse<walletd::UnlockWallet>;
+    walletd::wallet_height() -> ApiResponse<walletd::Height>;
+    walletd::wallet_properties() -> ApiResponse<walletd::Properties>;
+    walletd::sign_data(signer: &str, data: &[u8]) -> ApiResponse<walletd::SignData>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockNode;
+
+    const CHAIN: &str = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+
+    #[test]
+    fn reuses_runtime() {
+        let client = Factom::local_node().unwrap();
+        assert_eq!(client.block_on(async { 1 }), 1);
+        assert_eq!(client.block_on(async { 2 }), 2);
+    }
+
+    #[test]
+    fn calls_through_runtime() {
+        let node = MockNode::new();
+        node.respond("heights", json!({"leaderheight": 11}))
+            .respond_error("chain-head", -32009, "Missing Chain Head")
+            .fail("entry-credit-rate", "connection reset");
+        let client = Factom::from_client(node.client()).unwrap();
+
+        let heights = client.heights().unwrap();
+        assert_eq!(heights.result.leaderheight, 11);
+
+        let head = client.chain_head(CHAIN).unwrap();
+        assert_eq!(head.error.code, -32009);
+        let error = head.into_result().unwrap_err();
+        assert!(error.to_string().contains("Missing Chain Head"));
+
+        let error = client.entry_credit_rate().unwrap_err();
+        assert_eq!(error.to_string(), "connection reset");
+        assert!(client.chain_head("not a chain id").is_err());
+    }
+
+    #[test]
+    fn compose_id_attribute() {
+        let node = MockNode::new();
+        let client = Factom::from_client(node.client()).unwrap();
+        let attributes = vec![("email", "hello@example.com")];
+        let ecpub = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+        let compose = client
+            .compose_id_attribute(CHAIN, CHAIN, attributes, "idpub", CHAIN, ecpub, false)
+            .unwrap();
+        assert!(compose.success());
+        let request = &node.requests_for("compose-identity-attribute")[0];
+        assert_eq!(request.params["receiver-chainid"], CHAIN);
+        assert_eq!(request.params["ecpub"], ecpub);
+    }
+}
@@ -0,0 +1,321 @@
+//! Caching of responses that can never change.
+//!
+//! Blocks addressed by keymr or by a saved height, entries and raw data are
+//! immutable, so once a call for them succeeds its response can be reused. A
+//! client given a cache with
+//! [Factom::with_cache](../api/struct.Factom.html#method.with_cache) serves
+//! those calls from the cache, every other call always goes to the node.
+//!
+//! # Example
+//! ```no_run
+//! use factom::*;
+//! use factom::cache::{DiskCache, MemoryCache, TieredCache};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!   let disk = DiskCache::open("factom-cache").expect("Opening cache");
+//!   let client = Factom::open_node().with_cache(TieredCache::new(MemoryCache::new(1000), disk));
+//!   let keymr = "7c55e6d5e8d5b9b2bd4da1bab2ff8e04e78bdb2e7a4e4a9ab2e3d84bf59e9d7b";
+//!   block::entry_block(&client, keymr).await.unwrap();
+//!   block::entry_block(&client, keymr).await.unwrap();
+//!   assert_eq!(client.cache_stats().hits, 1);
+//! }
+//! ```
+use super::*;
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Methods whose successful responses never change
+pub const IMMUTABLE_METHODS: &[&str] = &[
+    "ablock-by-height",
+    "admin-block",
+    "dblock-by-height",
+    "directory-block",
+    "ecblock-by-height",
+    "entry",
+    "entry-block",
+    "entrycredit-block",
+    "factoid-block",
+    "fblock-by-height",
+    "raw-data",
+];
+
+/// Methods addressing a block by height, their responses only stop changing
+/// once the node has saved that height
+pub const BY_HEIGHT_METHODS: &[&str] = &[
+    "ablock-by-height",
+    "dblock-by-height",
+    "ecblock-by-height",
+    "fblock-by-height",
+];
+
+/// Storage for cached responses, keyed by the node, method and parameters of
+/// the request. Implementations are expected to be best effort, a failure to
+/// store a response should simply make a later lookup miss.
+pub trait Cache: fmt::Debug {
+    /// Returns the response body stored for the key
+    fn get(&self, key: &str) -> Option<Vec<u8>>;
+    /// Stores a response body
+    fn insert(&self, key: &str, body: &[u8]);
+}
+
+/// Number of cache lookups served from the cache and sent to the node
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Returns the cache key for a request if its response is immutable
+pub(crate) fn cache_key(uri: &http::Uri, req: &ApiRequest) -> Option<String> {
+    if !IMMUTABLE_METHODS.contains(&req.method.as_str()) {
+        return None;
+    }
+    // Sorted so the key doesn't depend on the params' hash map order
+    let params: BTreeMap<_, _> = req.params.iter().collect();
+    let params = serde_json::to_string(&params).ok()?;
+    Some(format!("{} {} {}", uri, req.method, params))
+}
+
+/// Returns the height requested by a by-height method
+pub(crate) fn requested_height(req: &ApiRequest) -> Option<i64> {
+    if !BY_HEIGHT_METHODS.contains(&req.method.as_str()) {
+        return None;
+    }
+    req.params.get("height").and_then(|height| height.as_i64())
+}
+
+/// Returns the saved directory block height from a heights response body
+pub(crate) fn saved_height(body: &[u8]) -> Option<i64> {
+    let json: serde_json::Value = serde_json::from_slice(body).ok()?;
+    json["result"]["directoryblockheight"].as_i64()
+}
+
+/// Replaces the json-rpc id of a cached response body with the id of the
+/// request it now answers
+pub(crate) fn with_id(body: Vec<u8>, id: usize) -> Vec<u8> {
+    let mut json: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(json) => json,
+        Err(_) => return body,
+    };
+    json["id"] = json!(id);
+    serde_json::to_vec(&json).unwrap_or(body)
+}
+
+/// Whether a response body holds a result rather than an error, only results
+/// are cached
+pub(crate) fn is_result(body: &[u8]) -> bool {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(json) => json.get("error").is_none() && !json["result"].is_null(),
+        Err(_) => false,
+    }
+}
+
+/// An in-memory cache evicting the least recently used response once it holds
+/// `capacity` responses
+#[derive(Debug)]
+pub struct MemoryCache {
+    capacity: usize,
+    inner: RefCell<Lru>,
+}
+
+#[derive(Debug, Default)]
+struct Lru {
+    tick: u64,
+    /// key -> (last use, body)
+    entries: HashMap<String, (u64, Vec<u8>)>,
+    /// last use -> key
+    order: BTreeMap<u64, String>,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize) -> MemoryCache {
+        MemoryCache {
+            capacity,
+            inner: RefCell::new(Lru::default()),
+        }
+    }
+
+    /// Number of responses held
+    pub fn len(&self) -> usize {
+        self.inner.borrow().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut lru = self.inner.borrow_mut();
+        lru.tick += 1;
+        let tick = lru.tick;
+        let (used, body) = lru.entries.get_mut(key)?;
+        let previous = std::mem::replace(used, tick);
+        let body = body.clone();
+        lru.order.remove(&previous);
+        lru.order.insert(tick, key.to_string());
+        Some(body)
+    }
+
+    fn insert(&self, key: &str, body: &[u8]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut lru = self.inner.borrow_mut();
+        lru.tick += 1;
+        let tick = lru.tick;
+        if let Some((previous, _)) = lru.entries.insert(key.to_string(), (tick, body.to_vec())) {
+            lru.order.remove(&previous);
+        }
+        lru.order.insert(tick, key.to_string());
+        while lru.entries.len() > self.capacity {
+            let oldest = *lru.order.keys().next().expect("Non empty cache");
+            let key = lru.order.remove(&oldest).expect("Oldest key");
+            lru.entries.remove(&key);
+        }
+    }
+}
+
+/// An on-disk cache storing each response in its own file, named after the
+/// hash of its key
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Opens the cache directory, creating it if needed
+    pub fn open(dir: impl AsRef<Path>) -> Result<DiskCache> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(DiskCache { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(hash::encode_hex(&Sha256::digest(key.as_bytes())))
+    }
+}
+
+impl Cache for DiskCache {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(key)).ok()
+    }
+
+    fn insert(&self, key: &str, body: &[u8]) {
+        let path = self.path(key);
+        // Written under a temporary name first so readers never see a
+        // partially written response
+        let tmp = path.with_extension("tmp");
+        if fs::write(&tmp, body).is_ok() {
+            let _ = fs::rename(&tmp, &path);
+        }
+    }
+}
+
+/// Looks up responses in memory first, then on disk, keeping responses read
+/// from disk in memory
+#[derive(Debug)]
+pub struct TieredCache<M, D> {
+    memory: M,
+    disk: D,
+}
+
+impl<M: Cache, D: Cache> TieredCache<M, D> {
+    pub fn new(memory: M, disk: D) -> TieredCache<M, D> {
+        TieredCache { memory, disk }
+    }
+}
+
+impl<M: Cache, D: Cache> Cache for TieredCache<M, D> {
+    fn get(&self, key: &str) -> Option<Vec<u8>> {
+        if let Some(body) = self.memory.get(key) {
+            return Some(body);
+        }
+        let body = self.disk.get(key)?;
+        self.memory.insert(key, &body);
+        Some(body)
+    }
+
+    fn insert(&self, key: &str, body: &[u8]) {
+        self.memory.insert(key, body);
+        self.disk.insert(key, body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockNode;
+    use futures::executor::block_on;
+
+    #[test]
+    fn lru_eviction() {
+        let cache = MemoryCache::new(2);
+        cache.insert("a", b"1");
+        cache.insert("b", b"2");
+        assert_eq!(cache.get("a"), Some(b"1".to_vec()));
+        cache.insert("c", b"3");
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(b"1".to_vec()));
+        assert_eq!(cache.get("c"), Some(b"3".to_vec()));
+    }
+
+    #[test]
+    fn immutable_requests() {
+        let uri = http::Uri::from_static("http://localhost:8088/v2");
+        let mut req = ApiRequest::new("entry-block");
+        req.params.insert("keymr".to_string(), json!("ab"));
+        assert!(cache_key(&uri, &req).is_some());
+        assert!(cache_key(&uri, &ApiRequest::new("heights")).is_none());
+        assert_eq!(requested_height(&req), None);
+        let mut req = ApiRequest::new("dblock-by-height");
+        req.params.insert("height".to_string(), json!(10));
+        assert_eq!(requested_height(&req), Some(10));
+        assert!(is_result(br#"{"jsonrpc":"2.0","id":0,"result":{"a":1}}"#));
+        assert!(!is_result(
+            br#"{"jsonrpc":"2.0","id":0,"error":{"code":-32009,"message":"Missing Chain Head"}}"#
+        ));
+    }
+
+    #[test]
+    fn rewrites_id() {
+        let body = with_id(br#"{"jsonrpc":"2.0","id":0,"result":{"a":1}}"#.to_vec(), 7);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["id"], 7);
+        assert_eq!(json["result"]["a"], 1);
+        assert_eq!(
+            saved_height(br#"{"result":{"directoryblockheight":5}}"#),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn by_height_cached_once_saved() {
+        let node = MockNode::new();
+        node.respond("heights", json!({"directoryblockheight": 9}));
+        let client = node.client().with_cache(MemoryCache::new(10));
+        for _ in 0..2 {
+            block_on(block::dblock_by_height(&client, 10)).unwrap();
+        }
+        assert_eq!(node.requests_for("dblock-by-height").len(), 2);
+
+        node.respond("heights", json!({"directoryblockheight": 10}));
+        for _ in 0..3 {
+            block_on(block::dblock_by_height(&client, 10)).unwrap();
+        }
+        assert_eq!(node.requests_for("dblock-by-height").len(), 3);
+        // Lower heights are known to be saved without asking the node again
+        node.clear_requests();
+        block_on(block::dblock_by_height(&client, 4)).unwrap();
+        assert!(node.requests_for("heights").is_empty());
+        assert_eq!(client.cache_stats().hits, 2);
+    }
+}
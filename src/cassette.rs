@@ -0,0 +1,252 @@
+//! Record and replay of json-rpc exchanges.
+//!
+//! A [Cassette](struct.Cassette.html) is a transport that either records the
+//! exchanges made through another transport to a json file, or replays them
+//! from that file without touching the network. Recorded requests are matched
+//! by method and params, so tests against a live node can be recorded once and
+//! then run deterministically offline.
+//!
+//! When several recorded exchanges share a method and params they are replayed
+//! in the order they were recorded, the last one is repeated once they run
+//! out. A request without a recording fails with an error naming the method.
+//!
+//! Enable with the `testing` feature.
+//!
+//! # Example
+//! ```
+//! use factom::*;
+//! use factom::cassette::Cassette;
+//! use factom::testing::MockNode;
+//! use std::rc::Rc;
+//!
+//! let path = std::env::temp_dir().join("factom-cassette-doc.json");
+//! let node = MockNode::new();
+//! node.respond("properties", serde_json::json!({"factomdversion": "6.5.0"}));
+//!
+//! // Record through the mock node, then replay from the file alone
+//! let recorder = Cassette::record(&path, Rc::new(node));
+//! let client = Factom::new().with_transport(recorder);
+//! futures::executor::block_on(factomd::properties(&client)).unwrap();
+//!
+//! let client = Factom::new().with_transport(Cassette::replay(&path).unwrap());
+//! let response = futures::executor::block_on(factomd::properties(&client)).unwrap();
+//! assert_eq!(response.result.factomdversion, "6.5.0");
+//! ```
+use super::*;
+use futures::future::LocalBoxFuture;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use transport::Transport;
+
+/// A recorded request and the node's response
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub url: String,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    pub response: Value,
+}
+
+/// Transport recording exchanges to, or replaying them from, a json file.
+/// Clones share the same recording.
+#[derive(Clone)]
+pub struct Cassette {
+    path: PathBuf,
+    /// Transport requests are recorded from, None when replaying
+    inner: Option<Rc<dyn Transport>>,
+    state: Rc<RefCell<Tape>>,
+}
+
+#[derive(Debug, Default)]
+struct Tape {
+    interactions: Vec<Interaction>,
+    /// Interactions already replayed
+    played: Vec<bool>,
+}
+
+impl Cassette {
+    /// Records every exchange made through the transport, overwriting the file.
+    /// The file is rewritten after each response so an interrupted recording
+    /// keeps what was received.
+    pub fn record<P: AsRef<Path>>(path: P, transport: Rc<dyn Transport>) -> Cassette {
+        Cassette {
+            path: path.as_ref().to_path_buf(),
+            inner: Some(transport),
+            state: Rc::new(RefCell::new(Tape::default())),
+        }
+    }
+
+    /// Replays the exchanges recorded in the file
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Cassette> {
+        let file = fs::read(path.as_ref())?;
+        let interactions: Vec<Interaction> = serde_json::from_slice(&file)?;
+        let played = vec![false; interactions.len()];
+        Ok(Cassette {
+            path: path.as_ref().to_path_buf(),
+            inner: None,
+            state: Rc::new(RefCell::new(Tape {
+                interactions,
+                played,
+            })),
+        })
+    }
+
+    /// Replays the file if it exists, otherwise records through the transport
+    pub fn auto<P: AsRef<Path>>(path: P, transport: Rc<dyn Transport>) -> Result<Cassette> {
+        if path.as_ref().exists() {
+            Cassette::replay(path)
+        } else {
+            Ok(Cassette::record(path, transport))
+        }
+    }
+
+    /// Whether the cassette is recording rather than replaying
+    pub fn is_recording(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// The file the cassette records to or replays from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The exchanges recorded so far, or loaded from the file
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state.borrow().interactions.clone()
+    }
+
+    fn play(&self, url: &str, method: &str, params: &Value) -> Option<Value> {
+        let mut tape = self.state.borrow_mut();
+        let matches: Vec<usize> = tape
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.method == method && i.params == *params)
+            .map(|(index, _)| index)
+            .collect();
+        // Prefer recordings made against the same node
+        let same_url: Vec<usize> = matches
+            .iter()
+            .copied()
+            .filter(|&index| tape.interactions[index].url == url)
+            .collect();
+        let candidates = if same_url.is_empty() {
+            matches
+        } else {
+            same_url
+        };
+        let index = candidates
+            .iter()
+            .copied()
+            .find(|&index| !tape.played[index])
+            .or_else(|| candidates.last().copied())?;
+        tape.played[index] = true;
+        Some(tape.interactions[index].response.clone())
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        let json = serde_json::to_string_pretty(&self.state.borrow().interactions)?;
+        fs::write(&self.path, json)?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Cassette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cassette")
+            .field("path", &self.path)
+            .field("recording", &self.is_recording())
+            .field("interactions", &self.state.borrow().interactions.len())
+            .finish()
+    }
+}
+
+impl Transport for Cassette {
+    fn post(&self, url: &str, body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+        let request: Value = match serde_json::from_str(&body) {
+            Ok(request) => request,
+            Err(e) => return Box::pin(futures::future::ready(Err(e.into()))),
+        };
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let params = request["params"].clone();
+        let inner = match &self.inner {
+            Some(inner) => inner.post(url, body),
+            None => {
+                let replayed = match self.play(url, &method, &params) {
+                    Some(mut response) => {
+                        // Echo the id of the request like a node would
+                        response["id"] = request["id"].clone();
+                        serde_json::to_vec(&response).map_err(Into::into)
+                    }
+                    None => Err(format!("No recorded response for method {}", method).into()),
+                };
+                return Box::pin(futures::future::ready(replayed));
+            }
+        };
+        let cassette = self.clone();
+        let url = url.to_string();
+        Box::pin(async move {
+            let body = inner.await?;
+            let response: Value = serde_json::from_slice(&body)?;
+            cassette.state.borrow_mut().interactions.push(Interaction {
+                url,
+                method,
+                params,
+                response,
+            });
+            cassette.state.borrow_mut().played.push(true);
+            cassette.save()?;
+            Ok(body)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockNode, MockReply};
+    use futures::executor::block_on;
+
+    #[test]
+    fn record_and_replay() {
+        let path =
+            std::env::temp_dir().join(format!("factom-cassette-{}.json", std::process::id()));
+        let node = MockNode::new();
+        node.respond_once("raw-data", MockReply::Result(json!({"data": "01"})))
+            .respond_once("raw-data", MockReply::Result(json!({"data": "02"})))
+            .respond("heights", json!({"leaderheight": 7}));
+        let hash = "0ae2ab2cf543eed52a13a5a405bded712444cc8f8b6724a00602e1c8550a4ec2";
+
+        let recorder = Cassette::record(&path, Rc::new(node.clone()));
+        let client = Factom::new().with_transport(recorder.clone());
+        block_on(entry::raw_data(&client, hash)).unwrap();
+        block_on(entry::raw_data(&client, hash)).unwrap();
+        block_on(factomd::heights(&client)).unwrap();
+        assert_eq!(recorder.interactions().len(), 3);
+
+        let player = Cassette::replay(&path).unwrap();
+        assert!(!player.is_recording());
+        let client = Factom::new().with_transport(player);
+        let first = block_on(entry::raw_data(&client, hash)).unwrap();
+        let second = block_on(entry::raw_data(&client, hash)).unwrap();
+        let third = block_on(entry::raw_data(&client, hash)).unwrap();
+        assert_eq!(first.result.data, "01");
+        assert_eq!(second.result.data, "02");
+        assert_eq!(third.result.data, "02");
+        let heights = block_on(factomd::heights(&client)).unwrap();
+        assert_eq!(heights.result.leaderheight, 7);
+        // Different params aren't matched
+        assert!(block_on(entry::raw_data(&client, &"00".repeat(32))).is_err());
+        assert_eq!(node.requests().len(), 3);
+        fs::remove_file(&path).unwrap();
+    }
+}
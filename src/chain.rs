@@ -18,7 +18,11 @@ use super::*;
 ///   assert!(response.success());
 /// }
 /// ```
-pub async fn chain_head(client: &Factom, chainid: &str) -> Result<ApiResponse<ChainHead>> {
+pub async fn chain_head(
+    client: &Factom,
+    chainid: impl IntoHash<ChainId>,
+) -> Result<ApiResponse<ChainHead>> {
+    let chainid = chainid.into_hash()?;
     let mut req = ApiRequest::new("chain-head");
     req.params.insert("chainid".to_string(), json!(chainid));
     let response = requests::factomd_call(client, req).await;
@@ -76,7 +80,7 @@ pub async fn reveal_chain(api: &Factom, entry: &str) -> Result<ApiResponse<Revea
 /// chain-head function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChainHead {
-    pub chainhead: String,
+    pub chainhead: KeyMr,
     pub chaininprocesslist: bool,
 }
 
@@ -84,17 +88,17 @@ pub struct ChainHead {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommitChain {
     pub message: String,
-    pub txid: String,
-    pub entryhash: String,
+    pub txid: TxId,
+    pub entryhash: EntryHash,
     #[serde(alias = "chainidhash")]
-    pub chainid: String,
+    pub chainid: ChainId,
 }
 
 /// reveal-chain function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RevealChain {
     pub message: String,
-    pub entryhash: String,
+    pub entryhash: EntryHash,
     #[serde(alias = "chainidhash")]
-    pub chainid: String,
+    pub chainid: ChainId,
 }
@@ -1,6 +1,6 @@
 //! Functions that compose transactions, entries and identities.
 use super::*;
-use factomd::str_to_hex;
+use hash::encode_hex;
 use std::collections::HashMap;
 
 /// This method, compose-chain, will return the appropriate API calls to create a
@@ -8,18 +8,23 @@ use std::collections::HashMap;
 /// API calls. To be safe, wait a few seconds after calling commit.
 ///
 /// Note: The firstentry fields are automatically hex encoded for the server to
-/// process.
-pub async fn compose_chain(
+/// process. Ext IDs and content can be any byte payload such as a `&str`,
+/// `String` or `Vec<u8>`.
+pub async fn compose_chain<E, C>(
     api: &Factom,
-    extids: Vec<&str>,
-    content: &str,
+    extids: Vec<E>,
+    content: C,
     ecpub: &str,
-) -> Result<ApiResponse<Compose>> {
+) -> Result<ApiResponse<Compose>>
+where
+    E: AsRef<[u8]>,
+    C: AsRef<[u8]>,
+{
     let mut req = ApiRequest::new("compose-chain");
-    let hex_content = str_to_hex(content);
+    let hex_content = encode_hex(content.as_ref());
     let mut hex_extids = Vec::new();
     for extid in extids {
-        hex_extids.push(str_to_hex(extid));
+        hex_extids.push(encode_hex(extid.as_ref()));
     }
     let chain = json!({
       "firstentry": {
@@ -38,22 +43,29 @@ pub async fn compose_chain(
 /// API calls. To be safe, wait a few seconds after calling commit.
 ///
 /// Note: The entry fields are automatically hex encoded for the server to process.
-pub async fn compose_entry(
+/// Ext IDs and content can be any byte payload such as a `&str`, `String` or
+/// `Vec<u8>`.
+pub async fn compose_entry<E, C>(
     api: &Factom,
-    chainid: &str,
-    extids: Vec<&str>,
-    content: &str,
+    chainid: impl IntoHash<ChainId>,
+    extids: Vec<E>,
+    content: C,
     ecpub: &str,
-) -> Result<ApiResponse<Compose>> {
+) -> Result<ApiResponse<Compose>>
+where
+    E: AsRef<[u8]>,
+    C: AsRef<[u8]>,
+{
+    let chainid = chainid.into_hash()?;
     let mut req = ApiRequest::new("compose-entry");
     let mut hex_extids = Vec::new();
     for extid in extids {
-        hex_extids.push(str_to_hex(extid));
+        hex_extids.push(encode_hex(extid.as_ref()));
     }
     let entry = json!({
     "chainid": chainid,
     "extids": hex_extids,
-    "content": str_to_hex(content)
+    "content": encode_hex(content.as_ref())
     });
     req.params.insert("entry".to_string(), entry);
     req.params.insert("ecpub".to_string(), json!(ecpub));
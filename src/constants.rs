@@ -19,5 +19,25 @@ pub const ID: usize = 0;
 pub const FCT_REGEX: &str = "^FA[123][1-9A-HJ-NP-Za-km-z]{49}";
 /// Regex for matching Entry Credit Addresses
 pub const EC_REGEX: &str = "^EC[123][1-9A-HJ-NP-Za-km-z]{49}";
+/// Api error code returned when a commit has already been submitted
+pub const REPEATED_COMMIT: i16 = -32011;
 /// Null key merkle root
 pub const NULL_KEYMR: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+/// Chain ID of the admin block chain
+pub const ADMIN_CHAINID: &str = "000000000000000000000000000000000000000000000000000000000000000a";
+/// Chain ID of the entry credit block chain
+pub const EC_CHAINID: &str = "000000000000000000000000000000000000000000000000000000000000000c";
+/// Chain ID of the factoid block chain
+pub const FACTOID_CHAINID: &str = "000000000000000000000000000000000000000000000000000000000000000f";
+/// Prefix of public factoid addresses, FA...
+pub const FCT_PUBLIC_PREFIX: [u8; 2] = [0x5f, 0xb1];
+/// Prefix of private factoid addresses, Fs...
+pub const FCT_SECRET_PREFIX: [u8; 2] = [0x64, 0x78];
+/// Prefix of public entry credit addresses, EC...
+pub const EC_PUBLIC_PREFIX: [u8; 2] = [0x59, 0x2a];
+/// Prefix of private entry credit addresses, Es...
+pub const EC_SECRET_PREFIX: [u8; 2] = [0x5d, 0xb6];
+/// Prefix of public identity keys, idpub...
+pub const ID_PUBLIC_PREFIX: [u8; 5] = [0x03, 0x45, 0xef, 0x9d, 0xe0];
+/// Prefix of private identity keys, idsec...
+pub const ID_SECRET_PREFIX: [u8; 5] = [0x03, 0x45, 0xf3, 0xd0, 0xd6];
@@ -297,6 +297,51 @@ pub async fn messages(api: &Factom) -> Result<ApiResponse<Messages>> {
     parse(response).await
 }
 
+/// Blocks until the node has saved the directory block at the given height.
+/// Meant for simulations and test networks driven through the debug api.
+///
+/// # Example
+///
+/// ```no_run
+/// use factom::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   /// Doctest examples will only work with a local factomd node running
+///   let client = Factom::new();
+///   let response = debug::wait_for_block(&client, 10).await.expect("Api Request");
+///   dbg!(&response);
+/// }
+/// ```
+pub async fn wait_for_block(api: &Factom, height: u32) -> Result<ApiResponse<WaitFor>> {
+    let mut req = ApiRequest::new("wait-for-block");
+    req.params.insert("block".to_string(), json!(height));
+    let response = debug_call(api, req).await;
+    parse(response).await
+}
+
+/// Blocks until the node reaches the given minute of the current block.
+///
+/// # Example
+///
+/// ```no_run
+/// use factom::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   /// Doctest examples will only work with a local factomd node running
+///   let client = Factom::new();
+///   let response = debug::wait_for_minute(&client, 5).await.expect("Api Request");
+///   dbg!(&response);
+/// }
+/// ```
+pub async fn wait_for_minute(api: &Factom, minute: u8) -> Result<ApiResponse<WaitFor>> {
+    let mut req = ApiRequest::new("wait-for-minute");
+    req.params.insert("minute".to_string(), json!(minute));
+    let response = debug_call(api, req).await;
+    parse(response).await
+}
+
 /// holding-queue function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HoldingQueue {
@@ -564,3 +609,18 @@ pub struct Messages {
     #[serde(rename = "Messages")]
     pub messages: Vec<String>,
 }
+
+/// wait-for-block and wait-for-minute functions, factomd only replies once the
+/// node gets there and the reply itself carries no information, so any reply is
+/// read as WaitFor
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct WaitFor;
+
+impl<'de> Deserialize<'de> for WaitFor {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<WaitFor, D::Error> {
+        serde::de::IgnoredAny::deserialize(deserializer)?;
+        Ok(WaitFor)
+    }
+}
@@ -1,5 +1,6 @@
 //! For creating and querying entires.
 use super::*;
+use serde::de::DeserializeOwned;
 
 /// Send an Entry Commit Message to factom to create a new Entry. The entry commit
 /// hex encoded string is documented here:
@@ -27,7 +28,8 @@ pub async fn commit_entry(api: &Factom, message: &str) -> Result<ApiResponse<Com
 }
 
 /// Get an Entry from factomd specified by the Entry Hash.
-pub async fn entry(api: &Factom, hash: &str) -> Result<ApiResponse<Entry>> {
+pub async fn entry(api: &Factom, hash: impl IntoHash<EntryHash>) -> Result<ApiResponse<Entry>> {
+    let hash = hash.into_hash()?;
     let mut req = ApiRequest::new("entry");
     req.params.insert("hash".to_string(), json!(hash));
     let response = factomd_call(api, req).await;
@@ -35,7 +37,12 @@ pub async fn entry(api: &Factom, hash: &str) -> Result<ApiResponse<Entry>> {
 }
 
 /// Retrieve an entry or transaction in raw format, the data is a hex encoded string.
-pub async fn raw_data(api: &Factom, hash: &str) -> Result<ApiResponse<RawData>> {
+/// Any of the hash types can be passed, or a hex string.
+pub async fn raw_data(
+    api: &Factom,
+    hash: impl IntoHash<hash::Hash>,
+) -> Result<ApiResponse<RawData>> {
+    let hash = hash.into_hash()?;
     let mut req = ApiRequest::new("raw-data");
     req.params.insert("hash".to_string(), json!(hash));
     let response = factomd_call(api, req).await;
@@ -69,18 +76,68 @@ pub async fn reveal_entry(api: &Factom, entry: &str) -> Result<ApiResponse<Revea
     parse(response).await
 }
 
+/// entry function, the content and extids are hex encoded. Use the methods
+/// below to decode them.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entry {
-    pub chainid: String,
+    pub chainid: ChainId,
     pub content: String,
     pub extids: Vec<String>,
 }
 
+impl Entry {
+    /// Decodes the hex encoded content into bytes
+    pub fn content_bytes(&self) -> Result<Vec<u8>> {
+        Ok(hash::decode_hex(&self.content)?)
+    }
+
+    /// Decodes each hex encoded ext id into bytes
+    pub fn extid_bytes(&self) -> Result<Vec<Vec<u8>>> {
+        let mut extids = Vec::with_capacity(self.extids.len());
+        for extid in &self.extids {
+            extids.push(hash::decode_hex(extid)?);
+        }
+        Ok(extids)
+    }
+
+    /// Decodes the content and interprets it as a UTF-8 string
+    pub fn content_utf8(&self) -> Result<String> {
+        Ok(String::from_utf8(self.content_bytes()?)?)
+    }
+
+    /// Decodes the ext ids and interprets each as a UTF-8 string
+    pub fn extids_utf8(&self) -> Result<Vec<String>> {
+        let mut extids = Vec::with_capacity(self.extids.len());
+        for extid in self.extid_bytes()? {
+            extids.push(String::from_utf8(extid)?);
+        }
+        Ok(extids)
+    }
+
+    /// Decodes the content and deserializes it from JSON
+    ///
+    /// # Example
+    /// ```
+    /// use factom::*;
+    /// use serde_json::Value;
+    ///
+    /// let entry = entry::Entry {
+    ///   content: factomd::str_to_hex(r#"{"height":1}"#),
+    ///   ..Default::default()
+    /// };
+    /// let json: Value = entry.content_json().unwrap();
+    /// assert_eq!(json["height"], 1);
+    /// ```
+    pub fn content_json<T: DeserializeOwned>(&self) -> Result<T> {
+        Ok(serde_json::from_slice(&self.content_bytes()?)?)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommitEntry {
     pub message: String,
-    pub txid: String,
-    pub entryhash: String,
+    pub txid: TxId,
+    pub entryhash: EntryHash,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -98,14 +155,32 @@ pub struct RawData {
 /// pending-entries function returns a Vec of PendingEntry
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PendingEntry {
-    pub entryhash: String,
-    pub chainid: Option<String>,
+    pub entryhash: EntryHash,
+    pub chainid: Option<ChainId>,
     pub status: String,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RevealEntry {
     pub message: String,
-    pub entryhash: String,
-    pub chainid: String,
+    pub entryhash: EntryHash,
+    pub chainid: ChainId,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_entry() {
+        let entry = Entry {
+            chainid: ChainId::default(),
+            content: "00ff".to_string(),
+            extids: vec!["48656c6c6f".to_string(), "".to_string()],
+        };
+        assert_eq!(entry.content_bytes().unwrap(), vec![0x00, 0xff]);
+        assert!(entry.content_utf8().is_err());
+        assert_eq!(entry.extids_utf8().unwrap(), vec!["Hello", ""]);
+        assert_eq!(entry.extid_bytes().unwrap()[0], b"Hello".to_vec());
+    }
 }
@@ -1,5 +1,6 @@
 //! General functions relating to factomd
 use super::*;
+use std::time::Duration;
 
 /// The current-minute API call returns:
 ///
@@ -228,9 +229,10 @@ pub async fn properties(api: &Factom) -> Result<ApiResponse<Properties>> {
 /// ```
 pub async fn receipt(
     api: &Factom,
-    hash: &str,
+    hash: impl IntoHash<EntryHash>,
     includerawentry: bool,
 ) -> Result<ApiResponse<Receipt>> {
+    let hash = hash.into_hash()?;
     let mut req = ApiRequest::new("receipt");
     req.params.insert("hash".to_string(), json!(hash));
     if includerawentry {
@@ -250,7 +252,8 @@ pub async fn send_raw_message(api: &Factom, msg: &str) -> Result<ApiResponse<Rec
     parse(response).await
 }
 
-/// Converts a string to its hexadecimal representation.
+/// Converts a string to its uppercase hexadecimal representation. For arbitrary
+/// byte payloads use [hash::encode_hex](../hash/fn.encode_hex.html).
 pub fn str_to_hex(utf8: &str) -> String {
     let strs: Vec<String> = utf8
         .as_bytes()
@@ -275,6 +278,23 @@ pub struct CurrentMinute {
     pub roundtimeout: i64,
 }
 
+impl CurrentMinute {
+    /// Time left until the next minute starts, according to the node's clock.
+    /// Factomd reports its times in nanoseconds.
+    pub fn until_next_minute(&self) -> Duration {
+        let minute = self.directoryblockinseconds * 100_000_000;
+        let elapsed = self.currenttime - self.currentminutestarttime;
+        Duration::from_nanos((minute - elapsed).max(0) as u64)
+    }
+
+    /// Time left until the current directory block ends
+    pub fn until_next_block(&self) -> Duration {
+        let block = self.directoryblockinseconds * 1_000_000_000;
+        let elapsed = self.currenttime - self.currentblockstarttime;
+        Duration::from_nanos((block - elapsed).max(0) as u64)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Diagnostics {
     pub name: String,
@@ -361,14 +381,14 @@ pub struct Receipt {
 pub struct ReceiptInner {
     pub entry: Entry,
     pub merklebranch: Vec<Merklebranch>,
-    pub entryblockkeymr: String,
-    pub directoryblockkeymr: String,
+    pub entryblockkeymr: KeyMr,
+    pub directoryblockkeymr: KeyMr,
     pub directoryblockheight: i64,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entry {
-    pub entryhash: String,
+    pub entryhash: EntryHash,
     #[serde(default)]
     pub raw: String,
     pub timestamp: i64,
@@ -384,6 +404,20 @@ pub struct Merklebranch {
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn minute_timing() {
+        let minute = CurrentMinute {
+            directoryblockinseconds: 600,
+            currentblockstarttime: 1_000_000_000_000,
+            currentminutestarttime: 1_060_000_000_000,
+            currenttime: 1_100_000_000_000,
+            ..CurrentMinute::default()
+        };
+        assert_eq!(minute.until_next_minute(), Duration::from_secs(20));
+        assert_eq!(minute.until_next_block(), Duration::from_secs(500));
+    }
+
     #[test]
     fn current_minute_test() {
         let client = Factom::open_node();
@@ -0,0 +1,621 @@
+//! Subscriptions following chains and directory blocks as new data lands.
+//!
+//! Followers time their polls around minute and block boundaries using
+//! [current_minute](../factomd/fn.current_minute.html), unless a fixed interval
+//! is given. Errors from a poll are yielded without ending the stream, the
+//! next poll carries on from the same position.
+use super::*;
+use crate::block::{Ablock, DblockHeight, Ecblock, Fblock};
+use crate::entry::PendingEntry;
+use crate::traverse::{ChainEntry, Cursor};
+use futures::future::Future;
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use futures_timer::Delay;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+/// Pause used when the next minute can't be read from the node
+const FALLBACK_INTERVAL: Duration = Duration::from_secs(10);
+/// Delay after a minute boundary before polling, leaving the node time to
+/// process the new minute
+const MINUTE_SLACK: Duration = Duration::from_secs(1);
+
+/// Stream of events returned by follow_chain
+pub type ChainEvents = LocalBoxStream<'static, Result<ChainEvent>>;
+/// Stream of block sets returned by follow_blocks
+pub type BlockSets = LocalBoxStream<'static, Result<BlockSet>>;
+
+/// Options for the followers
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowOptions {
+    /// Fixed pause between polls, None to poll after each minute boundary
+    pub interval: Option<Duration>,
+    /// Emit entries seen in the pending entries before they are confirmed
+    pub pending: bool,
+    /// Number of entries fetched concurrently
+    pub concurrency: usize,
+}
+
+impl Default for FollowOptions {
+    fn default() -> Self {
+        FollowOptions {
+            interval: None,
+            pending: true,
+            concurrency: 8,
+        }
+    }
+}
+
+/// Options for follow_blocks, choosing which blocks are fetched along with
+/// each directory block
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockOptions {
+    /// Fixed pause between polls, None to time polls with current-minute
+    pub interval: Option<Duration>,
+    pub admin: bool,
+    pub entry_credit: bool,
+    pub factoid: bool,
+}
+
+/// The blocks recorded at a directory block height
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockSet {
+    pub height: u32,
+    pub dblock: DblockHeight,
+    pub ablock: Option<Ablock>,
+    pub ecblock: Option<Ecblock>,
+    pub fblock: Option<Fblock>,
+}
+
+/// An event from follow_chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ChainEvent {
+    /// The entry was submitted but isn't recorded in an entry block yet
+    Pending(PendingEntry),
+    /// The entry was recorded in an entry block
+    Confirmed(ChainEntry),
+}
+
+struct Follower {
+    api: Factom,
+    chainid: ChainId,
+    options: FollowOptions,
+    cursor: Option<Cursor>,
+    /// Pending entries already emitted, or confirmed while still pending
+    known: HashSet<EntryHash>,
+    queue: VecDeque<Result<ChainEvent>>,
+    polled: bool,
+}
+
+/// Follows a chain, yielding its new entries as they are submitted and again
+/// once they are confirmed.
+///
+/// Without a cursor following starts after the current chain head, otherwise
+/// after the cursor's entry, so persisting the cursor of the last confirmed
+/// entry lets a follower resume without missing entries. Confirmed entries are
+/// emitted oldest first once their block is published, even if it's published
+/// late. Each pending entry is emitted once, and not at all if it is confirmed
+/// before a poll sees it pending.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+/// use factom::follow::*;
+/// use futures::stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::open_node();
+///   let chain = "843dbee7a49a9b9510d399759fbce24b1f700268c94508085abce352d70ed1f6";
+///   let mut events = follow_chain(&client, chain, None, FollowOptions::default())
+///                       .expect("Parsing chain id");
+///   while let Some(event) = events.next().await {
+///     match event {
+///       Ok(ChainEvent::Pending(entry)) => println!("pending {}", entry.entryhash),
+///       Ok(ChainEvent::Confirmed(entry)) => println!("confirmed {}", entry.entryhash),
+///       Err(e) => eprintln!("{}", e),
+///     }
+///   }
+/// }
+/// ```
+pub fn follow_chain(
+    api: &Factom,
+    chainid: impl IntoHash<ChainId>,
+    cursor: Option<Cursor>,
+    options: FollowOptions,
+) -> Result<ChainEvents> {
+    let follower = Follower {
+        api: api.clone(),
+        chainid: chainid.into_hash()?,
+        options,
+        cursor,
+        known: HashSet::new(),
+        queue: VecDeque::new(),
+        polled: false,
+    };
+    Ok(stream::unfold(follower, |mut follower| async move {
+        loop {
+            if let Some(event) = follower.queue.pop_front() {
+                return Some((event, follower));
+            }
+            if follower.polled {
+                let pause = poll_interval(&follower.api, follower.options.interval).await;
+                Delay::new(pause).await;
+            }
+            follower.polled = true;
+            follower.poll().await;
+        }
+    })
+    .boxed_local())
+}
+
+impl Follower {
+    /// Queues the events found since the last poll
+    async fn poll(&mut self) {
+        if let Err(e) = self.confirmed().await {
+            self.queue.push_back(Err(e));
+        }
+        if !self.options.pending {
+            return;
+        }
+        let pending = match entry::pending_entries(&self.api).await {
+            Ok(response) => match response.into_result() {
+                Ok(pending) => pending,
+                Err(e) => return self.queue.push_back(Err(e)),
+            },
+            Err(e) => return self.queue.push_back(Err(e)),
+        };
+        let pending: Vec<_> = pending
+            .into_iter()
+            .filter(|entry| entry.chainid == Some(self.chainid))
+            .collect();
+        for entry in &pending {
+            if self.known.insert(entry.entryhash) {
+                self.queue.push_back(Ok(ChainEvent::Pending(entry.clone())));
+            }
+        }
+        // Forget entries once they leave the pending list
+        self.known
+            .retain(|hash| pending.iter().any(|entry| entry.entryhash == *hash));
+    }
+
+    /// Queues the entries confirmed after the cursor
+    async fn confirmed(&mut self) -> Result<()> {
+        let cursor = match self.cursor {
+            Some(cursor) => cursor,
+            None => {
+                let cursor = self.head_cursor().await?;
+                self.cursor = Some(cursor);
+                return Ok(());
+            }
+        };
+        let entries: Vec<_> = traverse::replay(
+            &self.api,
+            self.chainid,
+            Some(cursor),
+            self.options.concurrency,
+        )?
+        .collect()
+        .await;
+        for entry in entries {
+            // Stop at the first failure so the next poll retries from there
+            let entry = entry?;
+            self.cursor = Some(entry.cursor());
+            // Only needed to skip it in the pending entries, which prune it
+            if self.options.pending {
+                self.known.insert(entry.entryhash);
+            }
+            self.queue.push_back(Ok(ChainEvent::Confirmed(entry)));
+        }
+        Ok(())
+    }
+
+    /// Cursor pointing at the last entry of the current chain head
+    async fn head_cursor(&self) -> Result<Cursor> {
+        let keymr = chain::chain_head(&self.api, self.chainid)
+            .await?
+            .into_result()?
+            .chainhead;
+        let block = block::entry_block(&self.api, keymr).await?.into_result()?;
+        Ok(Cursor {
+            keymr,
+            index: block.entrylist.len().saturating_sub(1),
+        })
+    }
+}
+
+/// Pause before the next poll, the fixed interval if set or until shortly
+/// after the next minute boundary
+pub(crate) async fn poll_interval(api: &Factom, interval: Option<Duration>) -> Duration {
+    if let Some(interval) = interval {
+        return interval;
+    }
+    match factomd::current_minute(api).await {
+        Ok(response) if !response.is_err() => response.result.until_next_minute() + MINUTE_SLACK,
+        _ => FALLBACK_INTERVAL,
+    }
+}
+
+struct BlockFollower {
+    api: Factom,
+    options: BlockOptions,
+    /// Next height to emit, None until the saved height is known
+    next: Option<u32>,
+    /// Last directory block height saved by the node
+    saved: Option<u32>,
+    failed: bool,
+}
+
+/// Follows the directory blocks, yielding a block set for each new height.
+///
+/// With a start height the stream first catches up from that height, then
+/// follows new blocks as they are saved, otherwise it starts at the next block.
+/// While waiting, polls are timed around block boundaries using
+/// current-minute: the node is polled once the block being built ends, then
+/// each minute until it has been saved. Errors are yielded without ending the
+/// stream and the same height is retried after a pause.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+/// use factom::follow::*;
+/// use futures::stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::open_node();
+///   let options = BlockOptions {
+///     factoid: true,
+///     ..BlockOptions::default()
+///   };
+///   let mut blocks = follow_blocks(&client, Some(220000), options);
+///   while let Some(set) = blocks.next().await {
+///     let set = set.expect("Fetching blocks");
+///     println!("{} {}", set.height, set.dblock.keymr);
+///   }
+/// }
+/// ```
+pub fn follow_blocks(api: &Factom, start: Option<u32>, options: BlockOptions) -> BlockSets {
+    let follower = BlockFollower {
+        api: api.clone(),
+        options,
+        next: start,
+        saved: None,
+        failed: false,
+    };
+    stream::unfold(follower, |mut follower| async move {
+        if follower.failed {
+            Delay::new(follower.options.interval.unwrap_or(FALLBACK_INTERVAL)).await;
+        }
+        let result = follower.step().await;
+        follower.failed = result.is_err();
+        Some((result, follower))
+    })
+    .boxed_local()
+}
+
+impl BlockFollower {
+    /// Waits until the next height is saved and fetches its blocks
+    async fn step(&mut self) -> Result<BlockSet> {
+        loop {
+            if let (Some(next), Some(saved)) = (self.next, self.saved) {
+                if next <= saved {
+                    let set = self.block_set(next).await?;
+                    self.next = Some(next + 1);
+                    return Ok(set);
+                }
+            }
+            let minute = factomd::current_minute(&self.api).await?.into_result()?;
+            let saved = minute.directoryblockheight as u32;
+            let next = *self.next.get_or_insert(saved + 1);
+            self.saved = Some(saved);
+            if next <= saved {
+                continue;
+            }
+            let pause = match self.options.interval {
+                Some(interval) => interval,
+                // The block is still being built
+                None if i64::from(next) >= minute.leaderheight => {
+                    minute.until_next_block() + MINUTE_SLACK
+                }
+                // The block has ended but hasn't been saved yet
+                None => minute.until_next_minute() + MINUTE_SLACK,
+            };
+            Delay::new(pause).await;
+        }
+    }
+
+    async fn block_set(&self, height: u32) -> Result<BlockSet> {
+        let api = &self.api;
+        let dblock = block::dblock_by_height(api, height)
+            .await?
+            .into_result()?
+            .dblock;
+        let (ablock, ecblock, fblock) = futures::join!(
+            fetch_block(
+                &dblock,
+                ADMIN_CHAINID,
+                self.options.admin,
+                |keymr| async move { Ok(block::admin_block(api, keymr).await?.into_result()?.ablock) }
+            ),
+            fetch_block(
+                &dblock,
+                EC_CHAINID,
+                self.options.entry_credit,
+                |keymr| async move {
+                    Ok(block::entry_credit_block(api, keymr)
+                        .await?
+                        .into_result()?
+                        .ecblock)
+                }
+            ),
+            fetch_block(
+                &dblock,
+                FACTOID_CHAINID,
+                self.options.factoid,
+                |keymr| async move {
+                    Ok(block::factoid_block(api, keymr)
+                        .await?
+                        .into_result()?
+                        .fblock)
+                }
+            )
+        );
+        Ok(BlockSet {
+            height,
+            dblock,
+            ablock: ablock?,
+            ecblock: ecblock?,
+            fblock: fblock?,
+        })
+    }
+}
+
+/// Fetches the block of the chain listed in the directory block, if enabled
+async fn fetch_block<T, F, Fut>(
+    dblock: &DblockHeight,
+    chainid: &str,
+    enabled: bool,
+    fetch: F,
+) -> Result<Option<T>>
+where
+    F: FnOnce(KeyMr) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    if !enabled {
+        return Ok(None);
+    }
+    let keymr = dblock
+        .dbentries
+        .iter()
+        .find(|entry| entry.chainid == chainid)
+        .map(|entry| entry.keymr)
+        .ok_or_else(|| format!("Directory block has no entry for chain {}", chainid))?;
+    fetch(keymr).await.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::DBlockHeightHeader;
+    use crate::testing::{MockNode, MockReply};
+    use futures::executor::block_on;
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const CHAIN: &str = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+    const OTHER: &str = "843dbee7a49a9b9510d399759fbce24b1f700268c94508085abce352d70ed1f6";
+
+    fn entryhash(block: u8, index: u8) -> EntryHash {
+        EntryHash([block << 4 | index; 32])
+    }
+
+    /// A chain of entry blocks holding two entries each, the head is read from
+    /// heads by the number of chain-head calls so far
+    fn chain_node(heads: &'static [u8]) -> MockNode {
+        let node = MockNode::new();
+        let calls = AtomicUsize::new(0);
+        node.respond_with("chain-head", move |_| {
+            let call = calls.fetch_add(1, Ordering::SeqCst).min(heads.len() - 1);
+            MockReply::Result(json!({"chainhead": KeyMr([heads[call]; 32])}))
+        })
+        .respond_with("entry-block", |params| {
+            let keymr: KeyMr = params["keymr"].as_str().unwrap().parse().unwrap();
+            let block = keymr.0[0];
+            MockReply::Result(json!({
+                "header": {
+                    "blocksequencenumber": block - 1,
+                    "chainid": CHAIN,
+                    "prevkeymr": KeyMr([block - 1; 32]),
+                    "timestamp": 0,
+                    "dbheight": block as usize * 10,
+                },
+                "entrylist": (0..2).map(|index| json!({
+                    "entryhash": entryhash(block, index),
+                    "timestamp": index,
+                })).collect::<Vec<_>>(),
+            }))
+        })
+        .respond_with("entry", |_| {
+            MockReply::Result(json!({"chainid": CHAIN, "content": ""}))
+        });
+        node
+    }
+
+    fn options(pending: bool) -> FollowOptions {
+        FollowOptions {
+            interval: Some(Duration::from_millis(1)),
+            pending,
+            concurrency: 2,
+        }
+    }
+
+    fn confirmed(events: &[ChainEvent]) -> Vec<EntryHash> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ChainEvent::Confirmed(entry) => Some(entry.entryhash),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn follows_from_head() {
+        let node = chain_node(&[1, 1, 2, 2, 3]);
+        let events = follow_chain(&node.client(), CHAIN, None, options(false)).unwrap();
+        let events: Vec<_> = block_on(events.take(4).try_collect()).unwrap();
+        assert_eq!(
+            confirmed(&events),
+            vec![
+                entryhash(2, 0),
+                entryhash(2, 1),
+                entryhash(3, 0),
+                entryhash(3, 1)
+            ]
+        );
+        assert!(node.requests_for("pending-entries").is_empty());
+    }
+
+    #[test]
+    fn confirmed_entries_forgotten_without_pending() {
+        let node = chain_node(&[2]);
+        let mut follower = Follower {
+            api: node.client(),
+            chainid: CHAIN.parse().unwrap(),
+            options: options(false),
+            cursor: Some(Cursor {
+                keymr: KeyMr([1; 32]),
+                index: 1,
+            }),
+            known: HashSet::new(),
+            queue: VecDeque::new(),
+            polled: false,
+        };
+        block_on(follower.poll());
+        assert_eq!(follower.queue.len(), 2);
+        assert!(follower.known.is_empty());
+    }
+
+    #[test]
+    fn resumes_from_cursor() {
+        let node = chain_node(&[2]);
+        let cursor = Cursor {
+            keymr: KeyMr([1; 32]),
+            index: 0,
+        };
+        let events = follow_chain(&node.client(), CHAIN, Some(cursor), options(false)).unwrap();
+        let events: Vec<_> = block_on(events.take(3).try_collect()).unwrap();
+        assert_eq!(
+            confirmed(&events),
+            vec![entryhash(1, 1), entryhash(2, 0), entryhash(2, 1)]
+        );
+    }
+
+    #[test]
+    fn pending_then_confirmed() {
+        let node = chain_node(&[1, 1, 2]);
+        let polls = AtomicUsize::new(0);
+        node.respond_with("pending-entries", move |_| {
+            let pending = |hash: EntryHash, chainid: &str| {
+                json!({"entryhash": hash, "chainid": chainid, "status": "TransactionACK"})
+            };
+            let list = match polls.fetch_add(1, Ordering::SeqCst) {
+                0 => vec![pending(entryhash(2, 0), CHAIN), pending(entryhash(9, 0), OTHER)],
+                1 => vec![pending(entryhash(2, 0), CHAIN), pending(entryhash(2, 1), CHAIN)],
+                // Still listed as pending after being confirmed
+                2 => vec![pending(entryhash(2, 1), CHAIN)],
+                _ => vec![pending(entryhash(3, 0), CHAIN)],
+            };
+            MockReply::Result(json!(list))
+        });
+        let events = follow_chain(&node.client(), CHAIN, None, options(true)).unwrap();
+        let events: Vec<_> = block_on(events.take(5).try_collect()).unwrap();
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                ChainEvent::Pending(entry) => ("pending", entry.entryhash),
+                ChainEvent::Confirmed(entry) => ("confirmed", entry.entryhash),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("pending", entryhash(2, 0)),
+                ("pending", entryhash(2, 1)),
+                ("confirmed", entryhash(2, 0)),
+                ("confirmed", entryhash(2, 1)),
+                ("pending", entryhash(3, 0)),
+            ]
+        );
+    }
+
+    fn dblock_by_height(params: &Value) -> MockReply {
+        let height = params["height"].as_u64().unwrap() as usize;
+        let dblock = DblockHeight {
+            header: DBlockHeightHeader {
+                dbheight: height,
+                ..DBlockHeightHeader::default()
+            },
+            keymr: KeyMr([height as u8; 32]),
+            ..DblockHeight::default()
+        };
+        MockReply::Result(json!({ "dblock": dblock }))
+    }
+
+    #[test]
+    fn follows_each_height_once() {
+        let node = MockNode::new();
+        let polls = AtomicUsize::new(0);
+        node.respond_with("current-minute", move |_| {
+            // Height 11 and 12 are saved between two polls
+            let saved = [10, 10, 13, 13, 14][polls.fetch_add(1, Ordering::SeqCst).min(4)];
+            MockReply::Result(json!({
+                "directoryblockheight": saved,
+                "leaderheight": saved + 1,
+            }))
+        })
+        .respond_with("dblock-by-height", dblock_by_height);
+        let options = BlockOptions {
+            interval: Some(Duration::from_millis(1)),
+            ..BlockOptions::default()
+        };
+        let sets = follow_blocks(&node.client(), None, options);
+        let sets: Vec<BlockSet> = block_on(sets.take(4).try_collect()).unwrap();
+        let heights: Vec<_> = sets.iter().map(|set| set.height).collect();
+        assert_eq!(heights, vec![11, 12, 13, 14]);
+        assert!(sets
+            .iter()
+            .all(|set| set.dblock.header.dbheight == set.height as usize));
+        let fetched: Vec<_> = node
+            .requests_for("dblock-by-height")
+            .iter()
+            .map(|request| request.params["height"].as_u64().unwrap())
+            .collect();
+        assert_eq!(fetched, vec![11, 12, 13, 14]);
+        assert_eq!(node.requests_for("current-minute").len(), 5);
+    }
+
+    #[test]
+    fn catches_up_from_start() {
+        let node = MockNode::new();
+        node.respond(
+            "current-minute",
+            json!({"directoryblockheight": 10, "leaderheight": 11}),
+        )
+        .respond_with("dblock-by-height", dblock_by_height);
+        let options = BlockOptions {
+            interval: Some(Duration::from_millis(1)),
+            ..BlockOptions::default()
+        };
+        let sets = follow_blocks(&node.client(), Some(8), options);
+        let sets: Vec<BlockSet> = block_on(sets.take(3).try_collect()).unwrap();
+        let heights: Vec<_> = node
+            .requests_for("dblock-by-height")
+            .iter()
+            .map(|request| request.params["height"].as_u64().unwrap())
+            .collect();
+        assert_eq!(heights, vec![8, 9, 10]);
+        assert_eq!(sets[2].height, 10);
+    }
+}
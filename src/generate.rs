@@ -1,5 +1,6 @@
 //! Functions for generating addresses or identities.
 use super::*;
+use crate::secret::Secret;
 
 /// Create a new Entry Credit Address and store it in the wallet. If the wallet
 /// is encrypted, it must be unlocked prior to using this command.
@@ -76,5 +77,5 @@ pub async fn identity_key(api: &Factom) -> Result<ApiResponse<Generate>> {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Generate {
     pub public: String,
-    pub secret: String,
+    pub secret: Secret,
 }
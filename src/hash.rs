@@ -0,0 +1,299 @@
+//! Strongly typed 32 byte hashes used to identify chains, blocks, entries and
+//! transactions.
+//!
+//! Each type is hex encoded when serialized and displayed. Api functions accept
+//! anything implementing [IntoHash](trait.IntoHash.html), which includes the
+//! hash types themselves as well as hex strings, so malformed ids are caught
+//! locally instead of surfacing as an error from the node.
+//!
+//! # Example
+//! ```
+//! use factom::hash::*;
+//!
+//! let chainid: ChainId = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef"
+//!                           .parse()
+//!                           .expect("Parsing chain id");
+//! assert_eq!(chainid.as_bytes()[0], 0xa6);
+//! assert!("a642a8".parse::<ChainId>().is_err());
+//! ```
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Error returned when parsing a hash from a hex string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseHashError {
+    /// The string contained a character that isn't a hex digit or had an odd
+    /// number of digits
+    InvalidHex,
+    /// The decoded hash wasn't 32 bytes long, holds the decoded length
+    InvalidLength(usize),
+}
+
+impl fmt::Display for ParseHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseHashError::InvalidHex => write!(f, "invalid hex string"),
+            ParseHashError::InvalidLength(len) => {
+                write!(f, "invalid hash length: expected 32 bytes, found {}", len)
+            }
+        }
+    }
+}
+
+impl Error for ParseHashError {}
+
+/// Conversion into one of the hash types, implemented for the hash types and
+/// for hex strings so api functions can be called with either.
+pub trait IntoHash<T> {
+    fn into_hash(self) -> Result<T, ParseHashError>;
+}
+
+/// Encodes bytes as a lowercase hex string
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Decodes a hex string into bytes, both upper and lowercase digits are accepted
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>, ParseHashError> {
+    // from_str_radix alone would also accept a leading + in each pair
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(ParseHashError::InvalidHex);
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| ParseHashError::InvalidHex))
+        .collect()
+}
+
+macro_rules! hash_type {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(pub [u8; 32]);
+
+        impl $name {
+            /// Returns the raw bytes of the hash
+            pub fn as_bytes(&self) -> &[u8; 32] {
+                &self.0
+            }
+
+            /// Returns true if every byte is zero, as used for the previous
+            /// keymr of the first block in a chain
+            pub fn is_zero(&self) -> bool {
+                self.0.iter().all(|b| *b == 0)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseHashError;
+
+            fn from_str(hex: &str) -> Result<Self, Self::Err> {
+                let bytes = decode_hex(hex)?;
+                if bytes.len() != 32 {
+                    return Err(ParseHashError::InvalidLength(bytes.len()));
+                }
+                let mut hash = [0u8; 32];
+                hash.copy_from_slice(&bytes);
+                Ok($name(hash))
+            }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = ParseHashError;
+
+            fn try_from(hex: &str) -> Result<Self, Self::Error> {
+                hex.parse()
+            }
+        }
+
+        impl From<[u8; 32]> for $name {
+            fn from(bytes: [u8; 32]) -> Self {
+                $name(bytes)
+            }
+        }
+
+        impl AsRef<[u8]> for $name {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", encode_hex(&self.0))
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}({})", stringify!($name), self)
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                other.parse::<$name>().map_or(false, |hash| hash == *self)
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                self == other.as_str()
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                serializer.serialize_str(&self.to_string())
+            }
+        }
+
+        /// An empty string is deserialized as the zero hash, factomd uses it
+        /// for hashes that aren't known yet
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                let hex = String::deserialize(deserializer)?;
+                if hex.is_empty() {
+                    return Ok($name::default());
+                }
+                hex.parse().map_err(serde::de::Error::custom)
+            }
+        }
+
+        impl IntoHash<$name> for $name {
+            fn into_hash(self) -> Result<$name, ParseHashError> {
+                Ok(self)
+            }
+        }
+
+        impl IntoHash<$name> for &$name {
+            fn into_hash(self) -> Result<$name, ParseHashError> {
+                Ok(*self)
+            }
+        }
+
+        impl IntoHash<$name> for &str {
+            fn into_hash(self) -> Result<$name, ParseHashError> {
+                self.parse()
+            }
+        }
+
+        impl IntoHash<$name> for String {
+            fn into_hash(self) -> Result<$name, ParseHashError> {
+                self.parse()
+            }
+        }
+
+        impl IntoHash<$name> for &String {
+            fn into_hash(self) -> Result<$name, ParseHashError> {
+                self.parse()
+            }
+        }
+    };
+}
+
+hash_type!(
+    /// Identifies a chain, the hash of the chain name (the first entry's ext ids)
+    ChainId
+);
+hash_type!(
+    /// Key merkle root identifying a directory, entry or factoid block
+    KeyMr
+);
+hash_type!(
+    /// Hash of an entry's contents
+    EntryHash
+);
+hash_type!(
+    /// Identifies a factoid transaction or an entry commit
+    TxId
+);
+hash_type!(
+    /// Any other 32 byte hash, such as the full hash, body merkle root or back
+    /// reference hash of a block
+    Hash
+);
+
+/// Any of the hash types can be passed where a plain [Hash](struct.Hash.html)
+/// is expected
+macro_rules! into_plain_hash {
+    ($($name:ident),*) => {
+        $(
+            impl IntoHash<Hash> for $name {
+                fn into_hash(self) -> Result<Hash, ParseHashError> {
+                    Ok(Hash(self.0))
+                }
+            }
+
+            impl IntoHash<Hash> for &$name {
+                fn into_hash(self) -> Result<Hash, ParseHashError> {
+                    Ok(Hash(self.0))
+                }
+            }
+        )*
+    };
+}
+
+into_plain_hash!(ChainId, KeyMr, EntryHash, TxId);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAIN_ID: &str = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+
+    #[test]
+    fn hex_round_trip() {
+        let chainid: ChainId = CHAIN_ID.parse().unwrap();
+        assert_eq!(chainid.to_string(), CHAIN_ID);
+        assert_eq!(chainid, CHAIN_ID.to_uppercase());
+        assert_eq!(decode_hex("0aFf").unwrap(), vec![0x0a, 0xff]);
+        assert_eq!(encode_hex(&[0x0a, 0xff]), "0aff");
+    }
+
+    #[test]
+    fn invalid_hashes() {
+        assert_eq!(
+            "abc".parse::<KeyMr>().unwrap_err(),
+            ParseHashError::InvalidHex
+        );
+        assert_eq!(
+            "zz".repeat(32).parse::<KeyMr>().unwrap_err(),
+            ParseHashError::InvalidHex
+        );
+        assert_eq!(
+            "ab".parse::<KeyMr>().unwrap_err(),
+            ParseHashError::InvalidLength(1)
+        );
+        assert_eq!(
+            "+f".repeat(32).parse::<ChainId>().unwrap_err(),
+            ParseHashError::InvalidHex
+        );
+        assert!(decode_hex("+f").is_err());
+    }
+
+    #[test]
+    fn serde_hex_strings() {
+        let entryhash: EntryHash = serde_json::from_str(&format!("\"{}\"", CHAIN_ID)).unwrap();
+        assert_eq!(
+            serde_json::to_string(&entryhash).unwrap(),
+            format!("\"{}\"", CHAIN_ID)
+        );
+        let empty: TxId = serde_json::from_str("\"\"").unwrap();
+        assert!(empty.is_zero());
+    }
+}
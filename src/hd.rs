@@ -0,0 +1,306 @@
+//! Offline derivation of wallet addresses from a mnemonic seed.
+//!
+//! factom-walletd generates its addresses from the BIP39 mnemonic returned as
+//! `wallet-seed` by [walletd::wallet_backup](../walletd/fn.wallet_backup.html),
+//! deriving BIP32 keys along the BIP44 paths `m/44'/131'/0'/0/i` for factoid
+//! addresses and `m/44'/132'/0'/0/i` for entry credit addresses, then using
+//! each derived key as an ed25519 private key. A [Seed](struct.Seed.html)
+//! repeats that derivation without the wallet, so addresses can be regenerated
+//! from a backup and backups checked against their seed.
+//!
+//! Enable with the `hd` feature.
+//!
+//! # Example
+//! ```
+//! use factom::address::KeyKind;
+//! use factom::hd::Seed;
+//!
+//! let mnemonic = "yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow";
+//! let seed = Seed::from_mnemonic(mnemonic).unwrap();
+//! let address = seed.address(KeyKind::Factoid, 0).unwrap();
+//! assert_eq!(address, "FA22de5NSG2FA2HmMaD4h8qSAZAJyztmmnwgLPghCQKoSekwYYct");
+//! ```
+use super::*;
+use crate::address::{encode_address, KeyKind};
+use crate::signer::KeySigner;
+use crate::walletd::WalletBackup;
+use hmac::{Hmac, Mac};
+use k256::elliptic_curve::ff::PrimeField;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use sha2::Sha512;
+use std::fmt;
+use zeroize::Zeroizing;
+
+/// BIP44 coin type of factoid addresses
+pub const FCT_COIN_TYPE: u32 = 131;
+/// BIP44 coin type of entry credit addresses
+pub const EC_COIN_TYPE: u32 = 132;
+/// Offset of hardened child indexes
+pub const HARDENED: u32 = 0x8000_0000;
+
+/// A BIP39 seed, zeroed when dropped
+pub struct Seed {
+    seed: Zeroizing<[u8; 64]>,
+}
+
+/// An extended private key, the key and its chain code
+struct ExtendedKey {
+    key: Zeroizing<[u8; 32]>,
+    chain_code: Zeroizing<[u8; 32]>,
+}
+
+impl Seed {
+    /// Checks the words and checksum of an english BIP39 mnemonic and stretches
+    /// it into a seed, with an empty passphrase like walletd
+    pub fn from_mnemonic(mnemonic: &str) -> Result<Seed> {
+        let mnemonic = bip39::Mnemonic::parse_normalized(&mnemonic.to_lowercase())
+            .map_err(|e| format!("Invalid mnemonic: {}", e))?;
+        Ok(Seed {
+            seed: Zeroizing::new(mnemonic.to_seed_normalized("")),
+        })
+    }
+
+    /// The seed of a wallet backup's `wallet-seed`
+    pub fn from_backup(backup: &WalletBackup) -> Result<Seed> {
+        Seed::from_mnemonic(backup.wallet_seed.expose_secret())
+    }
+
+    /// Derives the private key at a BIP32 path such as `m/44'/131'/0'/0/0`
+    pub fn derive_path(&self, path: &str) -> Result<Zeroizing<[u8; 32]>> {
+        let mut parts = path.split('/');
+        if parts.next() != Some("m") {
+            return Err(format!("Derivation path must start at m: {}", path).into());
+        }
+        let mut key = ExtendedKey::master(&self.seed[..]);
+        for part in parts {
+            let index = match part.strip_suffix('\'') {
+                Some(hardened) => hardened.parse::<u32>().map(|i| i | HARDENED),
+                None => part.parse::<u32>(),
+            };
+            let index = index.map_err(|_| format!("Invalid derivation path: {}", path))?;
+            key = key.child(index)?;
+        }
+        Ok(key.key)
+    }
+
+    /// The private key walletd derives for the address at index, only factoid
+    /// and entry credit addresses are derived from the seed
+    pub fn private_key(&self, kind: KeyKind, index: u32) -> Result<Zeroizing<[u8; 32]>> {
+        let coin = match kind {
+            KeyKind::Factoid => FCT_COIN_TYPE,
+            KeyKind::EntryCredit => EC_COIN_TYPE,
+            KeyKind::Identity => return Err("Identity keys aren't derived from the seed".into()),
+        };
+        self.derive_path(&format!("m/44'/{}'/0'/0/{}", coin, index))
+    }
+
+    /// A signer for the address at index
+    pub fn signer(&self, kind: KeyKind, index: u32) -> Result<KeySigner> {
+        Ok(KeySigner::new(kind, &*self.private_key(kind, index)?))
+    }
+
+    /// The public address at index, FA... or EC...
+    pub fn address(&self, kind: KeyKind, index: u32) -> Result<String> {
+        Ok(self.signer(kind, index)?.address())
+    }
+
+    /// The public addresses at indexes 0 to count
+    pub fn addresses(&self, kind: KeyKind, count: u32) -> Result<Vec<String>> {
+        (0..count).map(|i| self.address(kind, i)).collect()
+    }
+
+    /// The factoid key of a Koinify crowdsale phrase, derived like walletd's
+    /// import-koinify at `m/7'` of the phrase's seed
+    pub fn koinify_key(&self) -> Result<Zeroizing<[u8; 32]>> {
+        self.derive_path(KOINIFY_PATH)
+    }
+}
+
+/// Derivation path of Koinify crowdsale addresses
+pub const KOINIFY_PATH: &str = "m/7'";
+
+impl fmt::Debug for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Seed([REDACTED])")
+    }
+}
+
+impl ExtendedKey {
+    fn master(seed: &[u8]) -> ExtendedKey {
+        ExtendedKey::from_hmac(b"Bitcoin seed", &[seed])
+    }
+
+    /// Splits HMAC-SHA512 of the data into a key and chain code
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> ExtendedKey {
+        let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("Hmac takes any key length");
+        for part in data {
+            mac.update(part);
+        }
+        let digest = Zeroizing::new(mac.finalize().into_bytes());
+        let mut extended = ExtendedKey {
+            key: Zeroizing::new([0; 32]),
+            chain_code: Zeroizing::new([0; 32]),
+        };
+        extended.key.copy_from_slice(&digest[..32]);
+        extended.chain_code.copy_from_slice(&digest[32..]);
+        extended
+    }
+
+    fn child(&self, index: u32) -> Result<ExtendedKey> {
+        let index_bytes = index.to_be_bytes();
+        let derived = if index >= HARDENED {
+            ExtendedKey::from_hmac(&self.chain_code[..], &[&[0], &self.key[..], &index_bytes])
+        } else {
+            let secret =
+                k256::SecretKey::from_slice(&self.key[..]).map_err(|_| "Invalid parent key")?;
+            let public = secret.public_key().to_encoded_point(true);
+            ExtendedKey::from_hmac(&self.chain_code[..], &[public.as_bytes(), &index_bytes])
+        };
+        // The child key is the derived tweak added to the parent key mod n,
+        // indexes giving an invalid key are skipped by BIP32
+        let tweak = Option::<k256::Scalar>::from(k256::Scalar::from_repr((*derived.key).into()))
+            .ok_or("Invalid child key, use the next index")?;
+        let parent = Option::<k256::Scalar>::from(k256::Scalar::from_repr((*self.key).into()))
+            .ok_or("Invalid parent key")?;
+        let child = tweak + parent;
+        if bool::from(child.is_zero()) {
+            return Err("Invalid child key, use the next index".into());
+        }
+        let mut key = Zeroizing::new([0; 32]);
+        key.copy_from_slice(&child.to_bytes());
+        Ok(ExtendedKey {
+            key,
+            chain_code: derived.chain_code,
+        })
+    }
+}
+
+/// Checks a wallet backup against its seed, returning the public addresses of
+/// the backup that the seed doesn't derive, either imported addresses or a
+/// mismatched secret. walletd derives addresses in order from index 0, so the
+/// first n addresses of each kind are checked, n being the number of addresses
+/// of that kind in the backup.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::new();
+///   let backup = walletd::wallet_backup(&client).await.unwrap().result;
+///   let imported = hd::verify_backup(&backup).unwrap();
+///   println!("Addresses not derived from the seed: {:?}", imported);
+/// }
+/// ```
+pub fn verify_backup(backup: &WalletBackup) -> Result<Vec<String>> {
+    let seed = Seed::from_backup(backup)?;
+    let mut derived = Vec::new();
+    for kind in [KeyKind::Factoid, KeyKind::EntryCredit].iter().copied() {
+        let count = backup
+            .addresses
+            .iter()
+            .filter(|a| KeyKind::of_public(&a.public) == Some(kind))
+            .count();
+        for index in 0..count as u32 {
+            let key = seed.private_key(kind, index)?;
+            let signer = KeySigner::new(kind, &key);
+            let secret = Zeroizing::new(encode_address(kind.secret_prefix(), &key));
+            derived.push((signer.address(), secret));
+        }
+    }
+    let unmatched = backup
+        .addresses
+        .iter()
+        .filter(|a| {
+            !derived.iter().any(|(public, secret)| {
+                *public == a.public && secret.as_str() == a.secret.expose_secret()
+            })
+        })
+        .map(|a| a.public.clone())
+        .collect();
+    Ok(unmatched)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::walletd::Address;
+
+    const YELLOW: &str =
+        "yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow";
+
+    #[test]
+    fn bip32_test_vector() {
+        // BIP32 test vector 1, chain m/0'/1/2'/2/1000000000
+        let seed = Seed {
+            seed: Zeroizing::new([0; 64]),
+        };
+        let master = ExtendedKey::master(&decode_hex("000102030405060708090a0b0c0d0e0f"));
+        let key = [HARDENED, 1, 2 | HARDENED, 2, 1_000_000_000]
+            .iter()
+            .fold(master, |key, index| key.child(*index).unwrap());
+        assert_eq!(
+            hash::encode_hex(&key.key[..]),
+            "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8"
+        );
+        assert!(seed.derive_path("44'/0").is_err());
+        assert!(seed.derive_path("m/x").is_err());
+    }
+
+    #[test]
+    fn koinify() {
+        let seed = Seed::from_mnemonic(YELLOW).unwrap();
+        let signer = KeySigner::new(KeyKind::Factoid, &seed.koinify_key().unwrap());
+        assert_eq!(
+            signer.address(),
+            "FA3cih2o2tjEUsnnFR4jX1tQXPpSXFwsp3rhVp6odL5PNCHWvZV1"
+        );
+    }
+
+    #[test]
+    fn walletd_addresses() {
+        let seed = Seed::from_mnemonic(YELLOW).unwrap();
+        let fct = seed.addresses(KeyKind::Factoid, 2).unwrap();
+        assert_eq!(
+            fct[0],
+            "FA22de5NSG2FA2HmMaD4h8qSAZAJyztmmnwgLPghCQKoSekwYYct"
+        );
+        assert_ne!(fct[0], fct[1]);
+        assert!(seed
+            .address(KeyKind::EntryCredit, 0)
+            .unwrap()
+            .starts_with("EC"));
+        assert!(seed.address(KeyKind::Identity, 0).is_err());
+        assert!(Seed::from_mnemonic("yellow yellow").is_err());
+    }
+
+    #[test]
+    fn backup_verification() {
+        let seed = Seed::from_mnemonic(YELLOW).unwrap();
+        let derived = |kind, index| {
+            let key = seed.private_key(kind, index).unwrap();
+            Address {
+                public: seed.address(kind, index).unwrap(),
+                secret: encode_address(kind.secret_prefix(), &key).into(),
+            }
+        };
+        let imported = Address {
+            public: "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK".to_string(),
+            secret: "Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk".into(),
+        };
+        let backup = WalletBackup {
+            wallet_seed: YELLOW.into(),
+            addresses: vec![
+                derived(KeyKind::Factoid, 0),
+                derived(KeyKind::EntryCredit, 0),
+                imported.clone(),
+            ],
+        };
+        assert_eq!(verify_backup(&backup).unwrap(), vec![imported.public]);
+    }
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        hash::decode_hex(hex).unwrap()
+    }
+}
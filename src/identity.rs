@@ -1,5 +1,6 @@
 //! Relating to identity functions.
 use super::*;
+use crate::secret::Secret;
 
 /// Returns all of the identity key pairs that are currently stored in the wallet.
 /// If the wallet is encrypted, it must be unlocked prior to using this command.
@@ -58,11 +59,12 @@ pub async fn all_id_keys(api: &Factom) -> Result<ApiResponse<IdKeys>> {
 /// ```
 pub async fn active_id_keys(
     api: &Factom,
-    chain_id: &str,
+    chainid: impl IntoHash<ChainId>,
     height: Option<usize>,
 ) -> Result<ApiResponse<ActiveIdKeys>> {
+    let chainid = chainid.into_hash()?;
     let mut req = ApiRequest::new("active-identity-keys");
-    req.params.insert("chainid".to_string(), json!(chain_id));
+    req.params.insert("chainid".to_string(), json!(chainid));
     match height {
         Some(height) => req.params.insert("height".to_string(), json!(height)),
         None => None,
@@ -71,6 +73,22 @@ pub async fn active_id_keys(
     parse(response).await
 }
 
+/// Returns the keys of an identity that were active at the given height,
+/// like active_id_keys with a required height. This is the name the method had
+/// in older releases of factom-walletd.
+pub async fn id_keys_at_height(
+    api: &Factom,
+    chainid: impl IntoHash<ChainId>,
+    height: usize,
+) -> Result<ApiResponse<ActiveIdKeys>> {
+    let chainid = chainid.into_hash()?;
+    let mut req = ApiRequest::new("identity-keys-at-height");
+    req.params.insert("chainid".to_string(), json!(chainid));
+    req.params.insert("height".to_string(), json!(height));
+    let response = walletd_call(api, req).await;
+    parse(response).await
+}
+
 ///  **Be careful using this function! Ensure that you have backups of important keys
 ///  before removing them.** Given an identity public key, this command deletes the
 ///  corresponding identity key pair from the wallet. Once executed, the user will
@@ -137,10 +155,10 @@ pub struct IdKeys {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Key {
     pub public: String,
-    pub secret: String,
+    pub secret: Secret,
 }
 
-/// active-identity-keys function
+/// active-identity-keys and identity-keys-at-height functions
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ActiveIdKeys {
     pub chainid: String,
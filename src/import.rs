@@ -1,5 +1,6 @@
 //! For importing addresses or identities
 use super::*;
+use crate::secret::Secret;
 use std::collections::HashMap;
 
 /// Import Factoid and/or Entry Credit address secret keys into the wallet.
@@ -68,7 +69,8 @@ pub async fn import_identity_keys(api: &Factom, keys: Vec<&str>) -> Result<ApiRe
 /// Import a Koinify crowd sale address into the wallet. In our examples we used
 /// the word “yellow” twelve times, note that in your case the master passphrase
 /// will be different. If the wallet is encrypted, it must be unlocked prior to
-/// using this command.
+/// using this command. With the `hd` feature the address can be derived
+/// locally instead with [koinify_address](fn.koinify_address.html).
 ///
 /// # Example
 /// ```
@@ -92,6 +94,29 @@ pub async fn import_koinify(api: &Factom, phrase: &str) -> Result<ApiResponse<Ad
     parse(response).await
 }
 
+/// Derives the Koinify crowd sale address of a phrase locally, returning the
+/// same address as [import_koinify](fn.import_koinify.html) without sending the
+/// phrase to walletd. The secret can then be kept offline or imported with
+/// [import_addresses](fn.import_addresses.html). Requires the `hd` feature.
+///
+/// # Example
+/// ```
+/// use factom::*;
+///
+/// let koinify_phrase = "yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow yellow";
+/// let address = import::koinify_address(koinify_phrase).unwrap();
+/// assert_eq!(address.public, "FA3cih2o2tjEUsnnFR4jX1tQXPpSXFwsp3rhVp6odL5PNCHWvZV1");
+/// ```
+#[cfg(feature = "hd")]
+pub fn koinify_address(phrase: &str) -> Result<Address> {
+    use crate::address::{encode_address, KeyKind};
+    let key = hd::Seed::from_mnemonic(phrase)?.koinify_key()?;
+    Ok(Address {
+        public: signer::KeySigner::new(KeyKind::Factoid, &key).address(),
+        secret: Secret::new(encode_address(KeyKind::Factoid.secret_prefix(), &key)),
+    })
+}
+
 /// import-addresses function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Addresses {
@@ -101,7 +126,7 @@ pub struct Addresses {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Address {
     pub public: String,
-    pub secret: String,
+    pub secret: Secret,
 }
 
 /// import-identity-keys function
@@ -113,5 +138,5 @@ pub struct Keys {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Key {
     pub public: String,
-    pub secret: String,
+    pub secret: Secret,
 }
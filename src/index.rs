@@ -0,0 +1,550 @@
+//! A local index of the blockchain backed by an embedded sled database,
+//! available with the `index` feature.
+//!
+//! Syncing fetches the directory, entry, entry credit and factoid blocks of
+//! each height through the block module, once synced the index answers queries
+//! without contacting a node.
+//!
+//! # Example
+//! ```no_run
+//! use factom::*;
+//! use factom::index::Index;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!   let client = Factom::open_node();
+//!   let index = Index::open("factom-index").expect("Opening index");
+//!   index.sync(&client, 220000, Some(220010)).await.expect("Syncing index");
+//!   let chain = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+//!   for entry in index.entries_by_chain(chain).expect("Querying index") {
+//!     println!("{} {}", entry.dbheight, entry.entryhash);
+//!   }
+//! }
+//! ```
+use super::*;
+use crate::block::{DblockHeight, EBlock, Ecblock, Fblock, Transaction};
+use crate::traverse::{block_entries, ChainBlock, ChainEntry};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+use sled::Tree;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Number of blocks and entries fetched concurrently while syncing
+const CONCURRENCY: usize = 8;
+/// Key of the last synced height in the meta tree
+const SYNCED: &[u8] = b"synced";
+
+/// An entry credit spend recorded in an entry credit block
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EcSpend {
+    pub dbheight: u32,
+    pub ecpubkey: String,
+    pub entryhash: EntryHash,
+    pub credits: usize,
+    pub millitime: String,
+}
+
+/// A local index of directory blocks, entries, factoid transactions and entry
+/// credit spends.
+pub struct Index {
+    db: sled::Db,
+    meta: Tree,
+    /// height -> directory block
+    dblocks: Tree,
+    /// chainid, height, position -> entry
+    entries: Tree,
+    /// ext id, entry key -> ()
+    extids: Tree,
+    /// txid -> factoid transaction
+    transactions: Tree,
+    /// address, height, txid -> ()
+    addresses: Tree,
+    /// ec public key, height, position -> spend
+    ec_spends: Tree,
+}
+
+impl Index {
+    /// Opens or creates an index at the given path
+    pub fn open(path: impl AsRef<Path>) -> Result<Index> {
+        Index::with_db(sled::open(path)?)
+    }
+
+    /// Uses an already opened sled database, for example a temporary one
+    pub fn with_db(db: sled::Db) -> Result<Index> {
+        Ok(Index {
+            meta: db.open_tree("meta")?,
+            dblocks: db.open_tree("dblocks")?,
+            entries: db.open_tree("entries")?,
+            extids: db.open_tree("extids")?,
+            transactions: db.open_tree("transactions")?,
+            addresses: db.open_tree("addresses")?,
+            ec_spends: db.open_tree("ec_spends")?,
+            db,
+        })
+    }
+
+    /// The last height synced, None if nothing has been synced yet
+    pub fn synced_height(&self) -> Result<Option<u32>> {
+        Ok(self.meta.get(SYNCED)?.map(|height| be_u32(&height)))
+    }
+
+    /// Syncs the heights from `from`, or after the last synced height if it is
+    /// higher, up to and including `to`. Without `to` the index syncs up to
+    /// the node's last saved height. Returns the last synced height, which can
+    /// be past `to` if the index was already synced further, or None if nothing
+    /// has been synced yet.
+    ///
+    /// Each height is recorded as synced once all of its blocks are indexed,
+    /// so an interrupted sync resumes at the height it failed on. Heights
+    /// can't be skipped: once the index holds a height, `from` must be at most
+    /// the height after it.
+    pub async fn sync(&self, api: &Factom, from: u32, to: Option<u32>) -> Result<Option<u32>> {
+        let mut height = match self.synced_height()? {
+            Some(synced) if from > synced + 1 => {
+                return Err(format!(
+                    "Index is synced to height {}, syncing from {} would leave a gap",
+                    synced, from
+                )
+                .into())
+            }
+            Some(synced) if synced >= from => synced + 1,
+            _ => from,
+        };
+        let to = match to {
+            Some(to) => to,
+            None => {
+                let heights = factomd::heights(api).await?.into_result()?;
+                heights.directoryblockheight as u32
+            }
+        };
+        while height <= to {
+            self.sync_height(api, height).await?;
+            height += 1;
+        }
+        self.db.flush_async().await?;
+        self.synced_height()
+    }
+
+    async fn sync_height(&self, api: &Factom, height: u32) -> Result<()> {
+        let dblock = block::dblock_by_height(api, height)
+            .await?
+            .into_result()?
+            .dblock;
+        let mut chains = Vec::new();
+        for dbentry in &dblock.dbentries {
+            if dbentry.chainid == EC_CHAINID {
+                let ecblock = block::entry_credit_block(api, dbentry.keymr).await?;
+                self.index_ecblock(height, &ecblock.into_result()?.ecblock)?;
+            } else if dbentry.chainid == FACTOID_CHAINID {
+                let fblock = block::factoid_block(api, dbentry.keymr).await?;
+                self.index_fblock(height, &fblock.into_result()?.fblock)?;
+            } else if dbentry.chainid != ADMIN_CHAINID {
+                chains.push(dbentry.keymr);
+            }
+        }
+        let blocks: Vec<ChainBlock> = stream::iter(chains)
+            .map(|keymr| async move {
+                let block: EBlock = block::entry_block(api, keymr).await?.into_result()?;
+                Result::<ChainBlock>::Ok(ChainBlock { keymr, block })
+            })
+            .buffered(CONCURRENCY)
+            .try_collect()
+            .await?;
+        let fetches = blocks
+            .into_iter()
+            .flat_map(|block| block_entries(api, block, true));
+        let entries: Vec<ChainEntry> = stream::iter(fetches)
+            .buffered(CONCURRENCY)
+            .try_collect()
+            .await?;
+        self.index_entries(&entries)?;
+        self.dblocks.insert(height.to_be_bytes(), json(&dblock)?)?;
+        self.meta.insert(SYNCED, &height.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn index_entries(&self, entries: &[ChainEntry]) -> Result<()> {
+        for entry in entries {
+            let key = entry_key(entry);
+            for extid in entry.entry.extid_bytes()? {
+                self.extids
+                    .insert([&extid[..], &key[..]].concat(), sled::IVec::default())?;
+            }
+            self.entries.insert(key, json(entry)?)?;
+        }
+        Ok(())
+    }
+
+    fn index_fblock(&self, height: u32, fblock: &Fblock) -> Result<()> {
+        for tx in &fblock.transactions {
+            let inputs = tx.inputs.iter().map(|i| i.useraddress.as_str());
+            let outputs = tx.outputs.iter().map(|o| o.useraddress.as_str());
+            let ec_outputs = tx.outecs.iter().filter_map(|o| o["useraddress"].as_str());
+            for address in inputs.chain(outputs).chain(ec_outputs) {
+                let key = [
+                    address.as_bytes(),
+                    &height.to_be_bytes(),
+                    tx.txid.as_bytes(),
+                ];
+                self.addresses.insert(key.concat(), sled::IVec::default())?;
+            }
+            self.transactions.insert(tx.txid.as_bytes(), json(tx)?)?;
+        }
+        Ok(())
+    }
+
+    fn index_ecblock(&self, height: u32, ecblock: &Ecblock) -> Result<()> {
+        for (position, entry) in ecblock.body.entries.iter().enumerate() {
+            let (ecpubkey, entryhash) = match (&entry.ecpubkey, entry.entryhash) {
+                (Some(ecpubkey), Some(entryhash)) => (ecpubkey, entryhash),
+                // Server index markers and minute numbers
+                _ => continue,
+            };
+            let spend = EcSpend {
+                dbheight: height,
+                ecpubkey: ecpubkey.clone(),
+                entryhash,
+                credits: entry.credits.unwrap_or_default(),
+                millitime: entry.millitime.clone().unwrap_or_default(),
+            };
+            let key = [
+                &hash::decode_hex(ecpubkey)?[..],
+                &height.to_be_bytes(),
+                &(position as u32).to_be_bytes(),
+            ];
+            self.ec_spends.insert(key.concat(), json(&spend)?)?;
+        }
+        Ok(())
+    }
+
+    /// The directory block at the given height
+    pub fn dblock(&self, height: u32) -> Result<Option<DblockHeight>> {
+        self.dblocks
+            .get(height.to_be_bytes())?
+            .map(|value| from_json(&value))
+            .transpose()
+    }
+
+    /// The entries of a chain, oldest first
+    pub fn entries_by_chain(&self, chainid: impl IntoHash<ChainId>) -> Result<Vec<ChainEntry>> {
+        let chainid = chainid.into_hash()?;
+        self.entries
+            .scan_prefix(chainid.as_bytes())
+            .values()
+            .map(|value| from_json(&value?))
+            .collect()
+    }
+
+    /// The entries with an ext id starting with the prefix, ordered by ext id
+    pub fn entries_by_extid_prefix(&self, prefix: impl AsRef<[u8]>) -> Result<Vec<ChainEntry>> {
+        let mut seen = HashSet::new();
+        let mut entries = Vec::new();
+        for key in self.extids.scan_prefix(prefix).keys() {
+            let key = key?;
+            let entry_key = &key[key.len() - ENTRY_KEY_LEN..];
+            // An entry is listed once for each matching ext id
+            if !seen.insert(entry_key.to_vec()) {
+                continue;
+            }
+            if let Some(value) = self.entries.get(entry_key)? {
+                entries.push(from_json(&value)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// The factoid transactions with an input or output for the address,
+    /// oldest first
+    pub fn transactions_by_address(&self, address: &str) -> Result<Vec<Transaction>> {
+        let mut transactions = Vec::new();
+        for key in self.addresses.scan_prefix(address.as_bytes()).keys() {
+            let key = key?;
+            let txid = &key[key.len() - 32..];
+            if let Some(value) = self.transactions.get(txid)? {
+                transactions.push(from_json(&value)?);
+            }
+        }
+        Ok(transactions)
+    }
+
+    /// The entry credit spends signed by the hex encoded public key, oldest
+    /// first
+    pub fn ec_spends_by_pubkey(&self, ecpubkey: &str) -> Result<Vec<EcSpend>> {
+        let ecpubkey = hash::decode_hex(ecpubkey)?;
+        self.ec_spends
+            .scan_prefix(ecpubkey)
+            .values()
+            .map(|value| from_json(&value?))
+            .collect()
+    }
+}
+
+/// Length of an entries key: chainid, directory block height, position
+const ENTRY_KEY_LEN: usize = 40;
+
+fn entry_key(entry: &ChainEntry) -> Vec<u8> {
+    [
+        &entry.entry.chainid.as_bytes()[..],
+        &(entry.dbheight as u32).to_be_bytes(),
+        &(entry.index as u32).to_be_bytes(),
+    ]
+    .concat()
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    let mut be = [0u8; 4];
+    be.copy_from_slice(&bytes[..4]);
+    u32::from_be_bytes(be)
+}
+
+fn json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(value)?)
+}
+
+fn from_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Body, DBlockHeightHeader, Dbentry, EBlockHeader, Entrylist, Input, Output};
+    use crate::entry::Entry;
+    use crate::testing::{MockNode, MockReply};
+    use futures::executor::block_on;
+    use serde_json::Value;
+
+    const CHAIN: &str = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+    const FROM: &str = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+    const TO: &str = "FA3cih2o2tjEUsnnFR4jX1tQXPpSXFwsp3rhVp6odL5PNCHWvZV1";
+    const EC: &str = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+
+    fn chain_entry(chainid: u8, dbheight: usize, index: usize, extids: Vec<&str>) -> ChainEntry {
+        ChainEntry {
+            entryhash: EntryHash([dbheight as u8; 32]),
+            dbheight,
+            index,
+            entry: Entry {
+                chainid: ChainId([chainid; 32]),
+                content: String::new(),
+                extids: extids
+                    .iter()
+                    .map(|id| hash::encode_hex(id.as_bytes()))
+                    .collect(),
+            },
+            ..ChainEntry::default()
+        }
+    }
+
+    #[test]
+    fn entry_queries() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let index = Index::with_db(db).unwrap();
+        let entries = vec![
+            chain_entry(1, 300, 0, vec!["transfer", "alice"]),
+            chain_entry(1, 20, 1, vec!["issuance"]),
+            chain_entry(2, 25, 0, vec!["transfer"]),
+        ];
+        index.index_entries(&entries).unwrap();
+
+        let chain = index.entries_by_chain(ChainId([1; 32])).unwrap();
+        assert_eq!(chain, vec![entries[1].clone(), entries[0].clone()]);
+
+        let transfers = index.entries_by_extid_prefix("trans").unwrap();
+        assert_eq!(transfers.len(), 2);
+        assert!(index.entries_by_extid_prefix("bob").unwrap().is_empty());
+    }
+
+    fn temporary() -> Index {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        Index::with_db(db).unwrap()
+    }
+
+    /// Block keymrs start with the height, the second byte tells the chains
+    /// apart
+    fn height(params: &Value) -> u8 {
+        let keymr: KeyMr = params["keymr"].as_str().unwrap().parse().unwrap();
+        keymr.0[0]
+    }
+
+    /// A node where each height holds one entry, one factoid transaction and
+    /// one entry credit spend
+    fn chain_node() -> MockNode {
+        let node = MockNode::new();
+        node.respond("heights", json!({"directoryblockheight": 3}))
+            .respond_with("dblock-by-height", |params| {
+                let height = params["height"].as_u64().unwrap() as u8;
+                let dbentry = |chainid: &str, chain: u8| {
+                    let mut keymr = [height; 32];
+                    keymr[1] = chain;
+                    Dbentry {
+                        chainid: chainid.parse().unwrap(),
+                        keymr: KeyMr(keymr),
+                    }
+                };
+                let dblock = DblockHeight {
+                    header: DBlockHeightHeader {
+                        dbheight: height as usize,
+                        ..DBlockHeightHeader::default()
+                    },
+                    dbentries: vec![
+                        dbentry(ADMIN_CHAINID, 0),
+                        dbentry(EC_CHAINID, 1),
+                        dbentry(FACTOID_CHAINID, 2),
+                        dbentry(CHAIN, 3),
+                    ],
+                    ..DblockHeight::default()
+                };
+                MockReply::Result(json!({ "dblock": dblock }))
+            })
+            .respond_with("entrycredit-block", |params| {
+                let height = height(params);
+                let spend = block::Entry {
+                    entryhash: Some(EntryHash([height; 32])),
+                    credits: Some(height as usize),
+                    ecpubkey: Some("ab".repeat(32)),
+                    millitime: Some("1".to_string()),
+                    ..block::Entry::default()
+                };
+                let minute = block::Entry {
+                    number: Some(1),
+                    ..block::Entry::default()
+                };
+                let ecblock = Ecblock {
+                    body: Body {
+                        entries: vec![spend, minute],
+                    },
+                    ..Ecblock::default()
+                };
+                MockReply::Result(json!({ "ecblock": ecblock }))
+            })
+            .respond_with("factoid-block", |params| {
+                let height = height(params);
+                let tx = Transaction {
+                    txid: TxId([height; 32]),
+                    blockheight: height as usize,
+                    inputs: vec![Input {
+                        amount: 2,
+                        useraddress: FROM.to_string(),
+                        ..Input::default()
+                    }],
+                    outputs: vec![Output {
+                        amount: 1,
+                        useraddress: TO.to_string(),
+                        ..Output::default()
+                    }],
+                    outecs: vec![json!({"amount": 1, "useraddress": EC})],
+                    ..Transaction::default()
+                };
+                let fblock = Fblock {
+                    transactions: vec![tx],
+                    ..Fblock::default()
+                };
+                MockReply::Result(json!({ "fblock": fblock }))
+            })
+            .respond_with("entry-block", |params| {
+                let height = height(params);
+                let eblock = EBlock {
+                    header: EBlockHeader {
+                        chainid: CHAIN.parse().unwrap(),
+                        dbheight: height as usize,
+                        ..EBlockHeader::default()
+                    },
+                    entrylist: vec![Entrylist {
+                        entryhash: EntryHash([height; 32]),
+                        ..Entrylist::default()
+                    }],
+                };
+                MockReply::Result(json!(eblock))
+            })
+            .respond_with("entry", |params| {
+                let hash: EntryHash = params["hash"].as_str().unwrap().parse().unwrap();
+                let extid = hash::encode_hex(format!("height {}", hash.0[0]).as_bytes());
+                MockReply::Result(json!({"chainid": CHAIN, "extids": [extid]}))
+            });
+        node
+    }
+
+    fn synced_heights(node: &MockNode) -> Vec<u64> {
+        node.requests_for("dblock-by-height")
+            .iter()
+            .map(|request| request.params["height"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sync_indexes_blocks() {
+        let node = chain_node();
+        let index = temporary();
+        let synced = block_on(index.sync(&node.client(), 1, Some(2))).unwrap();
+        assert_eq!(synced, Some(2));
+        assert_eq!(index.dblock(2).unwrap().unwrap().header.dbheight, 2);
+        assert!(index.dblock(3).unwrap().is_none());
+
+        let entries = index.entries_by_chain(CHAIN).unwrap();
+        let heights: Vec<_> = entries.iter().map(|entry| entry.dbheight).collect();
+        assert_eq!(heights, vec![1, 2]);
+        assert_eq!(index.entries_by_extid_prefix("height 2").unwrap().len(), 1);
+        // Admin blocks aren't fetched
+        assert!(node.requests_for("admin-block").is_empty());
+    }
+
+    #[test]
+    fn factoid_transactions() {
+        let node = chain_node();
+        let index = temporary();
+        block_on(index.sync(&node.client(), 1, Some(2))).unwrap();
+        for address in &[FROM, TO, EC] {
+            let txids: Vec<_> = index
+                .transactions_by_address(address)
+                .unwrap()
+                .iter()
+                .map(|tx| tx.txid)
+                .collect();
+            assert_eq!(txids, vec![TxId([1; 32]), TxId([2; 32])], "{}", address);
+        }
+        let other = "FA3EPZYqodgyEGXNMbiZKE5TS2x2J9wF8J9MvPZb52iGR78xMgCb";
+        assert!(index.transactions_by_address(other).unwrap().is_empty());
+    }
+
+    #[test]
+    fn ec_spends() {
+        let node = chain_node();
+        let index = temporary();
+        block_on(index.sync(&node.client(), 1, Some(2))).unwrap();
+        let spends = index.ec_spends_by_pubkey(&"ab".repeat(32)).unwrap();
+        // Minute markers aren't spends
+        assert_eq!(spends.len(), 2);
+        assert_eq!(spends[1].dbheight, 2);
+        assert_eq!(spends[1].credits, 2);
+        assert_eq!(spends[1].entryhash, EntryHash([2; 32]));
+        assert!(index
+            .ec_spends_by_pubkey(&"cd".repeat(32))
+            .unwrap()
+            .is_empty());
+        assert!(index.ec_spends_by_pubkey("not hex").is_err());
+    }
+
+    #[test]
+    fn sync_resumes() {
+        let node = chain_node();
+        let client = node.client();
+        let index = temporary();
+        assert_eq!(block_on(index.sync(&client, 5, Some(4))).unwrap(), None);
+        assert_eq!(block_on(index.sync(&client, 1, Some(2))).unwrap(), Some(2));
+        node.clear_requests();
+
+        // Already synced past `to`
+        assert_eq!(block_on(index.sync(&client, 1, Some(1))).unwrap(), Some(2));
+        assert!(synced_heights(&node).is_empty());
+
+        // Syncs up to the node's height after the last synced height
+        assert_eq!(block_on(index.sync(&client, 1, None)).unwrap(), Some(3));
+        assert_eq!(synced_heights(&node), vec![3]);
+
+        let gap = block_on(index.sync(&client, 5, Some(6))).unwrap_err();
+        assert!(gap.to_string().contains("leave a gap"), "{}", gap);
+        assert_eq!(index.synced_height().unwrap(), Some(3));
+    }
+}
@@ -0,0 +1,502 @@
+//! An encrypted local keystore for factoid, entry credit and identity keys.
+//!
+//! The [Keystore](struct.Keystore.html) keeps Fs, Es and idsec keys outside of
+//! factom-walletd, in a json file encrypted with a passphrase. The passphrase
+//! is stretched with scrypt and every key is sealed separately with
+//! XChaCha20-Poly1305, bound to its public address. Public addresses can be
+//! listed while the keystore is locked, secrets and signing need it unlocked.
+//! Like [walletd::unlock_wallet](../walletd/fn.unlock_wallet.html) the keystore
+//! is unlocked for a number of seconds, after which it locks itself again and
+//! forgets the derived key.
+//!
+//! Keys are used offline through a [KeySigner](../signer/struct.KeySigner.html)
+//! so services can sign without a running wallet.
+//!
+//! Enable with the `keystore` feature.
+//!
+//! # Example
+//! ```
+//! use factom::address::KeyKind;
+//! use factom::keystore::{KdfParams, Keystore};
+//! use factom::signer::Signer;
+//!
+//! let path = std::env::temp_dir().join("factom-keystore-doc.json");
+//! # let _ = std::fs::remove_file(&path);
+//! // Cheap parameters for the example, keep the defaults for real keys
+//! let params = KdfParams { log_n: 10, ..KdfParams::default() };
+//! let mut keystore = Keystore::create(&path, "passphrase", params).unwrap();
+//! keystore.unlock("passphrase", 60).unwrap();
+//! let ec = keystore.import("Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk").unwrap();
+//! let identity = keystore.generate(KeyKind::Identity).unwrap();
+//!
+//! let signed = keystore.signer(&ec).unwrap().sign_data(b"attestation");
+//! assert!(signed.verify(b"attestation").unwrap());
+//!
+//! // The addresses are readable without the passphrase
+//! let keystore = Keystore::open(&path).unwrap();
+//! assert!(keystore.is_locked());
+//! assert_eq!(keystore.addresses(), vec![ec, identity]);
+//! # std::fs::remove_file(&path).unwrap();
+//! ```
+use super::*;
+use crate::address::{public_from_secret, KeyKind};
+use crate::hash::{decode_hex, encode_hex};
+use crate::secret::Secret;
+use crate::signer::KeySigner;
+use crate::walletd::UnlockWallet;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+const VERSION: u8 = 1;
+/// Sealed in every keystore so a wrong passphrase is detected even when it
+/// holds no keys
+const CHECK: &[u8] = b"factom keystore";
+
+/// Cost of the scrypt passphrase stretching
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Log2 of the scrypt cost parameter
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+/// The keystore as stored on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u8,
+    kdf: Kdf,
+    check: Sealed,
+    keys: Vec<StoredKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Kdf {
+    algorithm: String,
+    #[serde(flatten)]
+    params: KdfParams,
+    salt: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredKey {
+    public: String,
+    kind: KeyKind,
+    #[serde(flatten)]
+    sealed: Sealed,
+}
+
+/// The key derived from the passphrase and when it expires
+struct Unlocked {
+    key: Zeroizing<[u8; 32]>,
+    until: Instant,
+}
+
+/// Passphrase encrypted store of factoid, entry credit and identity keys,
+/// saved to its file after every change
+pub struct Keystore {
+    path: Option<PathBuf>,
+    file: KeystoreFile,
+    unlocked: RefCell<Option<Unlocked>>,
+}
+
+impl Keystore {
+    /// Creates an empty keystore held in memory only, locked
+    pub fn new(passphrase: &str, params: KdfParams) -> Result<Keystore> {
+        let salt = random::<16>()?;
+        let key = derive_key(passphrase, &salt, params)?;
+        let file = KeystoreFile {
+            version: VERSION,
+            kdf: Kdf {
+                algorithm: "scrypt".to_string(),
+                params,
+                salt: encode_hex(&salt),
+            },
+            check: seal(&key, CHECK, b"")?,
+            keys: Vec::new(),
+        };
+        Ok(Keystore {
+            path: None,
+            file,
+            unlocked: RefCell::new(None),
+        })
+    }
+
+    /// Creates an empty keystore file, locked. Fails if the file exists.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        passphrase: &str,
+        params: KdfParams,
+    ) -> Result<Keystore> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(format!("Keystore already exists: {}", path.display()).into());
+        }
+        let mut keystore = Keystore::new(passphrase, params)?;
+        keystore.path = Some(path.to_path_buf());
+        keystore.save(&keystore.file)?;
+        Ok(keystore)
+    }
+
+    /// Opens a keystore file, locked
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Keystore> {
+        let file: KeystoreFile = serde_json::from_slice(&fs::read(path.as_ref())?)?;
+        if file.version != VERSION || file.kdf.algorithm != "scrypt" {
+            return Err("Unsupported keystore version".into());
+        }
+        Ok(Keystore {
+            path: Some(path.as_ref().to_path_buf()),
+            file,
+            unlocked: RefCell::new(None),
+        })
+    }
+
+    /// Unlocks the keystore for timeout seconds, returning when it locks again
+    /// as a unix timestamp like walletd's unlock-wallet
+    pub fn unlock(&self, passphrase: &str, timeout: usize) -> Result<UnlockWallet> {
+        let salt = decode_hex(&self.file.kdf.salt)?;
+        let key = derive_key(passphrase, &salt, self.file.kdf.params)?;
+        open(&key, &self.file.check, b"").map_err(|_| "Incorrect passphrase")?;
+        let timeout = Duration::from_secs(timeout as u64);
+        *self.unlocked.borrow_mut() = Some(Unlocked {
+            key,
+            until: Instant::now() + timeout,
+        });
+        let until = SystemTime::now().duration_since(UNIX_EPOCH)? + timeout;
+        Ok(UnlockWallet {
+            success: true,
+            unlockeduntil: until.as_secs() as i64,
+        })
+    }
+
+    /// Locks the keystore, forgetting the key derived from the passphrase
+    pub fn lock(&self) {
+        self.unlocked.borrow_mut().take();
+    }
+
+    /// Whether the keystore is locked, either never unlocked or timed out
+    pub fn is_locked(&self) -> bool {
+        let mut unlocked = self.unlocked.borrow_mut();
+        if unlocked.as_ref().is_some_and(|u| Instant::now() >= u.until) {
+            unlocked.take();
+        }
+        unlocked.is_none()
+    }
+
+    /// The public addresses of the stored keys, in the order they were added
+    pub fn addresses(&self) -> Vec<String> {
+        self.file.keys.iter().map(|k| k.public.clone()).collect()
+    }
+
+    pub fn contains(&self, public: &str) -> bool {
+        self.file.keys.iter().any(|k| k.public == public)
+    }
+
+    /// Stores a secret address, Fs..., Es... or idsec..., and returns its
+    /// public address. Importing a key already stored does nothing.
+    pub fn import(&mut self, secret: &str) -> Result<String> {
+        let kind = KeyKind::of_secret(secret).ok_or("Unknown secret address prefix")?;
+        let public = public_from_secret(secret)?;
+        if self.contains(&public) {
+            return Ok(public);
+        }
+        let sealed = self.with_key(|key| seal(key, secret.as_bytes(), public.as_bytes()))?;
+        let mut file = self.file.clone();
+        file.keys.push(StoredKey {
+            public: public.clone(),
+            kind,
+            sealed,
+        });
+        self.update(file)?;
+        Ok(public)
+    }
+
+    /// Generates and stores a new random key, returning its public address
+    pub fn generate(&mut self, kind: KeyKind) -> Result<String> {
+        let private_key = Zeroizing::new(random::<32>()?);
+        let secret = Zeroizing::new(address::encode_address(kind.secret_prefix(), &private_key));
+        self.import(&secret)
+    }
+
+    /// **Removes the key for good**, make sure it is backed up
+    pub fn remove(&mut self, public: &str) -> Result<()> {
+        let stored = self.stored(public)?;
+        // Proves the passphrase is known before deleting anything
+        self.with_key(|key| open(key, &stored.sealed, public.as_bytes()))?;
+        let mut file = self.file.clone();
+        file.keys.retain(|k| k.public != public);
+        self.update(file)
+    }
+
+    /// The secret address of a stored key
+    pub fn secret(&self, public: &str) -> Result<Secret> {
+        let stored = self.stored(public)?;
+        let secret = self.with_key(|key| open(key, &stored.sealed, public.as_bytes()))?;
+        Ok(String::from_utf8(secret.to_vec())?.into())
+    }
+
+    /// A signer for a stored key, usable after the keystore locks again
+    pub fn signer(&self, public: &str) -> Result<KeySigner> {
+        KeySigner::from_secret(self.secret(public)?.expose_secret())
+    }
+
+    /// Re-encrypts every key under a new passphrase and salt, then locks
+    pub fn change_passphrase(&mut self, old: &str, new: &str) -> Result<()> {
+        let salt = decode_hex(&self.file.kdf.salt)?;
+        let old_key = derive_key(old, &salt, self.file.kdf.params)?;
+        open(&old_key, &self.file.check, b"").map_err(|_| "Incorrect passphrase")?;
+        let salt = random::<16>()?;
+        let new_key = derive_key(new, &salt, self.file.kdf.params)?;
+        let mut keys = Vec::with_capacity(self.file.keys.len());
+        for stored in &self.file.keys {
+            let secret = open(&old_key, &stored.sealed, stored.public.as_bytes())?;
+            let sealed = seal(&new_key, &secret, stored.public.as_bytes())?;
+            keys.push(StoredKey {
+                sealed,
+                ..stored.clone()
+            });
+        }
+        let mut file = self.file.clone();
+        file.kdf.salt = encode_hex(&salt);
+        file.check = seal(&new_key, CHECK, b"")?;
+        file.keys = keys;
+        self.update(file)?;
+        self.lock();
+        Ok(())
+    }
+
+    fn stored(&self, public: &str) -> Result<StoredKey> {
+        let stored = self.file.keys.iter().find(|k| k.public == public);
+        Ok(stored.ok_or("Address not in keystore")?.clone())
+    }
+
+    /// Runs f with the derived key, failing if the keystore is locked
+    fn with_key<T>(&self, f: impl FnOnce(&[u8; 32]) -> Result<T>) -> Result<T> {
+        if self.is_locked() {
+            return Err("Keystore is locked".into());
+        }
+        let unlocked = self.unlocked.borrow();
+        let unlocked = unlocked.as_ref().ok_or("Keystore is locked")?;
+        f(&unlocked.key)
+    }
+
+    /// Saves the changed keystore, only replacing the one in memory once it
+    /// is written so a failed write leaves both unchanged
+    fn update(&mut self, file: KeystoreFile) -> Result<()> {
+        self.save(&file)?;
+        self.file = file;
+        Ok(())
+    }
+
+    fn save(&self, file: &KeystoreFile) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        // Written under a temporary name first so an interrupted write never
+        // leaves a truncated keystore behind
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let written = write_private(&tmp, serde_json::to_string_pretty(file)?.as_bytes())
+            .and_then(|_| fs::rename(&tmp, path));
+        if written.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        Ok(written?)
+    }
+}
+
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keystore")
+            .field("path", &self.path)
+            .field("addresses", &self.addresses())
+            .field("locked", &self.is_locked())
+            .finish()
+    }
+}
+
+/// Writes a file readable by its owner only
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // A leftover temporary file would keep its permissions
+    let _ = fs::remove_file(path);
+    let mut file = options.open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: KdfParams) -> Result<Zeroizing<[u8; 32]>> {
+    let params = scrypt::Params::new(params.log_n, params.r, params.p, 32)
+        .map_err(|e| format!("Invalid scrypt parameters: {}", e))?;
+    let mut key = Zeroizing::new([0; 32]);
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key[..])
+        .map_err(|e| format!("Deriving key: {}", e))?;
+    Ok(key)
+}
+
+fn seal(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<Sealed> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = random::<24>()?;
+    let payload = Payload {
+        msg: plaintext,
+        aad,
+    };
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), payload)
+        .map_err(|_| "Encryption failed")?;
+    Ok(Sealed {
+        nonce: encode_hex(&nonce),
+        ciphertext: encode_hex(&ciphertext),
+    })
+}
+
+fn open(key: &[u8; 32], sealed: &Sealed, aad: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = decode_hex(&sealed.nonce)?;
+    if nonce.len() != 24 {
+        return Err("Invalid nonce".into());
+    }
+    let payload = Payload {
+        msg: &decode_hex(&sealed.ciphertext)?,
+        aad,
+    };
+    let plaintext = cipher
+        .decrypt(XNonce::from_slice(&nonce), payload)
+        .map_err(|_| "Decryption failed, wrong passphrase or corrupted keystore")?;
+    Ok(Zeroizing::new(plaintext))
+}
+
+fn random<const N: usize>() -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("Generating random bytes: {}", e))?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::Signer;
+
+    /// Cheap parameters so the tests don't spend seconds in scrypt
+    const FAST: KdfParams = KdfParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+    const EC_SECRET: &str = "Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk";
+    const EC_PUBLIC: &str = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+
+    #[test]
+    fn lock_and_unlock() {
+        let mut keystore = Keystore::new("passphrase", FAST).unwrap();
+        assert!(keystore.is_locked());
+        assert!(keystore.import(EC_SECRET).is_err());
+        assert!(keystore.unlock("wrong", 60).is_err());
+        let unlocked = keystore.unlock("passphrase", 60).unwrap();
+        assert!(unlocked.success);
+        assert_eq!(keystore.import(EC_SECRET).unwrap(), EC_PUBLIC);
+        assert_eq!(
+            keystore.secret(EC_PUBLIC).unwrap().expose_secret(),
+            EC_SECRET
+        );
+        keystore.lock();
+        assert!(keystore.secret(EC_PUBLIC).is_err());
+        assert_eq!(keystore.addresses(), vec![EC_PUBLIC]);
+
+        // A zero timeout locks straight away
+        keystore.unlock("passphrase", 0).unwrap();
+        assert!(keystore.is_locked());
+    }
+
+    #[test]
+    fn persisted_and_signing() {
+        let path =
+            std::env::temp_dir().join(format!("factom-keystore-{}.json", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut keystore = Keystore::create(&path, "passphrase", FAST).unwrap();
+        assert!(Keystore::create(&path, "passphrase", FAST).is_err());
+        keystore.unlock("passphrase", 60).unwrap();
+        keystore.import(EC_SECRET).unwrap();
+        let fct = keystore.generate(KeyKind::Factoid).unwrap();
+        assert!(fct.starts_with("FA"));
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains(EC_SECRET));
+
+        let mut keystore = Keystore::open(&path).unwrap();
+        assert_eq!(
+            keystore.addresses(),
+            vec![EC_PUBLIC.to_string(), fct.clone()]
+        );
+        keystore.change_passphrase("passphrase", "new").unwrap();
+        assert!(keystore.unlock("passphrase", 60).is_err());
+        keystore.unlock("new", 60).unwrap();
+        let signer = keystore.signer(EC_PUBLIC).unwrap();
+        keystore.lock();
+        assert_eq!(signer.address(), EC_PUBLIC);
+        assert!(signer.sign_data(b"data").verify(b"data").unwrap());
+
+        keystore.unlock("new", 60).unwrap();
+        keystore.remove(&fct).unwrap();
+        assert_eq!(Keystore::open(&path).unwrap().addresses(), vec![EC_PUBLIC]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn failed_save_keeps_keystore() {
+        let dir = std::env::temp_dir().join(format!("factom-keystore-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keys.json");
+        let _ = fs::remove_file(&path);
+        let mut keystore = Keystore::create(&path, "passphrase", FAST).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        keystore.unlock("passphrase", 60).unwrap();
+        keystore.import(EC_SECRET).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(keystore.generate(KeyKind::Factoid).is_err());
+        assert!(keystore.remove(EC_PUBLIC).is_err());
+        assert!(keystore.change_passphrase("passphrase", "new").is_err());
+        assert_eq!(keystore.addresses(), vec![EC_PUBLIC]);
+        assert!(!keystore.is_locked());
+        assert_eq!(
+            keystore.secret(EC_PUBLIC).unwrap().expose_secret(),
+            EC_SECRET
+        );
+    }
+}
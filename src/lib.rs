@@ -14,23 +14,49 @@ pub mod address;
 pub mod api;
 pub mod balance;
 pub mod block;
+#[cfg(feature = "default")]
+pub mod blocking;
+pub mod cache;
+#[cfg(any(test, feature = "testing"))]
+pub mod cassette;
 pub mod chain;
 pub mod compose;
 pub mod constants;
 pub mod debug;
 pub mod entry;
 pub mod factomd;
+pub mod follow;
 pub mod generate;
+pub mod hash;
+#[cfg(feature = "hd")]
+pub mod hd;
 pub mod identity;
 pub mod import;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+#[cfg(feature = "index")]
+pub mod index;
+pub mod limit;
+pub mod methods;
 pub mod requests;
 pub mod responses;
+pub mod secret;
+pub mod signer;
+#[cfg(any(test, feature = "testing"))]
+pub mod simulator;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod transport;
+pub mod traverse;
 pub mod tx;
 pub mod utils;
+pub mod validate;
 pub mod walletd;
+pub mod watch;
 
 pub use api::Factom;
 pub use constants::*;
+pub use hash::{ChainId, EntryHash, IntoHash, KeyMr, TxId};
 #[cfg(feature = "default")]
 pub use requests::fetch;
 pub use requests::ApiRequest;
@@ -43,7 +69,9 @@ pub use tokio::runtime::Runtime;
 
 #[cfg(feature = "default")]
 use futures::prelude::*;
+#[cfg(feature = "hyper013")]
 use hyper::{client::HttpConnector, Client};
+#[cfg(feature = "hyper013")]
 use hyper_tls::HttpsConnector;
 use requests::{debug_call, factomd_call, parse, walletd_call};
 use serde::{Deserialize, Serialize};
@@ -51,6 +79,7 @@ use serde_json::json;
 use std::rc::Rc;
 
 /// Reference counted Hyper client with custom https connector
+#[cfg(feature = "hyper013")]
 pub type HttpsClient = Rc<Client<HttpsConnector<HttpConnector>, hyper::Body>>;
 
 /// Async return type for API Calls
@@ -0,0 +1,270 @@
+//! Client side rate limiting of requests.
+//!
+//! Each endpoint (factomd, walletd and the factomd debug path) can be given its
+//! own limit with
+//! [Factom::with_rate_limit](../api/struct.Factom.html#method.with_rate_limit).
+//! A limit combines a token bucket, refilled at `requests_per_second` and
+//! holding up to `burst` tokens, with an optional cap on the number of requests
+//! in flight. Requests wait in order until both allow them through, the time
+//! spent waiting is recorded in the endpoint's metrics. Responses served from
+//! the cache don't count against the limit.
+//!
+//! # Example
+//! ```
+//! use factom::*;
+//! use factom::limit::{Endpoint, RateLimit};
+//!
+//! let limit = RateLimit {
+//!   requests_per_second: 5.0,
+//!   burst: 10,
+//!   max_in_flight: Some(4),
+//! };
+//! let client = Factom::open_node().with_rate_limit(Endpoint::Factomd, limit);
+//! assert_eq!(client.rate_limit_metrics(Endpoint::Factomd).unwrap().requests, 0);
+//! ```
+use futures::channel::oneshot;
+use futures_timer::Delay;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// The endpoints requests are sent to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    Factomd,
+    Walletd,
+    Debug,
+}
+
+/// Limits applied to the requests sent to an endpoint
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RateLimit {
+    /// Rate at which the token bucket refills
+    pub requests_per_second: f64,
+    /// Size of the token bucket, the number of requests that can be sent at
+    /// once after a quiet period
+    pub burst: u32,
+    /// Maximum number of requests awaiting a response, None for no cap
+    pub max_in_flight: Option<usize>,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            requests_per_second: 10.0,
+            burst: 10,
+            max_in_flight: Some(8),
+        }
+    }
+}
+
+/// Request and queue wait counters of a limited endpoint
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub struct LimitMetrics {
+    /// Requests let through the limiter
+    pub requests: u64,
+    /// Requests that had to wait before being sent
+    pub queued: u64,
+    /// Requests waiting at the moment
+    pub waiting: usize,
+    /// Requests awaiting a response at the moment
+    pub in_flight: usize,
+    /// Total time spent waiting
+    pub total_wait: Duration,
+    /// Longest time a single request waited
+    pub max_wait: Duration,
+}
+
+/// Rate limiters of each endpoint, shared by a client and its clones
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Limiters {
+    pub factomd: Option<Rc<Limiter>>,
+    pub walletd: Option<Rc<Limiter>>,
+    pub debug: Option<Rc<Limiter>>,
+}
+
+impl Limiters {
+    pub fn get(&self, endpoint: Endpoint) -> Option<&Rc<Limiter>> {
+        match endpoint {
+            Endpoint::Factomd => self.factomd.as_ref(),
+            Endpoint::Walletd => self.walletd.as_ref(),
+            Endpoint::Debug => self.debug.as_ref(),
+        }
+    }
+
+    pub fn set(&mut self, endpoint: Endpoint, limit: RateLimit) {
+        let limiter = Some(Rc::new(Limiter::new(limit)));
+        match endpoint {
+            Endpoint::Factomd => self.factomd = limiter,
+            Endpoint::Walletd => self.walletd = limiter,
+            Endpoint::Debug => self.debug = limiter,
+        }
+    }
+}
+
+/// Token bucket with an in-flight cap
+#[derive(Debug)]
+pub(crate) struct Limiter {
+    limit: RateLimit,
+    tokens: Cell<f64>,
+    refilled: Cell<Instant>,
+    in_flight: Cell<usize>,
+    /// Requests waiting for an in-flight slot, woken in order
+    slots: RefCell<VecDeque<oneshot::Sender<()>>>,
+    metrics: Cell<LimitMetrics>,
+}
+
+/// Held while a request is in flight, frees its slot when dropped
+pub(crate) struct Permit(Rc<Limiter>);
+
+impl Limiter {
+    fn new(limit: RateLimit) -> Limiter {
+        let burst = f64::from(limit.burst.max(1));
+        Limiter {
+            limit,
+            tokens: Cell::new(burst),
+            refilled: Cell::new(Instant::now()),
+            in_flight: Cell::new(0),
+            slots: RefCell::new(VecDeque::new()),
+            metrics: Cell::new(LimitMetrics::default()),
+        }
+    }
+
+    pub fn metrics(&self) -> LimitMetrics {
+        let mut metrics = self.metrics.get();
+        metrics.in_flight = self.in_flight.get();
+        metrics
+    }
+
+    /// Waits until the request is allowed through
+    pub async fn acquire(self: &Rc<Self>) -> Permit {
+        let start = Instant::now();
+        let waiting = Waiting::new(self);
+        if let Some(max) = self.limit.max_in_flight {
+            while self.in_flight.get() >= max.max(1) {
+                let (tx, rx) = oneshot::channel();
+                self.slots.borrow_mut().push_back(tx);
+                let _ = rx.await;
+            }
+        }
+        self.in_flight.set(self.in_flight.get() + 1);
+        let permit = Permit(Rc::clone(self));
+        while let Some(wait) = self.take_token() {
+            Delay::new(wait).await;
+        }
+        drop(waiting);
+        let wait = start.elapsed();
+        self.update(|m| {
+            m.requests += 1;
+            if wait > Duration::from_millis(1) {
+                m.queued += 1;
+            }
+            m.total_wait += wait;
+            m.max_wait = m.max_wait.max(wait);
+        });
+        permit
+    }
+
+    /// Takes a token, or returns how long until one is available
+    fn take_token(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let rate = self.limit.requests_per_second.max(f64::MIN_POSITIVE);
+        let burst = f64::from(self.limit.burst.max(1));
+        let elapsed = now.duration_since(self.refilled.get()).as_secs_f64();
+        let tokens = (self.tokens.get() + elapsed * rate).min(burst);
+        self.refilled.set(now);
+        if tokens >= 1.0 {
+            self.tokens.set(tokens - 1.0);
+            None
+        } else {
+            self.tokens.set(tokens);
+            Some(Duration::from_secs_f64((1.0 - tokens) / rate))
+        }
+    }
+
+    fn release(&self) {
+        self.in_flight.set(self.in_flight.get() - 1);
+        let mut slots = self.slots.borrow_mut();
+        // Senders whose request was cancelled fail, wake the next one instead
+        while let Some(slot) = slots.pop_front() {
+            if slot.send(()).is_ok() {
+                break;
+            }
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut LimitMetrics)) {
+        let mut metrics = self.metrics.get();
+        f(&mut metrics);
+        self.metrics.set(metrics);
+    }
+}
+
+/// Counts a request as waiting until dropped, including when the request is
+/// cancelled while waiting
+struct Waiting<'a>(&'a Limiter);
+
+impl<'a> Waiting<'a> {
+    fn new(limiter: &'a Limiter) -> Self {
+        limiter.update(|m| m.waiting += 1);
+        Waiting(limiter)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.update(|m| m.waiting -= 1);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::future::join_all;
+
+    #[test]
+    fn token_bucket() {
+        let limiter = Rc::new(Limiter::new(RateLimit {
+            requests_per_second: 100.0,
+            burst: 2,
+            max_in_flight: None,
+        }));
+        let start = Instant::now();
+        block_on(async {
+            for _ in 0..4 {
+                limiter.acquire().await;
+            }
+        });
+        // Two requests are let through at once, the others wait 10ms each
+        assert!(start.elapsed() >= Duration::from_millis(15));
+        let metrics = limiter.metrics();
+        assert_eq!(metrics.requests, 4);
+        assert_eq!(metrics.queued, 2);
+    }
+
+    #[test]
+    fn in_flight_cap() {
+        let limiter = Rc::new(Limiter::new(RateLimit {
+            requests_per_second: 1000.0,
+            burst: 100,
+            max_in_flight: Some(2),
+        }));
+        let peak = Cell::new(0);
+        let requests = (0..6).map(|_| async {
+            let _permit = limiter.acquire().await;
+            peak.set(peak.get().max(limiter.in_flight.get()));
+            Delay::new(Duration::from_millis(2)).await;
+        });
+        block_on(join_all(requests));
+        assert_eq!(peak.get(), 2);
+        assert_eq!(limiter.metrics().in_flight, 0);
+    }
+}
@@ -0,0 +1,216 @@
+//! The json-rpc methods implemented by this crate.
+//!
+//! [METHODS](constant.METHODS.html) lists every factomd, factomd debug and
+//! factom-walletd method the api functions call along with the endpoint it is
+//! sent to. It can be used to check a node or proxy allows everything the
+//! client needs, and the crate's tests use it to make sure no method is missing
+//! a function or a mock response.
+//!
+//! # Example
+//! ```
+//! use factom::limit::Endpoint;
+//! use factom::methods::{self, METHODS};
+//!
+//! let debug = METHODS.iter().filter(|m| m.endpoint == Endpoint::Debug).count();
+//! assert!(debug > 0);
+//! assert_eq!(methods::endpoints("properties").count(), 2);
+//! ```
+use crate::limit::Endpoint;
+
+/// A json-rpc method and the endpoint serving it
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Method {
+    pub name: &'static str,
+    pub endpoint: Endpoint,
+}
+
+const fn factomd(name: &'static str) -> Method {
+    Method {
+        name,
+        endpoint: Endpoint::Factomd,
+    }
+}
+
+const fn debug(name: &'static str) -> Method {
+    Method {
+        name,
+        endpoint: Endpoint::Debug,
+    }
+}
+
+const fn walletd(name: &'static str) -> Method {
+    Method {
+        name,
+        endpoint: Endpoint::Walletd,
+    }
+}
+
+/// Every method implemented by the api functions, by endpoint then name
+pub const METHODS: &[Method] = &[
+    factomd("ablock-by-height"),
+    factomd("ack"),
+    factomd("admin-block"),
+    factomd("anchors"),
+    factomd("chain-head"),
+    factomd("commit-chain"),
+    factomd("commit-entry"),
+    factomd("current-minute"),
+    factomd("dblock-by-height"),
+    factomd("diagnostics"),
+    factomd("directory-block"),
+    factomd("directory-block-head"),
+    factomd("ecblock-by-height"),
+    factomd("entry"),
+    factomd("entry-ack"),
+    factomd("entry-block"),
+    factomd("entry-credit-balance"),
+    factomd("entry-credit-rate"),
+    factomd("entrycredit-block"),
+    factomd("factoid-ack"),
+    factomd("factoid-balance"),
+    factomd("factoid-block"),
+    factomd("factoid-submit"),
+    factomd("fblock-by-height"),
+    factomd("heights"),
+    factomd("multiple-ec-balances"),
+    factomd("multiple-fct-balances"),
+    factomd("pending-entries"),
+    factomd("pending-transactions"),
+    factomd("properties"),
+    factomd("raw-data"),
+    factomd("receipt"),
+    factomd("reveal-chain"),
+    factomd("reveal-entry"),
+    factomd("send-raw-message"),
+    factomd("transaction"),
+    debug("audit-servers"),
+    debug("authorities"),
+    debug("configuration"),
+    debug("delay"),
+    debug("drop-rate"),
+    debug("federated-servers"),
+    debug("holding-queue"),
+    debug("messages"),
+    debug("network-info"),
+    debug("predictive-fer"),
+    debug("process-list"),
+    debug("reload-configuration"),
+    debug("set-delay"),
+    debug("set-drop-rate"),
+    debug("summary"),
+    debug("wait-for-block"),
+    debug("wait-for-minute"),
+    walletd("active-identity-keys"),
+    walletd("add-ec-output"),
+    walletd("add-fee"),
+    walletd("add-input"),
+    walletd("add-output"),
+    walletd("address"),
+    walletd("all-addresses"),
+    walletd("all-identity-keys"),
+    walletd("compose-chain"),
+    walletd("compose-entry"),
+    walletd("compose-identity-attribute"),
+    walletd("compose-identity-attribute-endorsement"),
+    walletd("compose-identity-chain"),
+    walletd("compose-identity-key-replacement"),
+    walletd("compose-transaction"),
+    walletd("delete-transaction"),
+    walletd("generate-ec-address"),
+    walletd("generate-factoid-address"),
+    walletd("generate-identity-key"),
+    walletd("get-height"),
+    walletd("identity-key"),
+    walletd("identity-keys-at-height"),
+    walletd("import-addresses"),
+    walletd("import-identity-keys"),
+    walletd("import-koinify"),
+    walletd("new-transaction"),
+    walletd("properties"),
+    walletd("remove-address"),
+    walletd("remove-identity-key"),
+    walletd("sign-data"),
+    walletd("sign-transaction"),
+    walletd("sub-fee"),
+    walletd("tmp-transactions"),
+    walletd("transactions"),
+    walletd("unlock-wallet"),
+    walletd("wallet-backup"),
+    walletd("wallet-balances"),
+];
+
+/// The endpoints serving a method, empty if the crate doesn't implement it
+pub fn endpoints(name: &str) -> impl Iterator<Item = Endpoint> + '_ {
+    METHODS
+        .iter()
+        .filter(move |method| method.name == name)
+        .map(|method| method.endpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Sources of the modules making api calls
+    const SOURCES: &[&str] = &[
+        include_str!("address.rs"),
+        include_str!("balance.rs"),
+        include_str!("block.rs"),
+        include_str!("chain.rs"),
+        include_str!("compose.rs"),
+        include_str!("debug.rs"),
+        include_str!("entry.rs"),
+        include_str!("factomd.rs"),
+        include_str!("generate.rs"),
+        include_str!("identity.rs"),
+        include_str!("import.rs"),
+        include_str!("tx.rs"),
+        include_str!("walletd.rs"),
+    ];
+
+    /// Finds the method and endpoint of each ApiRequest built in the source
+    fn implemented(source: &str) -> Vec<(String, Endpoint)> {
+        let source = source.split("#[cfg(test)]").next().unwrap_or_default();
+        let mut found = Vec::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("ApiRequest::new(\"") {
+            rest = &rest[start + 17..];
+            let name = &rest[..rest.find('"').unwrap()];
+            let call = rest.find("_call(").expect("Request is sent");
+            let endpoint = match &rest[..call] {
+                s if s.ends_with("factomd") => Endpoint::Factomd,
+                s if s.ends_with("walletd") => Endpoint::Walletd,
+                s if s.ends_with("debug") => Endpoint::Debug,
+                _ => panic!("Unknown endpoint for {}", name),
+            };
+            found.push((name.to_string(), endpoint));
+        }
+        found
+    }
+
+    #[test]
+    fn table_matches_functions() {
+        let table: HashSet<_> = METHODS
+            .iter()
+            .map(|m| (m.name.to_string(), m.endpoint))
+            .collect();
+        assert_eq!(table.len(), METHODS.len(), "Duplicate methods");
+        let implemented: HashSet<_> = SOURCES
+            .iter()
+            .flat_map(|source| implemented(source))
+            .collect();
+        assert_eq!(implemented, table);
+    }
+
+    #[test]
+    fn mock_node_answers_every_method() {
+        for method in METHODS {
+            assert!(
+                crate::testing::empty_result(method.name).is_some(),
+                "No mock response for {}",
+                method.name
+            );
+        }
+    }
+}
@@ -1,10 +1,9 @@
 //!  Request handling functions intrinsic to the factom struct
 use super::*;
 use crate::responses::ApiResponse;
-use bytes::buf::BufExt as _;
 use constants::*;
-use http::{header::CONTENT_TYPE, request::Builder, Uri};
-use hyper::{body, client::ResponseFuture, Body, Request};
+use http::{header::CONTENT_TYPE, request::Builder, Request, Uri};
+use limit::Endpoint;
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
@@ -47,45 +46,100 @@ impl ApiRequest {
 }
 
 /// Makes a request to the current factomd node
-pub async fn factomd_call(api: &Factom, req: ApiRequest) -> ResponseFuture {
-    inner_call(api, &api.factomd_uri, req).await
+pub async fn factomd_call(api: &Factom, req: ApiRequest) -> Result<Vec<u8>> {
+    inner_call(api, Endpoint::Factomd, req).await
 }
 
 /// Makes a request to the current walletd node
-pub async fn walletd_call(api: &Factom, req: ApiRequest) -> ResponseFuture {
-    inner_call(api, &api.walletd_uri, req).await
+pub async fn walletd_call(api: &Factom, req: ApiRequest) -> Result<Vec<u8>> {
+    inner_call(api, Endpoint::Walletd, req).await
 }
 
 /// Makes a request to the current factomd node using the debug path
-pub async fn debug_call(api: &Factom, req: ApiRequest) -> ResponseFuture {
-    inner_call(api, &api.debug_uri, req).await
+pub async fn debug_call(api: &Factom, req: ApiRequest) -> Result<Vec<u8>> {
+    inner_call(api, Endpoint::Debug, req).await
 }
 
-async fn inner_call(api: &Factom, uri: &Rc<Uri>, req: ApiRequest) -> ResponseFuture {
-    let json = Body::from(req.json());
-    let builder = ApiRequest::builder(&uri);
-    let payload = builder.body(json).expect("Constructing request body");
-    api.client.request(payload)
+/// Sends the request through the client's transport and returns the response
+/// body, immutable responses are served from and stored in the client's cache
+/// if it has one. Requests wait for the endpoint's rate limit if one is set.
+async fn inner_call(api: &Factom, endpoint: Endpoint, req: ApiRequest) -> Result<Vec<u8>> {
+    let uri = match endpoint {
+        Endpoint::Factomd => &api.factomd_uri,
+        Endpoint::Walletd => &api.walletd_uri,
+        Endpoint::Debug => &api.debug_uri,
+    };
+    let cached = match &api.cache {
+        Some(cache) => cache::cache_key(uri, &req).map(|key| (cache, key)),
+        None => None,
+    };
+    if let Some((cache, key)) = &cached {
+        let body = cache.get(key);
+        api.record_cache_lookup(body.is_some());
+        if let Some(body) = body {
+            return Ok(cache::with_id(body, req.id.0));
+        }
+    }
+    let _permit = match api.limiters.get(endpoint) {
+        Some(limiter) => Some(limiter.acquire().await),
+        None => None,
+    };
+    let height = cache::requested_height(&req);
+    let body = api.transport.post(&uri.to_string(), req.json()).await?;
+    if let Some((cache, key)) = cached {
+        if cache::is_result(&body) && is_saved(api, height).await {
+            cache.insert(&key, &body);
+        }
+    }
+    Ok(body)
 }
 
-/// Parses the response and deserialises the API call into an appropriate
+/// Whether the block at a requested height can no longer change, blocks
+/// addressed by height are only final once the node has saved that height.
+/// The highest saved height seen is remembered so the node is only asked
+/// again for heights above it.
+async fn is_saved(api: &Factom, height: Option<i64>) -> bool {
+    let height = match height {
+        Some(height) => height,
+        None => return true,
+    };
+    if height <= api.saved_height.get() {
+        return true;
+    }
+    let heights = ApiRequest::new("heights").json();
+    let saved = match api
+        .transport
+        .post(&api.factomd_uri.to_string(), heights)
+        .await
+    {
+        Ok(body) => cache::saved_height(&body),
+        Err(_) => None,
+    };
+    match saved {
+        Some(saved) => {
+            api.saved_height.set(saved.max(api.saved_height.get()));
+            height <= saved
+        }
+        None => false,
+    }
+}
+
+/// Parses the response body and deserialises the API call into an appropriate
 /// ApiResponse struct
-pub async fn parse<T>(fut: ResponseFuture) -> Result<ApiResponse<T>>
+pub async fn parse<T>(response: Result<Vec<u8>>) -> Result<ApiResponse<T>>
 where
     T: DeserializeOwned + Default,
 {
-    let res = fut.await?;
-    let body = body::aggregate(res.into_body())
-        .await
-        .expect("Parsing response body");
-    let res: ApiResponse<T> = serde_json::from_reader(body.reader()).expect("Deserialising JSON");
+    let body = response?;
+    let res: ApiResponse<T> = serde_json::from_slice(&body)?;
     Ok(res)
 }
 
 /// Fetch is a convenience function that will run a future to it's completion,
 /// the function will create a new runtime for every call, if making multiple
-/// api calls for synchronous usage it's recommended to create
-/// a single runtime and re-use it's blocking method instead
+/// api calls for synchronous usage it's recommended to use the
+/// [blocking](../blocking/index.html) client instead, which re-uses a single
+/// runtime
 #[cfg(feature = "default")]
 pub fn fetch<F: Future>(query: F) -> F::Output {
     let mut rt = Runtime::new().expect("Initialising Runtime");
@@ -40,10 +40,12 @@ where
 
 impl Display for ApiError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{} (code {})", self.message, self.code)
     }
 }
 
+impl Error for ApiError {}
+
 impl<T> Error for ApiResponse<T>
 where
     T: Default + Debug + Display,
@@ -69,4 +71,15 @@ where
     pub fn success(&self) -> bool {
         self.error.code == 0i16
     }
+
+    /// Converts the response into a Result, returning the ApiError as the error
+    /// value if the api call was unsuccessful. This is useful for chaining calls
+    /// together with the ? operator.
+    pub fn into_result(self) -> Result<T> {
+        if self.is_err() {
+            Err(Box::new(self.error))
+        } else {
+            Ok(self.result)
+        }
+    }
 }
@@ -0,0 +1,112 @@
+//! Secret keys and seeds that stay out of logs.
+//!
+//! Responses carrying private keys or the wallet seed hold them as a
+//! [Secret](struct.Secret.html), which prints as `[REDACTED]` through both
+//! `Debug` and `Display` and is zeroed when dropped, so `dbg!(&response)` is
+//! safe to leave in place. The value is serialized unchanged and read with
+//! [expose_secret](struct.Secret.html#method.expose_secret).
+//!
+//! # Example
+//! ```
+//! use factom::secret::Secret;
+//!
+//! let secret = Secret::from("Fs3E9gV6DXsYzf7Fqx1fVBQPQXV695eP3k5XbmHEZVRLkMdD9qCK");
+//! assert_eq!(format!("{:?}", secret), "Secret([REDACTED])");
+//! assert!(secret.expose_secret().starts_with("Fs"));
+//! ```
+use super::*;
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A secret string, redacted when printed and zeroed when dropped
+#[derive(Default, Clone, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new<S: Into<String>>(secret: S) -> Secret {
+        Secret(secret.into())
+    }
+
+    /// The secret value itself
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for Secret {
+    fn from(secret: String) -> Secret {
+        Secret(secret)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(secret: &str) -> Secret {
+        Secret(secret.to_string())
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Secret, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted() {
+        let backup: walletd::WalletBackup = serde_json::from_value(json!({
+            "wallet-seed": "yellow yellow yellow",
+            "addresses": [{
+                "public": "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK",
+                "secret": "Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk"
+            }]
+        }))
+        .unwrap();
+        let printed = format!("{:?} {}", backup, backup.wallet_seed);
+        assert!(!printed.contains("yellow"));
+        assert!(!printed.contains("Es3LS7"));
+        assert!(printed.contains("EC2MJzC"));
+        assert_eq!(backup.wallet_seed.expose_secret(), "yellow yellow yellow");
+        let json = serde_json::to_value(&backup).unwrap();
+        assert_eq!(
+            json["addresses"][0]["secret"],
+            "Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+//! Offline signing with ed25519 keys.
+//!
+//! factom-walletd signs with the keys it holds through
+//! [walletd::sign_data](../walletd/fn.sign_data.html). Services holding their
+//! own keys, for example in a [keystore](../keystore/index.html), sign through
+//! the [Signer](trait.Signer.html) trait instead and produce the same
+//! [SignData](../walletd/struct.SignData.html) without a running wallet.
+//!
+//! # Example
+//! ```
+//! use factom::signer::{KeySigner, Signer};
+//!
+//! let signer = KeySigner::from_secret("Es3LS7zYa9DSzZuUC14HDpMinehmzz61JG1XFY62rX5pVDenH8Pk").unwrap();
+//! assert_eq!(signer.address(), "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK");
+//! let signed = signer.sign_data(b"attestation");
+//! assert!(signed.verify(b"attestation").unwrap());
+//! ```
+use super::*;
+use crate::address::{decode_address, public_address, KeyKind};
+use crate::walletd::SignData;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Signer as _, SigningKey};
+use std::fmt;
+
+/// Signs data with an ed25519 key
+pub trait Signer {
+    /// The ed25519 public key
+    fn public_key(&self) -> [u8; 32];
+
+    /// The ed25519 signature of the data
+    fn sign(&self, data: &[u8]) -> [u8; 64];
+
+    /// Signs the data, returning the base64 encoded public key and signature
+    /// in the same form as walletd's sign-data
+    fn sign_data(&self, data: &[u8]) -> SignData {
+        SignData {
+            pubkey: STANDARD.encode(self.public_key()),
+            signature: STANDARD.encode(self.sign(data)),
+        }
+    }
+}
+
+/// A factoid, entry credit or identity key held in memory. The key is zeroed
+/// when the signer is dropped and never printed.
+#[derive(Clone)]
+pub struct KeySigner {
+    kind: KeyKind,
+    key: SigningKey,
+}
+
+impl KeySigner {
+    /// The signer of a 32 byte ed25519 private key
+    pub fn new(kind: KeyKind, private_key: &[u8; 32]) -> KeySigner {
+        KeySigner {
+            kind,
+            key: SigningKey::from_bytes(private_key),
+        }
+    }
+
+    /// The signer of a secret address, Fs..., Es... or idsec...
+    pub fn from_secret(secret: &str) -> Result<KeySigner> {
+        let kind = KeyKind::of_secret(secret).ok_or("Unknown secret address prefix")?;
+        let private_key = decode_address(kind.secret_prefix(), secret)?;
+        Ok(KeySigner::new(kind, &private_key))
+    }
+
+    pub fn kind(&self) -> KeyKind {
+        self.kind
+    }
+
+    /// The public address of the key, FA..., EC... or idpub...
+    pub fn address(&self) -> String {
+        public_address(self.kind, &self.public_key())
+    }
+}
+
+impl Signer for KeySigner {
+    fn public_key(&self) -> [u8; 32] {
+        self.key.verifying_key().to_bytes()
+    }
+
+    fn sign(&self, data: &[u8]) -> [u8; 64] {
+        self.key.sign(data).to_bytes()
+    }
+}
+
+impl fmt::Debug for KeySigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeySigner")
+            .field("kind", &self.kind)
+            .field("address", &self.address())
+            .finish()
+    }
+}
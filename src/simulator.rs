@@ -0,0 +1,1134 @@
+//! A simulated Factom network holding an in-memory ledger, for deterministic
+//! end to end tests of entry, chain and factoid transaction workflows.
+//!
+//! The [Simulator](struct.Simulator.html) answers requests through a
+//! [MockNode](../testing/struct.MockNode.html), tracking factoid and entry
+//! credit balances, commits, reveals and transactions. Nothing is written to a
+//! block until the test advances the clock with
+//! [advance_minute](struct.Simulator.html#method.advance_minute) or
+//! [advance_block](struct.Simulator.html#method.advance_block), at which point
+//! revealed entries are grouped into entry blocks under a new directory block
+//! and acknowledgements move to DBlockConfirmed.
+//!
+//! The simulator answers:
+//! * `commit-chain`, `commit-entry`, `reveal-chain`, `reveal-entry`, `ack`,
+//!   `receipt`, `chain-head`, `entry-block`, `entry`, `raw-data` and
+//!   `pending-entries`
+//! * `factoid-submit`, `factoid-balance`, `entry-credit-balance`,
+//!   `multiple-fct-balances`, `multiple-ec-balances` and `entry-credit-rate`
+//! * `heights`, `current-minute`, `directory-block-head`, `directory-block`
+//!   and `dblock-by-height`
+//! * the walletd `compose-chain` and `compose-entry` methods
+//!
+//! Every other method falls back to the mock node, which can also be used to
+//! inject errors or latency on top of the simulation. Signatures are not
+//! checked and key merkle roots are hashes of the block contents rather than
+//! the merkle roots factomd computes.
+//!
+//! Enable with the `testing` feature.
+//!
+//! # Example
+//! ```
+//! use factom::*;
+//! use factom::simulator::Simulator;
+//! use futures::executor::block_on;
+//!
+//! let sim = Simulator::new();
+//! let ec_pub = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+//! sim.fund_ec(ec_pub, 100).unwrap();
+//! let client = sim.client();
+//!
+//! let chain = block_on(utils::create_chain(&client, vec!["Simulated"], "Hello", ec_pub)).unwrap();
+//! assert_eq!(sim.ec_balance(ec_pub), 89);
+//!
+//! sim.advance_block();
+//! let head = block_on(chain::chain_head(&client, chain.chainid)).unwrap().result;
+//! let eblock = block_on(block::entry_block(&client, head.chainhead)).unwrap().result;
+//! assert_eq!(eblock.entrylist[0].entryhash, chain.entryhash);
+//! ```
+use super::*;
+use crate::address::{decode_address, encode_address};
+use crate::block::{DBlockHeightHeader, Dbentry, DblockHeight, EBlock, EBlockHeader, Entrylist};
+use crate::testing::{MockNode, MockReply};
+use crate::tx::AckStatus;
+use serde_json::Value;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Time of the simulated genesis block, in seconds since the unix epoch
+const GENESIS_TIME: u64 = 1_600_000_000;
+/// Length of a simulated directory block in seconds
+const BLOCK_SECONDS: u64 = 600;
+/// Length of the header of a marshalled entry: version, chain id and ext id size
+const ENTRY_HEADER_LEN: usize = 35;
+/// Largest entry payload accepted
+const MAX_ENTRY_SIZE: usize = 10240;
+/// Credits paid on top of the first entry to create a chain
+const CHAIN_CREDITS: u8 = 10;
+/// Length of the signed part of entry and chain commit messages
+const ENTRY_COMMIT_LEN: usize = 40;
+const CHAIN_COMMIT_LEN: usize = 104;
+/// Length of the public key and signature closing a commit message
+const COMMIT_SIG_LEN: usize = 96;
+
+const INVALID_PARAMS: i16 = -32602;
+const INTERNAL_ERROR: i16 = -32603;
+const RECEIPT_ERROR: i16 = -32008;
+const MISSING_CHAIN_HEAD: i16 = -32009;
+const BLOCK_NOT_FOUND: i16 = -32008;
+
+type Handled = std::result::Result<Value, MockReply>;
+type Route = fn(&mut Ledger, &Value) -> Handled;
+
+/// A simulated factomd and walletd node, clones share the same ledger
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    node: MockNode,
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+#[derive(Debug, Default)]
+struct Ledger {
+    /// Height of the block being built
+    height: u32,
+    minute: u8,
+    /// Factoshis per entry credit
+    ec_rate: u64,
+    /// Makes the timestamps of composed commits and transactions unique
+    nonce: u64,
+    fct: HashMap<String, u64>,
+    ec: HashMap<String, u64>,
+    commits: HashMap<EntryHash, Commit>,
+    commit_txids: HashMap<TxId, EntryHash>,
+    entries: HashMap<EntryHash, Revealed>,
+    /// Entries revealed since the last block, in order
+    pending: Vec<EntryHash>,
+    /// Entry blocks of each chain, oldest first
+    chains: HashMap<ChainId, Vec<KeyMr>>,
+    eblocks: HashMap<KeyMr, EBlock>,
+    dblocks: Vec<DblockHeight>,
+    transactions: HashMap<TxId, Submitted>,
+}
+
+#[derive(Debug)]
+struct Commit {
+    txid: TxId,
+    credits: u8,
+    /// The chain id hash of a chain commit
+    chainidhash: Option<[u8; 32]>,
+    status: AckStatus,
+}
+
+#[derive(Debug)]
+struct Revealed {
+    chainid: ChainId,
+    raw: Vec<u8>,
+    extids: Vec<Vec<u8>>,
+    content: Vec<u8>,
+    /// Seconds since the unix epoch
+    timestamp: u64,
+    /// Entry block keymr and height once written to a block
+    block: Option<(KeyMr, u32)>,
+}
+
+#[derive(Debug)]
+struct Submitted {
+    millis: u64,
+    status: AckStatus,
+}
+
+/// A factoid transaction decoded from its binary form
+#[derive(Debug, Default)]
+struct FactoidTx {
+    txid: TxId,
+    millis: u64,
+    inputs: Vec<(String, u64)>,
+    outputs: Vec<(String, u64)>,
+    ec_outputs: Vec<(String, u64)>,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Simulator::new()
+    }
+}
+
+impl Simulator {
+    /// Creates a simulator with an empty genesis block saved at height 0 and
+    /// an entry credit rate of 1000 factoshis per credit
+    pub fn new() -> Simulator {
+        let ledger = Ledger {
+            ec_rate: 1000,
+            ..Ledger::default()
+        };
+        let simulator = Simulator {
+            node: MockNode::new(),
+            ledger: Arc::new(Mutex::new(ledger)),
+        };
+        simulator.ledger().advance_block();
+        let routes: &[(&str, Route)] = &[
+            ("ack", Ledger::ack),
+            ("chain-head", Ledger::chain_head),
+            ("commit-chain", |ledger, params| ledger.commit(params, true)),
+            ("commit-entry", |ledger, params| {
+                ledger.commit(params, false)
+            }),
+            ("compose-chain", Ledger::compose_chain),
+            ("compose-entry", Ledger::compose_entry),
+            ("current-minute", Ledger::current_minute),
+            ("dblock-by-height", Ledger::dblock_by_height),
+            ("directory-block", Ledger::directory_block),
+            ("directory-block-head", Ledger::directory_block_head),
+            ("entry", Ledger::entry),
+            ("entry-block", Ledger::entry_block),
+            ("entry-credit-balance", |ledger, params| {
+                ledger.balance(params, false)
+            }),
+            ("entry-credit-rate", |ledger, _| {
+                Ok(json!({"rate": ledger.ec_rate}))
+            }),
+            ("factoid-balance", |ledger, params| {
+                ledger.balance(params, true)
+            }),
+            ("factoid-submit", Ledger::factoid_submit),
+            ("heights", Ledger::heights),
+            ("multiple-ec-balances", |ledger, params| {
+                ledger.balances(params, false)
+            }),
+            ("multiple-fct-balances", |ledger, params| {
+                ledger.balances(params, true)
+            }),
+            ("pending-entries", Ledger::pending_entries),
+            ("raw-data", Ledger::raw_data),
+            ("receipt", Ledger::receipt),
+            ("reveal-chain", |ledger, params| ledger.reveal(params, true)),
+            ("reveal-entry", |ledger, params| {
+                ledger.reveal(params, false)
+            }),
+        ];
+        for (method, route) in routes {
+            let ledger = Arc::clone(&simulator.ledger);
+            let route = *route;
+            simulator.node.respond_with(method, move |params| {
+                match route(&mut lock(&ledger), params) {
+                    Ok(result) => MockReply::Result(result),
+                    Err(reply) => reply,
+                }
+            });
+        }
+        simulator
+    }
+
+    /// The mock node answering the requests, to record requests, serve the
+    /// simulation over http or override methods
+    pub fn node(&self) -> &MockNode {
+        &self.node
+    }
+
+    /// A client sending its requests to the simulator
+    pub fn client(&self) -> Factom {
+        self.node.client()
+    }
+
+    /// Adds factoshis to a public factoid address
+    pub fn fund_fct(&self, address: &str, factoshis: u64) -> Result<()> {
+        decode_address(&FCT_PUBLIC_PREFIX, address)?;
+        *self.ledger().fct.entry(address.to_string()).or_default() += factoshis;
+        Ok(())
+    }
+
+    /// Adds credits to a public entry credit address
+    pub fn fund_ec(&self, address: &str, credits: u64) -> Result<()> {
+        decode_address(&EC_PUBLIC_PREFIX, address)?;
+        *self.ledger().ec.entry(address.to_string()).or_default() += credits;
+        Ok(())
+    }
+
+    /// Balance of a public factoid address in factoshis
+    pub fn fct_balance(&self, address: &str) -> u64 {
+        self.ledger().fct.get(address).copied().unwrap_or_default()
+    }
+
+    /// Balance of a public entry credit address in credits
+    pub fn ec_balance(&self, address: &str) -> u64 {
+        self.ledger().ec.get(address).copied().unwrap_or_default()
+    }
+
+    /// Sets the number of factoshis an entry credit costs
+    pub fn set_ec_rate(&self, factoshis: u64) {
+        self.ledger().ec_rate = factoshis.max(1);
+    }
+
+    /// Height of the directory block being built, one above the last saved
+    /// height
+    pub fn height(&self) -> u32 {
+        self.ledger().height
+    }
+
+    /// Minute of the block being built, from 0 to 9
+    pub fn minute(&self) -> u8 {
+        self.ledger().minute
+    }
+
+    /// Moves to the next minute, saving the block after minute 9. Returns the
+    /// saved height if a block was saved.
+    pub fn advance_minute(&self) -> Option<u32> {
+        let mut ledger = self.ledger();
+        if ledger.minute < 9 {
+            ledger.minute += 1;
+            None
+        } else {
+            Some(ledger.advance_block())
+        }
+    }
+
+    /// Saves the block being built and returns its height. Revealed entries
+    /// are written to entry blocks, and commits, entries and transactions
+    /// accepted since the last block become DBlockConfirmed.
+    pub fn advance_block(&self) -> u32 {
+        self.ledger().advance_block()
+    }
+
+    /// Builds a hex encoded factoid transaction that can be sent with
+    /// factoid-submit, amounts are in factoshis. The signatures are left
+    /// blank, the simulator accepts them but a real node would not.
+    ///
+    /// # Example
+    /// ```
+    /// use factom::*;
+    /// use factom::simulator::Simulator;
+    /// use futures::executor::block_on;
+    ///
+    /// let sim = Simulator::new();
+    /// let from = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+    /// let to = "FA3EPZYqodgyEGXNMbiZKE5TS2x2J9wF8J9MvPZb52iGR78xMgCb";
+    /// sim.fund_fct(from, 300_000).unwrap();
+    ///
+    /// let tx = sim.transaction(&[(from, 200_000)], &[(to, 150_000)], &[]).unwrap();
+    /// let submitted = block_on(tx::factoid_submit(&sim.client(), &tx)).unwrap();
+    /// assert!(submitted.success());
+    /// assert_eq!(sim.fct_balance(to), 150_000);
+    /// ```
+    pub fn transaction(
+        &self,
+        inputs: &[(&str, u64)],
+        outputs: &[(&str, u64)],
+        ec_outputs: &[(&str, u64)],
+    ) -> Result<String> {
+        let millis = self.ledger().timestamp_millis();
+        let mut tx = vec![0x02];
+        tx.extend_from_slice(&millis.to_be_bytes()[2..]);
+        tx.extend_from_slice(&[
+            inputs.len() as u8,
+            outputs.len() as u8,
+            ec_outputs.len() as u8,
+        ]);
+        let groups = [
+            (inputs, &FCT_PUBLIC_PREFIX),
+            (outputs, &FCT_PUBLIC_PREFIX),
+            (ec_outputs, &EC_PUBLIC_PREFIX),
+        ];
+        for (group, prefix) in groups.iter() {
+            for (address, amount) in group.iter() {
+                write_varint(&mut tx, *amount);
+                tx.extend_from_slice(&decode_address(&prefix[..], address)?);
+            }
+        }
+        for _ in inputs {
+            tx.push(0x01);
+            tx.extend_from_slice(&[0u8; 96]);
+        }
+        Ok(hash::encode_hex(&tx))
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        lock(&self.ledger)
+    }
+}
+
+fn lock(ledger: &Mutex<Ledger>) -> MutexGuard<'_, Ledger> {
+    ledger.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Ledger {
+    fn block_start(&self) -> u64 {
+        GENESIS_TIME + u64::from(self.height) * BLOCK_SECONDS
+    }
+
+    /// Simulated time in seconds
+    fn now(&self) -> u64 {
+        self.block_start() + u64::from(self.minute) * 60
+    }
+
+    /// Simulated time in milliseconds, unique on each call
+    fn timestamp_millis(&mut self) -> u64 {
+        self.nonce += 1;
+        self.now() * 1000 + self.nonce
+    }
+
+    fn advance_block(&mut self) -> u32 {
+        let height = self.height;
+        let timestamp = self.block_start() as usize;
+        let mut chains: Vec<(ChainId, Vec<Entrylist>)> = Vec::new();
+        for entryhash in self.pending.drain(..) {
+            let entry = &self.entries[&entryhash];
+            let item = Entrylist {
+                entryhash,
+                timestamp: entry.timestamp as usize,
+            };
+            match chains
+                .iter_mut()
+                .find(|(chainid, _)| *chainid == entry.chainid)
+            {
+                Some((_, entrylist)) => entrylist.push(item),
+                None => chains.push((entry.chainid, vec![item])),
+            }
+        }
+        let mut dbentries = Vec::new();
+        for (chainid, entrylist) in chains {
+            let blocks = self.chains.entry(chainid).or_default();
+            let eblock = EBlock {
+                header: EBlockHeader {
+                    blocksequencenumber: blocks.len(),
+                    chainid,
+                    prevkeymr: blocks.last().copied().unwrap_or_default(),
+                    timestamp,
+                    dbheight: height as usize,
+                },
+                entrylist,
+            };
+            let keymr = KeyMr(digest(&serde_json::to_vec(&eblock).unwrap_or_default()));
+            blocks.push(keymr);
+            for item in &eblock.entrylist {
+                if let Some(entry) = self.entries.get_mut(&item.entryhash) {
+                    entry.block = Some((keymr, height));
+                }
+            }
+            self.eblocks.insert(keymr, eblock);
+            dbentries.push(Dbentry { chainid, keymr });
+        }
+        let confirmed = self
+            .commits
+            .values_mut()
+            .map(|commit| &mut commit.status)
+            .chain(self.transactions.values_mut().map(|tx| &mut tx.status));
+        for status in confirmed {
+            *status = AckStatus::DBlockConfirmed;
+        }
+        let prevkeymr = self.dblocks.last().map(|d| d.keymr).unwrap_or_default();
+        let mut header = prevkeymr.as_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        for dbentry in &dbentries {
+            header.extend_from_slice(dbentry.chainid.as_bytes());
+            header.extend_from_slice(dbentry.keymr.as_bytes());
+        }
+        let keymr = KeyMr(digest(&header));
+        self.dblocks.push(DblockHeight {
+            header: DBlockHeightHeader {
+                prevkeymr,
+                timestamp: timestamp / 60,
+                dbheight: height as usize,
+                blockcount: dbentries.len(),
+                ..DBlockHeightHeader::default()
+            },
+            dbentries,
+            dbhash: hash::Hash(digest(keymr.as_bytes())),
+            keymr,
+        });
+        self.height += 1;
+        self.minute = 0;
+        height
+    }
+
+    fn heights(&mut self, _: &Value) -> Handled {
+        let saved = self.height - 1;
+        Ok(json!({
+            "directoryblockheight": saved,
+            "leaderheight": self.height,
+            "entryblockheight": saved,
+            "entryheight": saved,
+        }))
+    }
+
+    fn current_minute(&mut self, _: &Value) -> Handled {
+        let nanos = |seconds: u64| seconds * 1_000_000_000;
+        Ok(json!({
+            "leaderheight": self.height,
+            "directoryblockheight": self.height - 1,
+            "minute": self.minute,
+            "currentblockstarttime": nanos(self.block_start()),
+            "currentminutestarttime": nanos(self.now()),
+            "currenttime": nanos(self.now()),
+            "directoryblockinseconds": BLOCK_SECONDS,
+            "stalldetected": false,
+            "faulttimeout": 120,
+            "roundtimeout": 30,
+        }))
+    }
+
+    fn balance(&mut self, params: &Value, factoid: bool) -> Handled {
+        let address = param(params, "address")?;
+        let (prefix, balances) = self.balance_book(factoid);
+        decode_address(prefix, address).map_err(|e| invalid(&e.to_string()))?;
+        let balance = balances.get(address).copied().unwrap_or_default();
+        Ok(json!({ "balance": balance }))
+    }
+
+    fn balances(&mut self, params: &Value, factoid: bool) -> Handled {
+        let addresses = params["addresses"]
+            .as_array()
+            .ok_or_else(|| invalid("addresses"))?;
+        let (prefix, book) = self.balance_book(factoid);
+        let balances: Vec<Value> = addresses
+            .iter()
+            .map(|address| {
+                let address = address.as_str().unwrap_or_default();
+                match decode_address(prefix, address) {
+                    Ok(_) => {
+                        let balance = book.get(address).copied().unwrap_or_default();
+                        json!({"ack": balance, "saved": balance, "err": ""})
+                    }
+                    Err(_) => json!({"ack": 0, "saved": 0, "err": "Error decoding address"}),
+                }
+            })
+            .collect();
+        Ok(json!({
+            "currentheight": self.height,
+            "lastsavedheight": self.height - 1,
+            "balances": balances,
+        }))
+    }
+
+    fn balance_book(&self, factoid: bool) -> (&'static [u8], &HashMap<String, u64>) {
+        if factoid {
+            (&FCT_PUBLIC_PREFIX, &self.fct)
+        } else {
+            (&EC_PUBLIC_PREFIX, &self.ec)
+        }
+    }
+
+    fn compose_chain(&mut self, params: &Value) -> Handled {
+        let first = &params["chain"]["firstentry"];
+        let extids = hex_list(&first["extids"])?;
+        let content = hex_param(first, "content")?;
+        let chainid = chain_id(&extids);
+        self.compose(params, chainid, extids, content, true)
+    }
+
+    fn compose_entry(&mut self, params: &Value) -> Handled {
+        let entry = &params["entry"];
+        let chainid = param(entry, "chainid")?
+            .parse()
+            .map_err(|_| invalid("chainid"))?;
+        let extids = hex_list(&entry["extids"])?;
+        let content = hex_param(entry, "content")?;
+        self.compose(params, chainid, extids, content, false)
+    }
+
+    /// Builds the commit and reveal messages the wallet would compose, with a
+    /// blank signature
+    fn compose(
+        &mut self,
+        params: &Value,
+        chainid: ChainId,
+        extids: Vec<Vec<u8>>,
+        content: Vec<u8>,
+        chain: bool,
+    ) -> Handled {
+        let ecpub = param(params, "ecpub")?;
+        let ecpub =
+            decode_address(&EC_PUBLIC_PREFIX, ecpub).map_err(|e| invalid(&e.to_string()))?;
+        let raw = marshal_entry(&chainid, &extids, &content);
+        let credits = entry_cost(&raw)?;
+        let entryhash = entry_hash(&raw);
+        let mut commit = vec![0x00];
+        commit.extend_from_slice(&self.timestamp_millis().to_be_bytes()[2..]);
+        if chain {
+            let weld = [&entryhash.as_bytes()[..], chainid.as_bytes()].concat();
+            commit.extend_from_slice(&digest(&digest(chainid.as_bytes())));
+            commit.extend_from_slice(&digest(&digest(&weld)));
+        }
+        commit.extend_from_slice(entryhash.as_bytes());
+        commit.push(if chain {
+            credits + CHAIN_CREDITS
+        } else {
+            credits
+        });
+        commit.extend_from_slice(&ecpub);
+        commit.extend_from_slice(&[0u8; 64]);
+        let kind = if chain { "chain" } else { "entry" };
+        Ok(json!({
+            "commit": {
+                "jsonrpc": JSONRPC,
+                "id": ID,
+                "params": {"message": hash::encode_hex(&commit)},
+                "method": format!("commit-{}", kind),
+            },
+            "reveal": {
+                "jsonrpc": JSONRPC,
+                "id": ID,
+                "params": {"entry": hash::encode_hex(&raw)},
+                "method": format!("reveal-{}", kind),
+            },
+        }))
+    }
+
+    fn commit(&mut self, params: &Value, chain: bool) -> Handled {
+        let message = hex_param(params, "message")?;
+        let ledger_len = if chain {
+            CHAIN_COMMIT_LEN
+        } else {
+            ENTRY_COMMIT_LEN
+        };
+        if message.len() != ledger_len + COMMIT_SIG_LEN {
+            return Err(invalid("message"));
+        }
+        let ledger = &message[..ledger_len];
+        let txid = TxId(digest(ledger));
+        let entryhash = EntryHash(slice32(&ledger[ledger_len - 33..ledger_len - 1]));
+        let credits = ledger[ledger_len - 1];
+        let ecpub = encode_address(&EC_PUBLIC_PREFIX, &slice32(&message[ledger_len..]));
+        if self.commits.contains_key(&entryhash) {
+            return Err(error(REPEATED_COMMIT, "Repeated Commit"));
+        }
+        let balance = self.ec.entry(ecpub).or_default();
+        if *balance < u64::from(credits) {
+            return Err(error(INTERNAL_ERROR, "Insufficient entry credit balance"));
+        }
+        *balance -= u64::from(credits);
+        let commit = Commit {
+            txid,
+            credits,
+            chainidhash: if chain {
+                Some(slice32(&ledger[7..39]))
+            } else {
+                None
+            },
+            status: AckStatus::TransactionAck,
+        };
+        self.commits.insert(entryhash, commit);
+        self.commit_txids.insert(txid, entryhash);
+        let mut result = json!({"txid": txid, "entryhash": entryhash});
+        if chain {
+            result["message"] = json!("Chain Commit Success");
+            result["chainidhash"] = json!(hash::encode_hex(&ledger[7..39]));
+        } else {
+            result["message"] = json!("Entry Commit Success");
+        }
+        Ok(result)
+    }
+
+    fn reveal(&mut self, params: &Value, chain: bool) -> Handled {
+        let raw = hex_param(params, "entry")?;
+        let (chainid, extids, content) = parse_entry(&raw).ok_or_else(|| invalid("entry"))?;
+        let entryhash = entry_hash(&raw);
+        if self.entries.contains_key(&entryhash) {
+            return Err(error(INTERNAL_ERROR, "Entry already revealed"));
+        }
+        let commit = self
+            .commits
+            .get(&entryhash)
+            .ok_or_else(|| error(INTERNAL_ERROR, "Entry has not been committed"))?;
+        let cost = entry_cost(&raw)? + if chain { CHAIN_CREDITS } else { 0 };
+        if commit.credits < cost {
+            return Err(error(
+                INTERNAL_ERROR,
+                "Commit paid for fewer credits than needed",
+            ));
+        }
+        if chain {
+            let chainidhash = digest(&digest(chainid.as_bytes()));
+            if chainid != chain_id(&extids) || commit.chainidhash != Some(chainidhash) {
+                return Err(error(INTERNAL_ERROR, "Chain id does not match the commit"));
+            }
+            if self.chain_exists(chainid) {
+                return Err(error(INTERNAL_ERROR, "Chain already exists"));
+            }
+        } else if !self.chain_exists(chainid) {
+            return Err(error(MISSING_CHAIN_HEAD, "Missing Chain Head"));
+        }
+        let revealed = Revealed {
+            chainid,
+            raw,
+            extids,
+            content,
+            timestamp: self.now(),
+            block: None,
+        };
+        self.entries.insert(entryhash, revealed);
+        self.pending.push(entryhash);
+        Ok(json!({
+            "message": "Entry Reveal Success",
+            "entryhash": entryhash,
+            "chainid": chainid,
+        }))
+    }
+
+    /// Whether the chain has an entry block or a revealed first entry
+    fn chain_exists(&self, chainid: ChainId) -> bool {
+        self.chains.contains_key(&chainid)
+            || self
+                .pending
+                .iter()
+                .any(|hash| self.entries[hash].chainid == chainid)
+    }
+
+    fn ack(&mut self, params: &Value) -> Handled {
+        let hash = param(params, "hash")?;
+        let chainid = param(params, "chainid")?;
+        if chainid == "f" {
+            let txid: TxId = hash.parse().map_err(|_| invalid("hash"))?;
+            let (millis, status) = match self.transactions.get(&txid) {
+                Some(tx) => (tx.millis, tx.status),
+                None => (0, AckStatus::Unknown),
+            };
+            return Ok(json!({
+                "txid": txid,
+                "transactiondate": millis,
+                "status": status,
+            }));
+        }
+        let parsed: [u8; 32] = hash.parse::<EntryHash>().map_err(|_| invalid("hash"))?.0;
+        let entryhash = if chainid == "c" {
+            self.commit_txids.get(&TxId(parsed)).copied()
+        } else {
+            Some(EntryHash(parsed))
+        };
+        let commit = entryhash.and_then(|hash| self.commits.get(&hash));
+        let entry = entryhash.and_then(|hash| self.entries.get(&hash));
+        let entry_status = match entry {
+            Some(Revealed { block: Some(_), .. }) => AckStatus::DBlockConfirmed,
+            Some(_) => AckStatus::TransactionAck,
+            None => AckStatus::Unknown,
+        };
+        Ok(json!({
+            "committxid": commit.map(|c| c.txid).unwrap_or_default(),
+            "entryhash": entryhash.unwrap_or_default(),
+            "commitdata": {"status": commit.map_or(AckStatus::Unknown, |c| c.status)},
+            "entrydata": {"status": entry_status},
+        }))
+    }
+
+    fn chain_head(&mut self, params: &Value) -> Handled {
+        let chainid: ChainId = param(params, "chainid")?
+            .parse()
+            .map_err(|_| invalid("chainid"))?;
+        if !self.chain_exists(chainid) {
+            return Err(error(MISSING_CHAIN_HEAD, "Missing Chain Head"));
+        }
+        let head = self.chains.get(&chainid).and_then(|blocks| blocks.last());
+        let in_process = self
+            .pending
+            .iter()
+            .any(|hash| self.entries[hash].chainid == chainid);
+        Ok(json!({
+            "chainhead": head.copied().unwrap_or_default(),
+            "chaininprocesslist": in_process,
+        }))
+    }
+
+    fn entry_block(&mut self, params: &Value) -> Handled {
+        let keymr: KeyMr = param(params, "keymr")?
+            .parse()
+            .map_err(|_| invalid("keymr"))?;
+        let eblock = self
+            .eblocks
+            .get(&keymr)
+            .ok_or_else(|| error(BLOCK_NOT_FOUND, "Block not found"))?;
+        Ok(json!(eblock))
+    }
+
+    fn revealed(&self, params: &Value) -> std::result::Result<(EntryHash, &Revealed), MockReply> {
+        let hash: EntryHash = param(params, "hash")?
+            .parse()
+            .map_err(|_| invalid("hash"))?;
+        match self.entries.get(&hash) {
+            Some(entry) => Ok((hash, entry)),
+            None => Err(error(INTERNAL_ERROR, "Entry not found")),
+        }
+    }
+
+    fn entry(&mut self, params: &Value) -> Handled {
+        let (_, entry) = self.revealed(params)?;
+        let extids: Vec<String> = entry.extids.iter().map(|id| hash::encode_hex(id)).collect();
+        Ok(json!({
+            "chainid": entry.chainid,
+            "content": hash::encode_hex(&entry.content),
+            "extids": extids,
+        }))
+    }
+
+    fn raw_data(&mut self, params: &Value) -> Handled {
+        let (_, entry) = self.revealed(params)?;
+        Ok(json!({ "data": hash::encode_hex(&entry.raw) }))
+    }
+
+    fn pending_entries(&mut self, _: &Value) -> Handled {
+        let pending: Vec<Value> = self
+            .pending
+            .iter()
+            .map(|hash| {
+                let chainid = self.entries[hash].chainid;
+                json!({"entryhash": hash, "chainid": chainid, "status": "TransactionACK"})
+            })
+            .collect();
+        Ok(json!(pending))
+    }
+
+    fn receipt(&mut self, params: &Value) -> Handled {
+        let (entryhash, entry) = self.revealed(params)?;
+        let (keymr, height) = entry
+            .block
+            .ok_or_else(|| error(RECEIPT_ERROR, "Receipt creation error"))?;
+        let raw = if params["includerawentry"] == json!(true) {
+            hash::encode_hex(&entry.raw)
+        } else {
+            String::new()
+        };
+        Ok(json!({
+            "receipt": {
+                "entry": {"entryhash": entryhash, "raw": raw, "timestamp": entry.timestamp},
+                "merklebranch": [],
+                "entryblockkeymr": keymr,
+                "directoryblockkeymr": self.dblocks[height as usize].keymr,
+                "directoryblockheight": height,
+            }
+        }))
+    }
+
+    fn directory_block_head(&mut self, _: &Value) -> Handled {
+        let head = self.dblocks.last().map(|d| d.keymr).unwrap_or_default();
+        Ok(json!({ "keymr": head }))
+    }
+
+    fn directory_block(&mut self, params: &Value) -> Handled {
+        let keymr: KeyMr = param(params, "keymr")?
+            .parse()
+            .map_err(|_| invalid("keymr"))?;
+        let dblock = self
+            .dblocks
+            .iter()
+            .find(|d| d.keymr == keymr)
+            .ok_or_else(|| error(BLOCK_NOT_FOUND, "Block not found"))?;
+        Ok(json!({
+            "header": {
+                "prevblockkeymr": dblock.header.prevkeymr,
+                "sequencenumber": dblock.header.dbheight,
+                "timestamp": dblock.header.timestamp * 60,
+            },
+            "entryblocklist": dblock.dbentries,
+        }))
+    }
+
+    fn dblock_by_height(&mut self, params: &Value) -> Handled {
+        let height = params["height"].as_u64().ok_or_else(|| invalid("height"))?;
+        let dblock = self
+            .dblocks
+            .get(height as usize)
+            .ok_or_else(|| error(BLOCK_NOT_FOUND, "Block not found"))?;
+        Ok(json!({ "dblock": dblock, "rawdata": "" }))
+    }
+
+    fn factoid_submit(&mut self, params: &Value) -> Handled {
+        let raw = hex_param(params, "transaction")?;
+        let tx = parse_transaction(&raw).ok_or_else(|| invalid("transaction"))?;
+        if self.transactions.contains_key(&tx.txid) {
+            return Err(error(INTERNAL_ERROR, "Transaction already submitted"));
+        }
+        let total = |amounts: &[(String, u64)]| amounts.iter().map(|(_, a)| a).sum::<u64>();
+        let spent = total(&tx.outputs) + total(&tx.ec_outputs);
+        if total(&tx.inputs) < spent {
+            return Err(error(INTERNAL_ERROR, "Inputs are less than outputs"));
+        }
+        let mut debits: HashMap<&str, u64> = HashMap::new();
+        for (address, amount) in &tx.inputs {
+            *debits.entry(address).or_default() += amount;
+        }
+        for (address, amount) in &debits {
+            if self.fct.get(*address).copied().unwrap_or_default() < *amount {
+                return Err(error(INTERNAL_ERROR, "Insufficient factoid balance"));
+            }
+        }
+        for (address, amount) in debits {
+            *self.fct.entry(address.to_string()).or_default() -= amount;
+        }
+        for (address, amount) in &tx.outputs {
+            *self.fct.entry(address.clone()).or_default() += amount;
+        }
+        for (address, amount) in &tx.ec_outputs {
+            *self.ec.entry(address.clone()).or_default() += amount / self.ec_rate;
+        }
+        let submitted = Submitted {
+            millis: tx.millis,
+            status: AckStatus::TransactionAck,
+        };
+        self.transactions.insert(tx.txid, submitted);
+        Ok(json!({
+            "message": "Successfully submitted the transaction",
+            "txid": tx.txid,
+        }))
+    }
+}
+
+fn error(code: i16, message: &str) -> MockReply {
+    MockReply::Error {
+        code,
+        message: message.to_string(),
+    }
+}
+
+fn invalid(param: &str) -> MockReply {
+    error(INVALID_PARAMS, &format!("Invalid params: {}", param))
+}
+
+fn param<'a>(params: &'a Value, name: &str) -> std::result::Result<&'a str, MockReply> {
+    params[name].as_str().ok_or_else(|| invalid(name))
+}
+
+fn hex_param(params: &Value, name: &str) -> std::result::Result<Vec<u8>, MockReply> {
+    hash::decode_hex(param(params, name)?).map_err(|_| invalid(name))
+}
+
+fn hex_list(list: &Value) -> std::result::Result<Vec<Vec<u8>>, MockReply> {
+    let list = list.as_array().map(Vec::as_slice).unwrap_or_default();
+    list.iter()
+        .map(|hex| {
+            hash::decode_hex(hex.as_str().unwrap_or_default()).map_err(|_| invalid("extids"))
+        })
+        .collect()
+}
+
+fn digest(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+fn slice32(bytes: &[u8]) -> [u8; 32] {
+    bytes[..32].try_into().expect("32 bytes")
+}
+
+/// The chain id of a chain whose first entry has the ext ids
+fn chain_id(extids: &[Vec<u8>]) -> ChainId {
+    let mut hasher = Sha256::new();
+    for extid in extids {
+        hasher.update(Sha256::digest(extid));
+    }
+    ChainId(hasher.finalize().into())
+}
+
+fn entry_hash(raw: &[u8]) -> EntryHash {
+    let sha512 = Sha512::digest(raw);
+    EntryHash(digest(&[&sha512[..], raw].concat()))
+}
+
+/// Credits needed to write the entry, one per started kilobyte of payload
+fn entry_cost(raw: &[u8]) -> std::result::Result<u8, MockReply> {
+    let size = raw.len() - ENTRY_HEADER_LEN;
+    if size > MAX_ENTRY_SIZE {
+        return Err(error(INTERNAL_ERROR, "Entry is larger than 10KiB"));
+    }
+    Ok(size.div_ceil(1024).max(1) as u8)
+}
+
+fn marshal_entry(chainid: &ChainId, extids: &[Vec<u8>], content: &[u8]) -> Vec<u8> {
+    let mut ids = Vec::new();
+    for extid in extids {
+        ids.extend_from_slice(&(extid.len() as u16).to_be_bytes());
+        ids.extend_from_slice(extid);
+    }
+    let mut raw = vec![0x00];
+    raw.extend_from_slice(chainid.as_bytes());
+    raw.extend_from_slice(&(ids.len() as u16).to_be_bytes());
+    raw.extend_from_slice(&ids);
+    raw.extend_from_slice(content);
+    raw
+}
+
+/// Splits a marshalled entry into its chain id, ext ids and content
+fn parse_entry(raw: &[u8]) -> Option<(ChainId, Vec<Vec<u8>>, Vec<u8>)> {
+    if raw.len() < ENTRY_HEADER_LEN || raw[0] != 0 {
+        return None;
+    }
+    let chainid = ChainId(slice32(&raw[1..33]));
+    let size = usize::from(u16::from_be_bytes([raw[33], raw[34]]));
+    let mut ids = raw.get(ENTRY_HEADER_LEN..ENTRY_HEADER_LEN + size)?;
+    let mut extids = Vec::new();
+    while !ids.is_empty() {
+        let len = usize::from(u16::from_be_bytes([*ids.first()?, *ids.get(1)?]));
+        extids.push(ids.get(2..2 + len)?.to_vec());
+        ids = &ids[2 + len..];
+    }
+    Some((chainid, extids, raw[ENTRY_HEADER_LEN + size..].to_vec()))
+}
+
+/// Decodes the addresses and amounts of a factoid transaction, ignoring its
+/// RCDs and signatures
+fn parse_transaction(raw: &[u8]) -> Option<FactoidTx> {
+    let mut reader = Reader { bytes: raw, pos: 0 };
+    reader.varint()?;
+    let millis = reader
+        .take(6)?
+        .iter()
+        .fold(0, |n, b| n << 8 | u64::from(*b));
+    let counts = reader.take(3)?.to_vec();
+    let mut read = |count: u8, prefix: &[u8]| -> Option<Vec<(String, u64)>> {
+        (0..count)
+            .map(|_| {
+                let amount = reader.varint()?;
+                let key = slice32(reader.take(32)?);
+                Some((encode_address(prefix, &key), amount))
+            })
+            .collect()
+    };
+    let inputs = read(counts[0], &FCT_PUBLIC_PREFIX)?;
+    let outputs = read(counts[1], &FCT_PUBLIC_PREFIX)?;
+    let ec_outputs = read(counts[2], &EC_PUBLIC_PREFIX)?;
+    let txid = TxId(digest(&raw[..reader.pos]));
+    Some(FactoidTx {
+        txid,
+        millis,
+        inputs,
+        outputs,
+        ec_outputs,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    /// Factom varints hold 7 bits per byte, most significant first, with the
+    /// high bit set on every byte but the last
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        loop {
+            let byte = self.take(1)?[0];
+            value = value.checked_mul(128)? | u64::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, value: u64) {
+    let mut groups = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(groups.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    const EC: &str = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+    const FA: &str = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+
+    #[test]
+    fn chain_and_entry_lifecycle() {
+        let sim = Simulator::new();
+        sim.fund_ec(EC, 30).unwrap();
+        let client = sim.client();
+        let chain = block_on(utils::create_chain(
+            &client,
+            vec!["sim", "chain"],
+            "first",
+            EC,
+        ))
+        .unwrap();
+        assert_eq!(chain.status, AckStatus::TransactionAck);
+        let entry = block_on(utils::create_entry(
+            &client,
+            chain.chainid,
+            vec!["e"],
+            "second",
+            EC,
+        ))
+        .unwrap();
+        assert_eq!(sim.ec_balance(EC), 18);
+        let head = block_on(chain::chain_head(&client, chain.chainid)).unwrap();
+        assert!(head.result.chaininprocesslist);
+        assert!(block_on(factomd::receipt(&client, entry.entryhash, false))
+            .unwrap()
+            .is_err());
+
+        assert_eq!(sim.advance_block(), 1);
+        let ack = block_on(tx::ec_ack(&client, entry.entryhash, chain.chainid, None)).unwrap();
+        assert_eq!(ack.result.commitdata.status, AckStatus::DBlockConfirmed);
+        assert_eq!(ack.result.entrydata.status, AckStatus::DBlockConfirmed);
+        let entries = block_on(utils::traverse_chain(&client, chain.chainid, None)).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].content, hash::encode_hex(b"second"));
+        let receipt = block_on(factomd::receipt(&client, entry.entryhash, false)).unwrap();
+        assert_eq!(receipt.result.receipt.directoryblockheight, 1);
+
+        let repeat = block_on(utils::create_chain(
+            &client,
+            vec!["sim", "chain"],
+            "first",
+            EC,
+        ));
+        assert!(repeat.is_err());
+        let unfunded = encode_address(&EC_PUBLIC_PREFIX, &[7; 32]);
+        let entry = utils::create_entry(&client, chain.chainid, vec!["e"], "third", &unfunded);
+        assert!(block_on(entry).is_err());
+    }
+
+    #[test]
+    fn factoid_transactions() {
+        let sim = Simulator::new();
+        sim.set_ec_rate(500);
+        sim.fund_fct(FA, 1_000_000).unwrap();
+        let client = sim.client();
+        let to = "FA3EPZYqodgyEGXNMbiZKE5TS2x2J9wF8J9MvPZb52iGR78xMgCb";
+        let tx = sim
+            .transaction(&[(FA, 700_000)], &[(to, 600_000)], &[(EC, 50_000)])
+            .unwrap();
+        let submitted = block_on(tx::factoid_submit(&client, &tx)).unwrap().result;
+        assert_eq!(sim.fct_balance(FA), 300_000);
+        assert_eq!(sim.fct_balance(to), 600_000);
+        assert_eq!(sim.ec_balance(EC), 100);
+        let ack = block_on(tx::fct_ack(&client, submitted.txid, None)).unwrap();
+        assert_eq!(ack.result.status, AckStatus::TransactionAck);
+        sim.advance_block();
+        let ack = block_on(tx::fct_ack(&client, submitted.txid, None)).unwrap();
+        assert_eq!(ack.result.status, AckStatus::DBlockConfirmed);
+
+        let overdraw = sim
+            .transaction(&[(FA, 400_000)], &[(to, 400_000)], &[])
+            .unwrap();
+        assert!(block_on(tx::factoid_submit(&client, &overdraw))
+            .unwrap()
+            .is_err());
+        let balances = block_on(balance::multiple_fct_balances(&client, vec![FA, "FA0"])).unwrap();
+        assert!(balances.success());
+    }
+
+    #[test]
+    fn varints() {
+        for value in &[0, 1, 127, 128, 300, 100_000_000, u64::from(u32::MAX)] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, *value);
+            let mut reader = Reader {
+                bytes: &bytes,
+                pos: 0,
+            };
+            assert_eq!(reader.varint(), Some(*value));
+            assert_eq!(reader.pos, bytes.len());
+        }
+    }
+}
@@ -0,0 +1,623 @@
+//! An in-process stand-in for factomd and factom-walletd, for testing code
+//! built on this crate without a network.
+//!
+//! A [MockNode](struct.MockNode.html) answers every json-rpc method the crate
+//! calls. Methods answer with an empty result of the right shape unless
+//! programmed with a canned result, an api error, a failure or a handler
+//! computing the reply from the request's params. Results given as objects
+//! are laid over the empty result, so only the fields a test cares about need
+//! to be set. Every request is recorded and latency can be added to all or
+//! some methods.
+//!
+//! The node can be used directly as the client's transport, needing no
+//! runtime, or served over http on a local port with
+//! [serve](struct.MockNode.html#method.serve) when the `default` feature is
+//! enabled.
+//!
+//! Enable with the `testing` feature.
+//!
+//! # Example
+//! ```
+//! use factom::*;
+//! use factom::testing::{MockNode, MockReply};
+//! use futures::executor::block_on;
+//! use serde_json::json;
+//!
+//! let node = MockNode::new();
+//! node.respond("entry-credit-rate", json!({"rate": 1000}))
+//!     .respond_with("factoid-balance", |params| {
+//!         let funded = params["address"] == "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+//!         MockReply::Result(json!({"balance": if funded { 5000 } else { 0 }}))
+//!     })
+//!     .respond_error("chain-head", -32009, "Missing Chain Head");
+//! let client = node.client();
+//!
+//! let rate = block_on(factomd::entry_credit_rate(&client)).unwrap();
+//! assert_eq!(rate.result.rate, 1000);
+//! let chainid = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+//! let head = block_on(chain::chain_head(&client, chainid)).unwrap();
+//! assert!(head.is_err());
+//! assert_eq!(node.requests_for("chain-head")[0].params["chainid"], chainid);
+//! ```
+use super::*;
+use crate::transport::Transport;
+use futures::future::LocalBoxFuture;
+use futures_timer::Delay;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// How the mock node replies to a request
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockReply {
+    /// A successful response holding the result
+    Result(Value),
+    /// A json-rpc error response
+    Error { code: i16, message: String },
+    /// The request fails without a json-rpc response, as if the connection
+    /// failed. Served over http as a 500 status with the message as body.
+    Fail(String),
+}
+
+/// A request received by the mock node
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockRequest {
+    /// Path of the url the request was sent to, `/v2` or `/debug`
+    pub path: String,
+    pub method: String,
+    pub params: Value,
+}
+
+type Handler = Arc<dyn Fn(&Value) -> MockReply + Send + Sync>;
+
+enum Responder {
+    Reply(MockReply),
+    Handler(Handler),
+}
+
+#[derive(Default)]
+struct State {
+    /// Replies used once, in order, before the method's responder
+    once: HashMap<String, VecDeque<MockReply>>,
+    responders: HashMap<String, Responder>,
+    latency: Duration,
+    method_latency: HashMap<String, Duration>,
+    requests: Vec<MockRequest>,
+}
+
+/// A programmable factomd and walletd node. Clones share their responses and
+/// recorded requests, so a clone can be handed to a client while the test
+/// keeps programming and inspecting the original.
+#[derive(Clone, Default)]
+pub struct MockNode {
+    state: Arc<Mutex<State>>,
+}
+
+impl fmt::Debug for MockNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("MockNode")
+            .field("programmed", &state.responders.keys().collect::<Vec<_>>())
+            .field("requests", &state.requests.len())
+            .finish()
+    }
+}
+
+impl MockNode {
+    pub fn new() -> MockNode {
+        MockNode::default()
+    }
+
+    /// A client sending its requests straight to this node
+    pub fn client(&self) -> Factom {
+        Factom::new().with_transport(self.clone())
+    }
+
+    /// Answers every call to the method with the result
+    pub fn respond(&self, method: &str, result: Value) -> &Self {
+        self.set(method, Responder::Reply(MockReply::Result(result)))
+    }
+
+    /// Answers every call to the method with an api error
+    pub fn respond_error(&self, method: &str, code: i16, message: &str) -> &Self {
+        let error = MockReply::Error {
+            code,
+            message: message.to_string(),
+        };
+        self.set(method, Responder::Reply(error))
+    }
+
+    /// Fails every call to the method without a response
+    pub fn fail(&self, method: &str, message: &str) -> &Self {
+        self.set(
+            method,
+            Responder::Reply(MockReply::Fail(message.to_string())),
+        )
+    }
+
+    /// Answers every call to the method with the reply computed from the
+    /// request's params
+    pub fn respond_with<F>(&self, method: &str, handler: F) -> &Self
+    where
+        F: Fn(&Value) -> MockReply + Send + Sync + 'static,
+    {
+        self.set(method, Responder::Handler(Arc::new(handler)))
+    }
+
+    /// Answers the next call to the method with the reply, replies queued
+    /// this way are used in order before the method's other responses
+    pub fn respond_once(&self, method: &str, reply: MockReply) -> &Self {
+        self.state()
+            .once
+            .entry(method.to_string())
+            .or_default()
+            .push_back(reply);
+        self
+    }
+
+    /// Delays every reply
+    pub fn set_latency(&self, latency: Duration) -> &Self {
+        self.state().latency = latency;
+        self
+    }
+
+    /// Delays the replies to a method, replacing the latency of every reply
+    pub fn set_method_latency(&self, method: &str, latency: Duration) -> &Self {
+        self.state()
+            .method_latency
+            .insert(method.to_string(), latency);
+        self
+    }
+
+    /// Forgets the programmed responses and latencies, methods go back to
+    /// answering with empty results. Recorded requests are kept.
+    pub fn reset(&self) {
+        let mut state = self.state();
+        state.once.clear();
+        state.responders.clear();
+        state.latency = Duration::default();
+        state.method_latency.clear();
+    }
+
+    /// The requests received so far, oldest first
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.state().requests.clone()
+    }
+
+    /// The requests received so far for a method, oldest first
+    pub fn requests_for(&self, method: &str) -> Vec<MockRequest> {
+        self.state()
+            .requests
+            .iter()
+            .filter(|req| req.method == method)
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state().requests.clear();
+    }
+
+    fn set(&self, method: &str, responder: Responder) -> &Self {
+        self.state()
+            .responders
+            .insert(method.to_string(), responder);
+        self
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // A handler panicking on another thread leaves the state usable
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Records the request and returns the delay before replying along with
+    /// the response body, or the failure message
+    fn reply(&self, path: &str, body: &[u8]) -> (Duration, std::result::Result<Vec<u8>, String>) {
+        let request: Value = match serde_json::from_slice(body) {
+            Ok(request) => request,
+            Err(e) => return (Duration::default(), Err(format!("Invalid request: {}", e))),
+        };
+        let method = request["method"].as_str().unwrap_or_default().to_string();
+        let params = request["params"].clone();
+        let mut state = self.state();
+        state.requests.push(MockRequest {
+            path: path.to_string(),
+            method: method.clone(),
+            params: params.clone(),
+        });
+        let latency = match state.method_latency.get(&method) {
+            Some(latency) => *latency,
+            None => state.latency,
+        };
+        let once = state.once.get_mut(&method).and_then(VecDeque::pop_front);
+        let reply = match (once, state.responders.get(&method)) {
+            (Some(reply), _) => reply,
+            (None, Some(Responder::Reply(reply))) => reply.clone(),
+            (None, Some(Responder::Handler(handler))) => {
+                let handler = Arc::clone(handler);
+                // Released so handlers can use the node themselves
+                drop(state);
+                handler(&params)
+            }
+            (None, None) => match empty_result(&method) {
+                Some(result) => MockReply::Result(result),
+                None => MockReply::Error {
+                    code: -32601,
+                    message: "Method not found".to_string(),
+                },
+            },
+        };
+        let id = request["id"].clone();
+        let response = match reply {
+            MockReply::Result(result) => {
+                let result = match empty_result(&method) {
+                    Some(empty) if result.is_object() => merge(empty, result),
+                    _ => result,
+                };
+                json!({"jsonrpc": JSONRPC, "id": id, "result": result})
+            }
+            MockReply::Error { code, message } => {
+                let error = json!({"code": code, "message": message});
+                json!({"jsonrpc": JSONRPC, "id": id, "error": error})
+            }
+            MockReply::Fail(message) => return (latency, Err(message)),
+        };
+        (
+            latency,
+            serde_json::to_vec(&response).map_err(|e| e.to_string()),
+        )
+    }
+}
+
+impl Transport for MockNode {
+    fn post(&self, url: &str, body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+        let path = match url.parse::<http::Uri>() {
+            Ok(uri) => uri.path().to_string(),
+            Err(_) => url.to_string(),
+        };
+        let (latency, reply) = self.reply(&path, body.as_bytes());
+        Box::pin(async move {
+            if latency > Duration::default() {
+                Delay::new(latency).await;
+            }
+            Ok(reply?)
+        })
+    }
+}
+
+/// A mock node served over http, shut down when dropped
+#[cfg(feature = "default")]
+#[derive(Debug)]
+pub struct MockServer {
+    url: String,
+    shutdown: Option<futures::channel::oneshot::Sender<()>>,
+}
+
+#[cfg(feature = "default")]
+impl MockNode {
+    /// Serves the node over http on a random local port, both the factomd
+    /// and walletd paths are answered on the same port. Must be called within
+    /// a tokio runtime, which the server is spawned on.
+    ///
+    /// # Example
+    /// ```
+    /// use factom::*;
+    /// use factom::testing::MockNode;
+    /// use serde_json::json;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///   let node = MockNode::new();
+    ///   node.respond("heights", json!({"leaderheight": 10}));
+    ///   let server = node.serve().await.expect("Binding mock node");
+    ///   let client = server.client();
+    ///   let heights = factomd::heights(&client).await.unwrap();
+    ///   assert_eq!(heights.result.leaderheight, 10);
+    /// }
+    /// ```
+    pub async fn serve(&self) -> Result<MockServer> {
+        use hyper::service::{make_service_fn, service_fn};
+        let node = self.clone();
+        let make_service = make_service_fn(move |_| {
+            let node = node.clone();
+            async move {
+                let service = service_fn(move |req| node.clone().serve_request(req));
+                Ok::<_, hyper::Error>(service)
+            }
+        });
+        let server = hyper::Server::try_bind(&([127, 0, 0, 1], 0).into())?.serve(make_service);
+        let url = format!("http://{}", server.local_addr());
+        let (shutdown, stopped) = futures::channel::oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            stopped.await.ok();
+        }));
+        Ok(MockServer {
+            url,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    async fn serve_request(
+        self,
+        req: hyper::Request<hyper::Body>,
+    ) -> std::result::Result<hyper::Response<hyper::Body>, hyper::Error> {
+        let path = req.uri().path().to_string();
+        let body = hyper::body::to_bytes(req.into_body()).await?;
+        let (latency, reply) = self.reply(&path, &body);
+        if latency > Duration::default() {
+            Delay::new(latency).await;
+        }
+        let res = match reply {
+            Ok(body) => hyper::Response::new(body.into()),
+            Err(message) => {
+                let mut res = hyper::Response::new(message.into());
+                *res.status_mut() = http::StatusCode::INTERNAL_SERVER_ERROR;
+                res
+            }
+        };
+        Ok(res)
+    }
+}
+
+#[cfg(feature = "default")]
+impl MockServer {
+    /// Base url of the server, to be used as both the factomd and walletd host
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A client sending its factomd and walletd requests to the server
+    pub fn client(&self) -> Factom {
+        Factom::custom_node(&self.url, &self.url)
+    }
+}
+
+#[cfg(feature = "default")]
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// The result of a method when nothing is programmed, the serialized default
+/// of the type the crate parses it into. Methods shared by factomd and walletd
+/// or parsed into different types merge the fields of each.
+pub(crate) fn empty_result(method: &str) -> Option<Value> {
+    let result = match method {
+        "ablock-by-height" => empty::<block::ABlockHeightResult>(),
+        "ack" => merge(empty::<tx::EntryAck>(), empty::<tx::FactoidAck>()),
+        "active-identity-keys" => empty::<identity::ActiveIdKeys>(),
+        "add-ec-output" => empty::<tx::WalletTransaction>(),
+        "add-fee" | "add-input" | "add-output" | "sign-transaction" | "sub-fee" => {
+            empty::<tx::WalletTransaction>()
+        }
+        "address" => empty::<address::Address>(),
+        "admin-block" => empty::<block::ABlockResult>(),
+        "all-addresses" => empty::<address::AllAddresses>(),
+        "all-identity-keys" => empty::<identity::IdKeys>(),
+        "anchors" => empty::<block::Anchor>(),
+        "audit-servers" => empty::<debug::AuditServers>(),
+        "authorities" => empty::<debug::Authorities>(),
+        "chain-head" => empty::<chain::ChainHead>(),
+        "commit-chain" => empty::<chain::CommitChain>(),
+        "commit-entry" => empty::<entry::CommitEntry>(),
+        "compose-chain"
+        | "compose-entry"
+        | "compose-identity-attribute"
+        | "compose-identity-attribute-endorsement"
+        | "compose-identity-chain"
+        | "compose-identity-key-replacement" => empty::<compose::Compose>(),
+        "compose-transaction" => empty::<compose::ComposeTx>(),
+        "configuration" | "reload-configuration" => empty::<debug::Configuration>(),
+        "current-minute" => empty::<factomd::CurrentMinute>(),
+        "dblock-by-height" => empty::<block::DBlockHeightResult>(),
+        "delay" | "set-delay" => empty::<debug::Delay>(),
+        "delete-transaction" => empty::<tx::WalletTransaction>(),
+        "diagnostics" => empty::<factomd::Diagnostics>(),
+        "directory-block" => empty::<block::DBlock>(),
+        "directory-block-head" => empty::<block::DBlockHead>(),
+        "drop-rate" | "set-drop-rate" => empty::<debug::DropRate>(),
+        "ecblock-by-height" => empty::<block::EBlockHeightResult>(),
+        "entry" => empty::<entry::Entry>(),
+        "entry-ack" => empty::<tx::EntryAck>(),
+        "entry-block" => empty::<block::EBlock>(),
+        "entry-credit-balance" | "factoid-balance" => empty::<balance::Balance>(),
+        "entry-credit-rate" => empty::<factomd::EcRate>(),
+        "entrycredit-block" => empty::<block::EcBlockResult>(),
+        "factoid-ack" => empty::<tx::FactoidAck>(),
+        "factoid-block" | "fblock-by-height" => empty::<block::FBlockResult>(),
+        "factoid-submit" => empty::<tx::FctSubmit>(),
+        "federated-servers" => empty::<debug::FederatedServers>(),
+        "generate-ec-address" | "generate-factoid-address" | "generate-identity-key" => {
+            empty::<generate::Generate>()
+        }
+        "get-height" => empty::<walletd::Height>(),
+        "heights" => empty::<factomd::Heights>(),
+        "holding-queue" => empty::<debug::HoldingQueue>(),
+        "identity-key" => empty::<identity::Key>(),
+        "identity-keys-at-height" => empty::<identity::ActiveIdKeys>(),
+        "import-addresses" => empty::<import::Addresses>(),
+        "import-identity-keys" => empty::<import::Keys>(),
+        "import-koinify" => empty::<import::Address>(),
+        "messages" => empty::<debug::Messages>(),
+        "multiple-ec-balances" | "multiple-fct-balances" => empty::<balance::MultipleBalances>(),
+        "network-info" => empty::<debug::NetworkInfo>(),
+        "new-transaction" => empty::<tx::WalletTransaction>(),
+        "pending-entries" => json!([]),
+        "pending-transactions" => json!([]),
+        "predictive-fer" => empty::<debug::PredictiveFER>(),
+        "process-list" => empty::<debug::ProcessList>(),
+        "properties" => merge(
+            empty::<factomd::Properties>(),
+            empty::<walletd::Properties>(),
+        ),
+        "raw-data" => empty::<entry::RawData>(),
+        "receipt" | "send-raw-message" => empty::<factomd::Receipt>(),
+        "remove-address" => empty::<address::RemoveAddress>(),
+        "remove-identity-key" => empty::<identity::RemoveIdKey>(),
+        "reveal-chain" => empty::<chain::RevealChain>(),
+        "reveal-entry" => empty::<entry::RevealEntry>(),
+        "sign-data" => empty::<walletd::SignData>(),
+        "summary" => empty::<debug::Summary>(),
+        "tmp-transactions" => empty::<tx::TmpTransactions>(),
+        "transaction" => empty::<tx::Transaction>(),
+        "transactions" => empty::<tx::Transactions>(),
+        "unlock-wallet" => empty::<walletd::UnlockWallet>(),
+        "wait-for-block" | "wait-for-minute" => empty::<debug::WaitFor>(),
+        "wallet-backup" => empty::<walletd::WalletBackup>(),
+        "wallet-balances" => empty::<walletd::WalletBalances>(),
+        _ => return None,
+    };
+    Some(result)
+}
+
+fn empty<T: Default + Serialize>() -> Value {
+    serde_json::to_value(T::default()).unwrap_or_default()
+}
+
+/// Adds the fields of the second object to the first
+fn merge(mut first: Value, second: Value) -> Value {
+    if let (Some(first), Value::Object(second)) = (first.as_object_mut(), second) {
+        first.extend(second);
+    }
+    first
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::time::Instant;
+
+    #[test]
+    fn empty_results_parse() {
+        let node = MockNode::new();
+        let client = node.client();
+        block_on(async {
+            let hash = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+            assert!(address::all_addresses(&client).await.unwrap().success());
+            assert!(balance::multiple_fct_balances(&client, vec![hash])
+                .await
+                .unwrap()
+                .success());
+            assert!(block::ablock_by_height(&client, 1).await.unwrap().success());
+            assert!(block::admin_block(&client, hash).await.unwrap().success());
+            assert!(block::anchors(&client, block::AnchorType::Height(1))
+                .await
+                .unwrap()
+                .success());
+            assert!(block::dblock_by_height(&client, 1).await.unwrap().success());
+            assert!(block::directory_block(&client, hash)
+                .await
+                .unwrap()
+                .success());
+            assert!(block::ecblock_by_height(&client, 1)
+                .await
+                .unwrap()
+                .success());
+            assert!(block::entry_block(&client, hash).await.unwrap().success());
+            assert!(block::entry_credit_block(&client, hash)
+                .await
+                .unwrap()
+                .success());
+            assert!(block::fblock_by_height(&client, 1).await.unwrap().success());
+            assert!(chain::chain_head(&client, hash).await.unwrap().success());
+            assert!(debug::configuration(&client).await.unwrap().success());
+            assert!(debug::process_list(&client).await.unwrap().success());
+            assert!(entry::pending_entries(&client).await.unwrap().success());
+            assert!(factomd::diagnostics(&client).await.unwrap().success());
+            assert!(factomd::properties(&client).await.unwrap().success());
+            assert!(factomd::receipt(&client, hash, false)
+                .await
+                .unwrap()
+                .success());
+            assert!(identity::active_id_keys(&client, hash, None)
+                .await
+                .unwrap()
+                .success());
+            assert!(tx::ec_ack(&client, hash, hash, None)
+                .await
+                .unwrap()
+                .success());
+            assert!(tx::fct_ack(&client, hash, None).await.unwrap().success());
+            assert!(tx::pending_transactions(&client, None)
+                .await
+                .unwrap()
+                .success());
+            assert!(tx::tmp_transactions(&client).await.unwrap().success());
+            assert!(walletd::wallet_backup(&client).await.unwrap().success());
+            assert!(walletd::wallet_balances(&client).await.unwrap().success());
+            assert!(walletd::wallet_properties(&client).await.unwrap().success());
+        });
+        assert_eq!(node.requests().len(), 26);
+        assert_eq!(node.requests()[0].path, "/v2");
+    }
+
+    #[test]
+    fn programmed_replies() {
+        let node = MockNode::new();
+        node.respond_once("heights", MockReply::Result(json!({"leaderheight": 5})))
+            .respond("heights", json!({"leaderheight": 6}))
+            .fail("properties", "Connection reset")
+            .set_method_latency("heights", Duration::from_millis(20));
+        let client = node.client();
+        let start = Instant::now();
+        let first = block_on(factomd::heights(&client)).unwrap();
+        let second = block_on(factomd::heights(&client)).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(
+            (first.result.leaderheight, second.result.leaderheight),
+            (5, 6)
+        );
+        assert!(block_on(factomd::properties(&client)).is_err());
+        let unknown = block_on(requests::factomd_call(
+            &client,
+            ApiRequest::new("no-such-method"),
+        ));
+        let unknown = block_on(parse::<Value>(unknown)).unwrap();
+        assert_eq!(unknown.error.code, -32601);
+        node.reset();
+        assert_eq!(
+            block_on(factomd::heights(&client))
+                .unwrap()
+                .result
+                .leaderheight,
+            0
+        );
+    }
+
+    #[cfg(feature = "default")]
+    #[test]
+    fn served_over_http() {
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .build()
+            .unwrap();
+        let node = MockNode::new();
+        node.respond_error("entry", -32008, "Receipt creation error");
+        runtime.block_on(async {
+            let server = node.serve().await.unwrap();
+            let client = server.client();
+            let response = entry::entry(
+                &client,
+                "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef",
+            )
+            .await
+            .unwrap();
+            assert_eq!(response.error.code, -32008);
+            node.fail("entry", "Unavailable");
+            assert!(entry::entry(
+                &client,
+                "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef"
+            )
+            .await
+            .is_err());
+        });
+        assert_eq!(node.requests_for("entry").len(), 2);
+    }
+}
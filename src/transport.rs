@@ -0,0 +1,218 @@
+//! Transports sending the json-rpc requests to the nodes.
+//!
+//! Every request goes through the client's [Transport](trait.Transport.html),
+//! so the http stack and async runtime are chosen with cargo features:
+//!
+//! * `hyper013` (default): hyper 0.13 on the tokio 0.2 runtime re-exported by
+//!   this crate
+//! * `hyper1`: hyper 1 with the legacy client from hyper-util, requires a
+//!   tokio 1 runtime
+//! * `reqwest`: reqwest 0.12, requires a tokio 1 runtime
+//!
+//! Other runtimes such as async-std can be supported by implementing the
+//! trait. For tests, the [MockNode](../testing/struct.MockNode.html) of the
+//! `testing` feature answers requests without any runtime or network.
+//!
+//! # Example
+//! ```
+//! use factom::*;
+//! use factom::transport::Transport;
+//! use futures::future::LocalBoxFuture;
+//!
+//! /// Answers every request with the same response body
+//! #[derive(Debug)]
+//! struct Canned(&'static str);
+//!
+//! impl Transport for Canned {
+//!     fn post(&self, _url: &str, _body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+//!         Box::pin(futures::future::ready(Ok(self.0.as_bytes().to_vec())))
+//!     }
+//! }
+//!
+//! let client = Factom::new().with_transport(Canned(
+//!     r#"{"jsonrpc": "2.0", "id": 0, "result": {"directoryblockheight": 10,
+//!     "leaderheight": 11, "entryblockheight": 10, "entryheight": 10}}"#,
+//! ));
+//! let heights = futures::executor::block_on(factomd::heights(&client)).unwrap();
+//! assert_eq!(heights.result.leaderheight, 11);
+//! ```
+use super::*;
+use futures::future::LocalBoxFuture;
+use std::fmt;
+
+/// Sends a request body to a node and returns the response body
+pub trait Transport: fmt::Debug {
+    /// Posts the json body to the url
+    fn post(&self, url: &str, body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>>;
+}
+
+/// Returns the transport enabled by the cargo features, preferring hyper 0.13,
+/// then hyper 1, then reqwest
+pub fn default_transport() -> Rc<dyn Transport> {
+    #[cfg(feature = "hyper013")]
+    return Rc::new(HyperTransport::new());
+    #[cfg(all(feature = "hyper1", not(feature = "hyper013")))]
+    return Rc::new(Hyper1Transport::new());
+    #[cfg(all(
+        feature = "reqwest",
+        not(any(feature = "hyper013", feature = "hyper1"))
+    ))]
+    return Rc::new(ReqwestTransport::new());
+    #[cfg(not(any(feature = "hyper013", feature = "hyper1", feature = "reqwest")))]
+    return Rc::new(NoTransport);
+}
+
+/// Used when no transport feature is enabled, every request fails
+#[cfg(not(any(feature = "hyper013", feature = "hyper1", feature = "reqwest")))]
+#[derive(Debug)]
+struct NoTransport;
+
+#[cfg(not(any(feature = "hyper013", feature = "hyper1", feature = "reqwest")))]
+impl Transport for NoTransport {
+    fn post(&self, _url: &str, _body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+        let error =
+            "No transport enabled, enable a transport feature or use Factom::with_transport";
+        Box::pin(futures::future::ready(Err(error.into())))
+    }
+}
+
+/// Transport using hyper 0.13 with a https connector
+#[cfg(feature = "hyper013")]
+#[derive(Debug, Clone)]
+pub struct HyperTransport {
+    client: HttpsClient,
+}
+
+#[cfg(feature = "hyper013")]
+impl HyperTransport {
+    pub fn new() -> HyperTransport {
+        let connector = HttpsConnector::new();
+        let client = Client::builder().build::<_, hyper::Body>(connector);
+        HyperTransport {
+            client: Rc::new(client),
+        }
+    }
+}
+
+#[cfg(feature = "hyper013")]
+impl Default for HyperTransport {
+    fn default() -> Self {
+        HyperTransport::new()
+    }
+}
+
+#[cfg(feature = "hyper013")]
+impl Transport for HyperTransport {
+    fn post(&self, url: &str, body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+        let client = Rc::clone(&self.client);
+        let url = url.to_string();
+        Box::pin(async move {
+            let uri: http::Uri = url.parse()?;
+            let req = ApiRequest::builder(&uri).body(hyper::Body::from(body))?;
+            let res = client.request(req).await?;
+            let body = hyper::body::to_bytes(res.into_body()).await?;
+            Ok(body.to_vec())
+        })
+    }
+}
+
+/// Transport using hyper 1 and the hyper-util legacy client with a https
+/// connector
+#[cfg(feature = "hyper1")]
+#[derive(Debug, Clone)]
+pub struct Hyper1Transport {
+    client: hyper_util::client::legacy::Client<
+        hyper_tls1::HttpsConnector<hyper_util::client::legacy::connect::HttpConnector>,
+        http_body_util::Full<hyper1::body::Bytes>,
+    >,
+}
+
+#[cfg(feature = "hyper1")]
+impl Hyper1Transport {
+    pub fn new() -> Hyper1Transport {
+        let executor = hyper_util::rt::TokioExecutor::new();
+        let client = hyper_util::client::legacy::Client::builder(executor)
+            .build(hyper_tls1::HttpsConnector::new());
+        Hyper1Transport { client }
+    }
+}
+
+#[cfg(feature = "hyper1")]
+impl Default for Hyper1Transport {
+    fn default() -> Self {
+        Hyper1Transport::new()
+    }
+}
+
+#[cfg(feature = "hyper1")]
+impl Transport for Hyper1Transport {
+    fn post(&self, url: &str, body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+        use http_body_util::BodyExt;
+        let client = self.client.clone();
+        let url = url.to_string();
+        Box::pin(async move {
+            let req = hyper1::Request::builder()
+                .method("POST")
+                .header("content-type", "application/json")
+                .uri(url)
+                .body(http_body_util::Full::from(body))?;
+            let res = client.request(req).await?;
+            let body = res.into_body().collect().await?.to_bytes();
+            Ok(body.to_vec())
+        })
+    }
+}
+
+/// Transport using reqwest
+#[cfg(feature = "reqwest")]
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+#[cfg(feature = "reqwest")]
+impl ReqwestTransport {
+    pub fn new() -> ReqwestTransport {
+        ReqwestTransport::default()
+    }
+
+    /// Uses a configured reqwest client, for example with custom timeouts
+    pub fn with_client(client: reqwest::Client) -> ReqwestTransport {
+        ReqwestTransport { client }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl Transport for ReqwestTransport {
+    fn post(&self, url: &str, body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+        let request = self
+            .client
+            .post(url)
+            .header("content-type", "application/json")
+            .body(body);
+        Box::pin(async move {
+            let body = request.send().await?.bytes().await?;
+            Ok(body.to_vec())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::MemoryCache;
+    use crate::testing::MockNode;
+    use futures::executor::block_on;
+
+    #[test]
+    fn cached_through_transport() {
+        let node = MockNode::new();
+        node.respond("raw-data", json!({"data": "00"}));
+        let client = node.client().with_cache(MemoryCache::new(10));
+        for _ in 0..3 {
+            block_on(entry::raw_data(&client, "ab".repeat(32))).unwrap();
+        }
+        assert_eq!(node.requests().len(), 1);
+        assert_eq!(client.cache_stats().hits, 2);
+    }
+}
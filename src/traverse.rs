@@ -0,0 +1,577 @@
+//! Streams walking the entry blocks and entries of a chain.
+//!
+//! Entry blocks are linked backwards through their previous keymr, so a chain
+//! is walked from its head towards the genesis block. Walking forwards first
+//! discovers the blocks within the bounds, then yields them oldest first.
+//! Applications building state from a chain can use replay, which resumes from
+//! a persisted cursor.
+//!
+//! # Example
+//! ```no_run
+//! use factom::*;
+//! use factom::traverse::*;
+//! use futures::stream::StreamExt;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!   let client = Factom::open_node();
+//!   let chain = "843dbee7a49a9b9510d399759fbce24b1f700268c94508085abce352d70ed1f6";
+//!   let options = TraverseOptions {
+//!     from: Some(Bound::Height(200_000)),
+//!     ..TraverseOptions::default()
+//!   };
+//!   let mut entries = traverse::entries(&client, chain, options).expect("Parsing chain id");
+//!   while let Some(entry) = entries.next().await {
+//!     let entry = entry.expect("Fetching entry");
+//!     println!("{} {} {}", entry.dbheight, entry.entryhash, entry.entry.content);
+//!   }
+//! }
+//! ```
+use super::*;
+use crate::block::{EBlock, Entrylist};
+use crate::entry::Entry;
+use futures::future::{self, LocalBoxFuture};
+use futures::stream::{self, LocalBoxStream, StreamExt, TryStreamExt};
+
+/// Stream of entry blocks returned by entry_blocks
+pub type BlockStream = LocalBoxStream<'static, Result<ChainBlock>>;
+/// Stream of entries returned by entries
+pub type EntryStream = LocalBoxStream<'static, Result<ChainEntry>>;
+
+/// Order in which a chain is walked
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    /// From the chain head to the genesis block, newest entries first
+    #[default]
+    Backward,
+    /// From the genesis block to the chain head, oldest entries first
+    Forward,
+}
+
+/// Limits the blocks walked. Bounds are inclusive and chronological whatever
+/// the direction, `from` is the oldest block included and `to` the newest.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bound {
+    /// Directory block height of the entry block
+    Height(usize),
+    /// Keymr of the entry block
+    KeyMr(KeyMr),
+}
+
+/// Options for entry_blocks and entries
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraverseOptions {
+    pub direction: Direction,
+    /// Oldest block to include, None to walk to the genesis block
+    pub from: Option<Bound>,
+    /// Newest block to include, None to start at the chain head
+    pub to: Option<Bound>,
+    /// Number of entries fetched concurrently
+    pub concurrency: usize,
+}
+
+impl Default for TraverseOptions {
+    fn default() -> Self {
+        TraverseOptions {
+            direction: Direction::default(),
+            from: None,
+            to: None,
+            concurrency: 8,
+        }
+    }
+}
+
+/// An entry block along with its keymr
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainBlock {
+    pub keymr: KeyMr,
+    pub block: EBlock,
+}
+
+/// An entry along with the context of the entry block it was found in
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainEntry {
+    pub entryhash: EntryHash,
+    /// Time the entry was recorded, in seconds since the unix epoch
+    pub timestamp: usize,
+    /// Keymr of the entry block holding the entry
+    pub keymr: KeyMr,
+    /// Directory block height of the entry block
+    pub dbheight: usize,
+    /// Position of the entry within its entry block
+    pub index: usize,
+    pub entry: Entry,
+}
+
+struct Walk {
+    api: Factom,
+    chainid: ChainId,
+    next: Option<KeyMr>,
+    options: TraverseOptions,
+    started: bool,
+    done: bool,
+}
+
+/// Returns a stream of the chain's entry blocks within the bounds of the
+/// options. The stream ends after the first error.
+pub fn entry_blocks(
+    api: &Factom,
+    chainid: impl IntoHash<ChainId>,
+    options: TraverseOptions,
+) -> Result<BlockStream> {
+    let chainid = chainid.into_hash()?;
+    let forward = options.direction == Direction::Forward;
+    let walk = Walk {
+        api: api.clone(),
+        chainid,
+        next: None,
+        options,
+        started: false,
+        done: false,
+    };
+    let backward = stream::unfold(walk, |mut walk| async move {
+        if walk.done {
+            return None;
+        }
+        let result = walk.step().await;
+        walk.done |= result.is_err();
+        result.transpose().map(|item| (item, walk))
+    });
+    if !forward {
+        return Ok(backward.boxed_local());
+    }
+    let blocks = stream::once(backward.try_collect::<Vec<_>>());
+    Ok(blocks
+        .map(|blocks| match blocks {
+            Ok(blocks) => stream::iter(blocks.into_iter().rev().map(Ok)).left_stream(),
+            Err(e) => stream::once(future::ready(Err(e))).right_stream(),
+        })
+        .flatten()
+        .boxed_local())
+}
+
+impl Walk {
+    /// Fetches the next block within the bounds, None once the walk is done
+    async fn step(&mut self) -> Result<Option<ChainBlock>> {
+        if !self.started {
+            self.started = true;
+            self.next = match self.options.to {
+                Some(Bound::KeyMr(keymr)) => Some(keymr),
+                _ => Some(
+                    chain::chain_head(&self.api, self.chainid)
+                        .await?
+                        .into_result()?
+                        .chainhead,
+                ),
+            };
+        }
+        while let Some(keymr) = self.next.take() {
+            if keymr.is_zero() {
+                if let Some(Bound::KeyMr(from)) = self.options.from {
+                    return Err(format!("Entry block {} not found in chain", from).into());
+                }
+                break;
+            }
+            let block = block::entry_block(&self.api, keymr).await?.into_result()?;
+            let height = block.header.dbheight;
+            if let Some(Bound::Height(from)) = self.options.from {
+                if height < from {
+                    break;
+                }
+            }
+            if self.options.from != Some(Bound::KeyMr(keymr)) {
+                self.next = Some(block.header.prevkeymr);
+            }
+            if let Some(Bound::Height(to)) = self.options.to {
+                if height > to {
+                    continue;
+                }
+            }
+            return Ok(Some(ChainBlock { keymr, block }));
+        }
+        self.done = true;
+        Ok(None)
+    }
+}
+
+/// Returns a stream of the chain's entries within the bounds of the options,
+/// fetching up to `concurrency` entries at once while keeping their order.
+/// A failed block fetch ends the stream after yielding its error, failed entry
+/// fetches are yielded as errors without ending it.
+pub fn entries(
+    api: &Factom,
+    chainid: impl IntoHash<ChainId>,
+    options: TraverseOptions,
+) -> Result<EntryStream> {
+    let concurrency = options.concurrency;
+    let forward = options.direction == Direction::Forward;
+    let blocks = entry_blocks(api, chainid, options)?;
+    Ok(fetch_entries(api, blocks, forward, concurrency, None))
+}
+
+/// Replays a chain's entries oldest first.
+///
+/// Without a cursor the replay starts at the first entry of the chain. With a
+/// cursor only the blocks from the cursor's block up to the chain head are
+/// fetched and the replay starts after the cursor's entry, so persisting the
+/// cursor of the last processed entry allows incremental catch-up. An error is
+/// returned if the cursor's block isn't part of the chain.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+/// use factom::traverse::Cursor;
+/// use futures::stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::open_node();
+///   let chain = "843dbee7a49a9b9510d399759fbce24b1f700268c94508085abce352d70ed1f6";
+///   let mut cursor: Option<Cursor> = None;
+///   let mut entries = traverse::replay(&client, chain, cursor, 8).expect("Parsing chain id");
+///   while let Some(entry) = entries.next().await {
+///     let entry = entry.expect("Fetching entry");
+///     // Apply the entry then persist the cursor
+///     cursor = Some(entry.cursor());
+///   }
+///   dbg!(cursor);
+/// }
+/// ```
+pub fn replay(
+    api: &Factom,
+    chainid: impl IntoHash<ChainId>,
+    cursor: Option<Cursor>,
+    concurrency: usize,
+) -> Result<EntryStream> {
+    let options = TraverseOptions {
+        direction: Direction::Forward,
+        from: cursor.map(|cursor| Bound::KeyMr(cursor.keymr)),
+        to: None,
+        concurrency,
+    };
+    let blocks = entry_blocks(api, chainid, options)?;
+    Ok(fetch_entries(api, blocks, true, concurrency, cursor))
+}
+
+/// Position of an entry within its chain, used to resume a replay
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cursor {
+    /// Keymr of the entry block holding the entry
+    pub keymr: KeyMr,
+    /// Position of the entry within its entry block
+    pub index: usize,
+}
+
+impl ChainEntry {
+    /// Returns the cursor pointing at this entry
+    pub fn cursor(&self) -> Cursor {
+        Cursor {
+            keymr: self.keymr,
+            index: self.index,
+        }
+    }
+}
+
+/// Fetches the entries of each block, skipping those up to and including the
+/// cursor's entry
+fn fetch_entries(
+    api: &Factom,
+    blocks: BlockStream,
+    forward: bool,
+    concurrency: usize,
+    cursor: Option<Cursor>,
+) -> EntryStream {
+    let api = api.clone();
+    blocks
+        .map(move |block| match block {
+            Ok(block) => {
+                let skip = match cursor {
+                    Some(cursor) if cursor.keymr == block.keymr => cursor.index + 1,
+                    _ => 0,
+                };
+                let fetches = block_entries(&api, block, forward);
+                stream::iter(fetches.into_iter().skip(skip)).left_stream()
+            }
+            Err(e) => {
+                let failed: LocalBoxFuture<_> = Box::pin(future::ready(Err(e)));
+                stream::once(future::ready(failed)).right_stream()
+            }
+        })
+        .flatten()
+        .buffered(concurrency.max(1))
+        .boxed_local()
+}
+
+/// Futures fetching each entry of a block, in the order they are yielded
+pub(crate) fn block_entries(
+    api: &Factom,
+    block: ChainBlock,
+    forward: bool,
+) -> Vec<LocalBoxFuture<'static, Result<ChainEntry>>> {
+    let ChainBlock { keymr, block } = block;
+    let dbheight = block.header.dbheight;
+    let mut fetches: Vec<_> = block
+        .entrylist
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let api = api.clone();
+            let Entrylist {
+                entryhash,
+                timestamp,
+            } = item;
+            let fetch: LocalBoxFuture<_> = Box::pin(async move {
+                let entry = entry::entry(&api, entryhash).await?.into_result()?;
+                Ok(ChainEntry {
+                    entryhash,
+                    timestamp,
+                    keymr,
+                    dbheight,
+                    index,
+                    entry,
+                })
+            });
+            fetch
+        })
+        .collect();
+    if !forward {
+        fetches.reverse();
+    }
+    fetches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockNode, MockReply};
+    use crate::transport::Transport;
+    use futures::executor::block_on;
+    use futures_timer::Delay;
+    use serde_json::Value;
+    use std::time::Duration;
+
+    const CHAIN: &str = "a642a8674f46696cc47fdb6b65f9c87b2a19c5ea8123b3d2f0c13b6f33a9d5ef";
+
+    fn keymr(block: u8) -> KeyMr {
+        KeyMr([block; 32])
+    }
+
+    /// Entry hashes start with the block number and index, 0x41 is the second
+    /// entry of the fourth block
+    fn entryhash(block: u8, index: u8) -> EntryHash {
+        EntryHash([block << 4 | index; 32])
+    }
+
+    /// A chain of four entry blocks at heights 10 to 40 holding two entries each
+    fn chain_node() -> MockNode {
+        let node = MockNode::new();
+        node.respond("chain-head", json!({"chainhead": keymr(4)}))
+            .respond_with("entry-block", |params| {
+                let keymr: KeyMr = params["keymr"].as_str().unwrap().parse().unwrap();
+                let block = keymr.0[0];
+                MockReply::Result(json!({
+                    "header": {
+                        "blocksequencenumber": block - 1,
+                        "chainid": CHAIN,
+                        "prevkeymr": KeyMr([block - 1; 32]),
+                        "timestamp": 0,
+                        "dbheight": block as usize * 10,
+                    },
+                    "entrylist": (0..2).map(|index| json!({
+                        "entryhash": entryhash(block, index),
+                        "timestamp": index,
+                    })).collect::<Vec<_>>(),
+                }))
+            })
+            .respond_with("entry", |params| {
+                let hash = &params["hash"].as_str().unwrap()[..2];
+                MockReply::Result(json!({"chainid": CHAIN, "content": hash}))
+            });
+        node
+    }
+
+    fn options(from: Option<Bound>, to: Option<Bound>, direction: Direction) -> TraverseOptions {
+        TraverseOptions {
+            direction,
+            from,
+            to,
+            ..TraverseOptions::default()
+        }
+    }
+
+    fn collect(stream: EntryStream) -> Result<Vec<u8>> {
+        let entries: Vec<ChainEntry> = block_on(stream.try_collect())?;
+        Ok(entries.iter().map(|e| e.entryhash.0[0]).collect())
+    }
+
+    fn blocks_fetched(node: &MockNode) -> Vec<u8> {
+        node.requests_for("entry-block")
+            .iter()
+            .map(|r| {
+                r.params["keymr"]
+                    .as_str()
+                    .unwrap()
+                    .parse::<KeyMr>()
+                    .unwrap()
+                    .0[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn backward_walk() {
+        let node = chain_node();
+        let stream = entries(&node.client(), CHAIN, TraverseOptions::default()).unwrap();
+        let hashes = collect(stream).unwrap();
+        assert_eq!(hashes, vec![0x41, 0x40, 0x31, 0x30, 0x21, 0x20, 0x11, 0x10]);
+        assert_eq!(blocks_fetched(&node), vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn height_bounds() {
+        let node = chain_node();
+        let options = options(
+            Some(Bound::Height(20)),
+            Some(Bound::Height(30)),
+            Direction::Backward,
+        );
+        let blocks: Vec<ChainBlock> = block_on(
+            entry_blocks(&node.client(), CHAIN, options)
+                .unwrap()
+                .try_collect(),
+        )
+        .unwrap();
+        let keymrs: Vec<_> = blocks.iter().map(|b| b.keymr).collect();
+        assert_eq!(keymrs, vec![keymr(3), keymr(2)]);
+        // The head is walked past to reach the newest bound and the block below
+        // the oldest bound is read to find the end
+        assert_eq!(blocks_fetched(&node), vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn keymr_bounds() {
+        let node = chain_node();
+        let options = options(
+            Some(Bound::KeyMr(keymr(2))),
+            Some(Bound::KeyMr(keymr(3))),
+            Direction::Backward,
+        );
+        let hashes = collect(entries(&node.client(), CHAIN, options).unwrap()).unwrap();
+        assert_eq!(hashes, vec![0x31, 0x30, 0x21, 0x20]);
+        assert_eq!(blocks_fetched(&node), vec![3, 2]);
+        assert!(node.requests_for("chain-head").is_empty());
+    }
+
+    #[test]
+    fn missing_keymr_bound() {
+        let node = chain_node();
+        let options = options(Some(Bound::KeyMr(keymr(9))), None, Direction::Backward);
+        let blocks: Vec<_> = block_on(
+            entry_blocks(&node.client(), CHAIN, options)
+                .unwrap()
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(blocks.len(), 5);
+        let err = blocks[4].as_ref().unwrap_err().to_string();
+        assert!(err.ends_with("not found in chain"), "{}", err);
+    }
+
+    #[test]
+    fn forward_walk() {
+        let node = chain_node();
+        let options = options(Some(Bound::Height(20)), None, Direction::Forward);
+        let hashes = collect(entries(&node.client(), CHAIN, options).unwrap()).unwrap();
+        assert_eq!(hashes, vec![0x20, 0x21, 0x30, 0x31, 0x40, 0x41]);
+    }
+
+    /// Delays entry replies so later entries arrive before earlier ones
+    #[derive(Debug)]
+    struct Reordering(MockNode);
+
+    impl Transport for Reordering {
+        fn post(&self, url: &str, body: String) -> LocalBoxFuture<'static, Result<Vec<u8>>> {
+            let request: Value = serde_json::from_str(&body).unwrap();
+            let delay = match request["params"]["hash"].as_str() {
+                Some(hash) => {
+                    let byte = hash::decode_hex(&hash[..2]).unwrap()[0];
+                    let position = u64::from((byte >> 4) * 2 + (byte & 0x0f));
+                    Duration::from_millis((12 - position) * 5)
+                }
+                None => Duration::default(),
+            };
+            let reply = self.0.post(url, body);
+            Box::pin(async move {
+                Delay::new(delay).await;
+                reply.await
+            })
+        }
+    }
+
+    #[test]
+    fn concurrent_fetches_keep_order() {
+        let client = Factom::new().with_transport(Reordering(chain_node()));
+        let options = options(None, None, Direction::Forward);
+        let hashes = collect(entries(&client, CHAIN, options).unwrap()).unwrap();
+        assert_eq!(hashes, vec![0x10, 0x11, 0x20, 0x21, 0x30, 0x31, 0x40, 0x41]);
+    }
+
+    #[test]
+    fn failed_entry_continues() {
+        let node = chain_node();
+        node.respond_once("entry", MockReply::Result(json!({"content": "41"})))
+            .respond_once(
+                "entry",
+                MockReply::Error {
+                    code: -32008,
+                    message: "Entry not found".to_string(),
+                },
+            );
+        let results: Vec<_> = block_on(
+            entries(&node.client(), CHAIN, TraverseOptions::default())
+                .unwrap()
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(results.len(), 8);
+        assert!(results[1].is_err());
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 7);
+        assert_eq!(results[7].as_ref().unwrap().entryhash, entryhash(1, 0));
+    }
+
+    #[test]
+    fn replay_from_cursor() {
+        let node = chain_node();
+        let cursor = Cursor {
+            keymr: keymr(2),
+            index: 0,
+        };
+        let hashes = collect(replay(&node.client(), CHAIN, Some(cursor), 2).unwrap()).unwrap();
+        assert_eq!(hashes, vec![0x21, 0x30, 0x31, 0x40, 0x41]);
+        assert!(!node
+            .requests_for("entry")
+            .iter()
+            .any(|r| r.params["hash"] == json!(entryhash(2, 0))));
+    }
+
+    #[test]
+    fn replay_from_head() {
+        let node = chain_node();
+        let cursor = Cursor {
+            keymr: keymr(4),
+            index: 1,
+        };
+        let hashes = collect(replay(&node.client(), CHAIN, Some(cursor), 2).unwrap()).unwrap();
+        assert!(hashes.is_empty());
+        assert!(node.requests_for("entry").is_empty());
+    }
+
+    #[test]
+    fn replay_from_missing_cursor() {
+        let node = chain_node();
+        let cursor = Cursor {
+            keymr: keymr(9),
+            index: 0,
+        };
+        let err = collect(replay(&node.client(), CHAIN, Some(cursor), 2).unwrap()).unwrap_err();
+        assert!(err.to_string().ends_with("not found in chain"), "{}", err);
+    }
+}
@@ -1,21 +1,31 @@
 //! Functions relating to transactions
 use super::*;
+use futures::future::Future;
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use futures_timer::Delay;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 /// This api call is used to find the status of a transaction, whether it be a
-/// factoid, reveal entry, or commit entry. When using this, you must specify the
-/// type of the transaction by giving the chainid field 1 of 3 values:
+/// factoid, reveal entry, or commit entry. factomd tells the type of the
+/// transaction apart by the chainid field, which takes 1 of 3 values:
 ///
-/// * f for factoid transactions
-/// * c for entry credit transactions (commit entry/chain)
-/// * <ChainID> for reveal entry/chain
+/// * f for factoid transactions, sent by [fct_ack](fn.fct_ack.html)
+/// * c for entry credit transactions (commit entry/chain), sent by
+///   [commit_ack](fn.commit_ack.html)
+/// * `<ChainID>` for reveal entry/chain, sent by this function
 ///
-/// The status types returned are as follows:0
+/// **Breaking change:** ec_ack only takes a chain id, passing "c" or "f" as the
+/// chainid now fails to parse. Use commit_ack for commits and fct_ack for
+/// factoid transactions instead.
+///
+/// The status types returned are as follows:
 ///
 /// * “Unknown” : Not found anywhere
 /// * “NotConfirmed” : Found on local node, but not in network (Holding Map)
 /// * “TransactionACK” : Found in network, but not written to the blockchain
-/// yet (ProcessList)
+///   yet (ProcessList)
 /// * “DBlockConfirmed” : Found in Blockchain
 ///
 /// You may also provide the full marshaled transaction, instead of a hash, and it
@@ -25,23 +35,20 @@ use std::collections::HashMap;
 ///
 /// ### Entries
 /// Requesting an entry requires you to specify if the hash you provide is a commit
-/// or an entry hash. The chainid field is used to specify this. If you are
-/// searching for a commit, put c as the chainid field, otherwise, put the chainid
-/// that the entry belongs too.
+/// or an entry hash. For an entry hash call ec_ack with the chainid that the
+/// entry belongs to, for a commit txid call commit_ack.
 ///
 /// For commit/reveal acks, the response has 2 sections, one for the commit, one
 /// for the reveal. If you provide the entryhash and chainid, both will be
-/// filled (if found). If you only provide the commit txid and c as the chainid,
-/// then only the commitdata is guaranteed to come back with data. The committxid
+/// filled (if found). If you only provide the commit txid to commit_ack, then
+/// only the commitdata is guaranteed to come back with data. The committxid
 ///  and entryhash fields correspond to the commitdata and entrydata objects.
 ///
 /// ### Factoid Transactions
 ///
-/// The hash field for a factoid transaction is equivalent to txid. To indicate
-/// the hash is a factoid transaction, put f in the chainid field and the
-/// txid in the hash field.
-///
-/// The response will look different than entry related ack calls.
+/// The hash field for a factoid transaction is equivalent to txid, use fct_ack
+/// with the txid. The response will look different than entry related ack
+/// calls.
 ///
 /// ### Extra notes:
 ///
@@ -72,10 +79,11 @@ use std::collections::HashMap;
 /// ```
 pub async fn ec_ack(
     api: &Factom,
-    hash: &str,
-    chainid: &str,
+    hash: impl IntoHash<EntryHash>,
+    chainid: impl IntoHash<ChainId>,
     full_transaction: Option<&str>,
 ) -> Result<ApiResponse<EntryAck>> {
+    let (hash, chainid) = (hash.into_hash()?, chainid.into_hash()?);
     let mut req = ApiRequest::new("ack");
     req.params.insert("hash".to_string(), json!(hash));
     req.params.insert("chainid".to_string(), json!(chainid));
@@ -86,12 +94,32 @@ pub async fn ec_ack(
     parse(response).await
 }
 
+/// The status of an entry commit by its commit txid, before the entry and its
+/// chain are known, sent with c as the chainid. See documentation for ec_ack
+pub async fn commit_ack(
+    api: &Factom,
+    txid: impl IntoHash<TxId>,
+    full_transaction: Option<&str>,
+) -> Result<ApiResponse<EntryAck>> {
+    let txid = txid.into_hash()?;
+    let mut req = ApiRequest::new("ack");
+    req.params.insert("hash".to_string(), json!(txid));
+    req.params.insert("chainid".to_string(), json!("c"));
+    if let Some(tx) = full_transaction {
+        req.params.insert("fulltransaction".to_string(), json!(tx));
+    }
+    let response = factomd_call(api, req).await;
+    parse(response).await
+}
+
+/// The status of a factoid transaction by its txid, sent with f as the chainid.
 /// See documentation for ec_ack
 pub async fn fct_ack(
     api: &Factom,
-    hash: &str,
+    hash: impl IntoHash<TxId>,
     full_transaction: Option<&str>,
 ) -> Result<ApiResponse<FactoidAck>> {
+    let hash = hash.into_hash()?;
     let mut req = ApiRequest::new("ack");
     req.params.insert("hash".to_string(), json!(hash));
     req.params.insert("chainid".to_string(), json!("f"));
@@ -102,6 +130,187 @@ pub async fn fct_ack(
     parse(response).await
 }
 
+/// Legacy form of ec_ack kept by factomd for older clients, returns the status
+/// of a commit or entry by its hash without the chainid. Prefer ec_ack, factomd
+/// has deprecated this method.
+pub async fn entry_ack(
+    api: &Factom,
+    hash: impl IntoHash<EntryHash>,
+) -> Result<ApiResponse<EntryAck>> {
+    let hash = hash.into_hash()?;
+    let mut req = ApiRequest::new("entry-ack");
+    req.params.insert("txid".to_string(), json!(hash));
+    let response = factomd_call(api, req).await;
+    parse(response).await
+}
+
+/// Legacy form of fct_ack kept by factomd for older clients. Prefer fct_ack,
+/// factomd has deprecated this method.
+pub async fn factoid_ack(
+    api: &Factom,
+    hash: impl IntoHash<TxId>,
+) -> Result<ApiResponse<FactoidAck>> {
+    let hash = hash.into_hash()?;
+    let mut req = ApiRequest::new("factoid-ack");
+    req.params.insert("txid".to_string(), json!(hash));
+    let response = factomd_call(api, req).await;
+    parse(response).await
+}
+
+/// Stream of acknowledgement statuses returned by wait_for_entry and
+/// wait_for_transaction
+pub type AckStream = LocalBoxStream<'static, Result<AckStatus>>;
+
+/// Polling options for wait_for_entry and wait_for_transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaitOptions {
+    /// Pause between calls to the ack function
+    pub interval: Duration,
+    /// Time after which the stream gives up, None to wait indefinitely
+    pub timeout: Option<Duration>,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        WaitOptions {
+            interval: Duration::from_secs(5),
+            timeout: Some(Duration::from_secs(20 * 60)),
+        }
+    }
+}
+
+/// Polls ec_ack for an entry and yields its status each time it changes,
+/// starting with the current status. The stream ends once the entry is
+/// DBlockConfirmed, or after yielding an error if a call fails or the
+/// timeout is reached.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+/// use futures::stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::open_node();
+///   let hash = "e96cca381bf25f6dd4dfdf9f7009ff84ee6edaa3f47f9ccf06d2787482438f4b";
+///   let chainid = "f9164cd66af9d5773b4523a510b5eefb9a5e626480feeb6671ef2d17510ca300";
+///   let mut statuses = tx::wait_for_entry(&client, hash, chainid, tx::WaitOptions::default())
+///                         .expect("Parsing hashes");
+///   while let Some(status) = statuses.next().await {
+///     dbg!(status.expect("Waiting for entry"));
+///   }
+/// }
+/// ```
+pub fn wait_for_entry(
+    api: &Factom,
+    entryhash: impl IntoHash<EntryHash>,
+    chainid: impl IntoHash<ChainId>,
+    options: WaitOptions,
+) -> Result<AckStream> {
+    let hash = entryhash.into_hash()?;
+    let chainid = chainid.into_hash()?;
+    let api = api.clone();
+    Ok(poll_ack(options, move || {
+        let api = api.clone();
+        async move {
+            let ack = ec_ack(&api, hash, chainid, None).await?.into_result()?;
+            Ok(ack.entrydata.status)
+        }
+    }))
+}
+
+/// Polls fct_ack for a factoid transaction and yields its status each time it
+/// changes, see wait_for_entry.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+/// use futures::stream::TryStreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::open_node();
+///   let txid = "b8cd8d9e4e53c9d5e8d6e0e4d0d32f1bdc1c13bb8b9ae8e5d78a0bb4f6b62e4d";
+///   let statuses: Vec<_> = tx::wait_for_transaction(&client, txid, tx::WaitOptions::default())
+///                             .expect("Parsing txid")
+///                             .try_collect()
+///                             .await
+///                             .expect("Waiting for transaction");
+///   assert_eq!(statuses.last(), Some(&tx::AckStatus::DBlockConfirmed));
+/// }
+/// ```
+pub fn wait_for_transaction(
+    api: &Factom,
+    txid: impl IntoHash<TxId>,
+    options: WaitOptions,
+) -> Result<AckStream> {
+    let txid = txid.into_hash()?;
+    let api = api.clone();
+    Ok(poll_ack(options, move || {
+        let api = api.clone();
+        async move {
+            let ack = fct_ack(&api, txid, None).await?.into_result()?;
+            Ok(ack.status)
+        }
+    }))
+}
+
+struct AckPoll<F> {
+    poll: F,
+    last: Option<AckStatus>,
+    started: Option<Instant>,
+    finished: bool,
+}
+
+/// Turns a function fetching the current status into a stream of status changes
+fn poll_ack<F, Fut>(options: WaitOptions, poll: F) -> AckStream
+where
+    F: FnMut() -> Fut + 'static,
+    Fut: Future<Output = Result<AckStatus>> + 'static,
+{
+    let state = AckPoll {
+        poll,
+        last: None,
+        started: None,
+        finished: false,
+    };
+    stream::unfold(state, move |mut state| {
+        let options = options.clone();
+        async move {
+            if state.finished {
+                return None;
+            }
+            loop {
+                let started = match state.started {
+                    Some(started) => {
+                        Delay::new(options.interval).await;
+                        started
+                    }
+                    None => *state.started.get_or_insert_with(Instant::now),
+                };
+                match (state.poll)().await {
+                    Ok(status) if state.last != Some(status) => {
+                        state.last = Some(status);
+                        state.finished = status == AckStatus::DBlockConfirmed;
+                        return Some((Ok(status), state));
+                    }
+                    Ok(_) => (),
+                    Err(e) => {
+                        state.finished = true;
+                        return Some((Err(e), state));
+                    }
+                }
+                if options.timeout.is_some_and(|t| started.elapsed() >= t) {
+                    state.finished = true;
+                    let timeout = "Timed out waiting for confirmation".into();
+                    return Some((Err(timeout), state));
+                }
+            }
+        }
+    })
+    .boxed_local()
+}
+
 /// Submit a factoid transaction. The transaction hex encoded string is
 /// documented here:
 /// [Github Documentation](https://github.com/FactomProject/FactomDocs/blob/master/factomDataStructureDetails.md#factoid-transaction)
@@ -160,7 +369,11 @@ pub async fn factoid_submit(api: &Factom, transaction: &str) -> Result<ApiRespon
 ///   assert_eq!(response.result.includedindirectoryblockheight, 220000);
 /// }
 /// ```
-pub async fn transaction(api: &Factom, hash: &str) -> Result<ApiResponse<Transaction>> {
+pub async fn transaction(
+    api: &Factom,
+    hash: impl IntoHash<TxId>,
+) -> Result<ApiResponse<Transaction>> {
+    let hash = hash.into_hash()?;
     let mut req = ApiRequest::new("transaction");
     req.params.insert("hash".to_string(), json!(hash));
     let response = factomd_call(api, req).await;
@@ -210,7 +423,7 @@ pub async fn add_ec_output(
     txname: &str,
     address: &str,
     amount: u64,
-) -> Result<ApiResponse<NewTx>> {
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("add-ec-output");
     req.params.insert("tx-name".to_string(), json!(txname));
     req.params.insert("address".to_string(), json!(address));
@@ -229,7 +442,11 @@ pub async fn add_ec_output(
 ///
 /// Add fee is a part of sending a transaction to see a full example check the
 /// examples folder.
-pub async fn add_fee(api: &Factom, txname: &str, address: &str) -> Result<ApiResponse<Tx>> {
+pub async fn add_fee(
+    api: &Factom,
+    txname: &str,
+    address: &str,
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("add-fee");
     req.params.insert("tx-name".to_string(), json!(txname));
     req.params.insert("address".to_string(), json!(address));
@@ -253,7 +470,7 @@ pub async fn add_input(
     txname: &str,
     address: &str,
     amount: u64,
-) -> Result<ApiResponse<Tx>> {
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("add-input");
     req.params.insert("tx-name".to_string(), json!(txname));
     req.params.insert("address".to_string(), json!(address));
@@ -277,7 +494,7 @@ pub async fn add_output(
     txname: &str,
     address: &str,
     amount: u64,
-) -> Result<ApiResponse<Tx>> {
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("add-output");
     req.params.insert("tx-name".to_string(), json!(txname));
     req.params.insert("address".to_string(), json!(address));
@@ -301,7 +518,10 @@ pub async fn add_output(
 ///   assert!(response.success());
 /// }
 /// ```
-pub async fn delete_transaction(api: &Factom, tx_name: &str) -> Result<ApiResponse<DeleteTx>> {
+pub async fn delete_transaction(
+    api: &Factom,
+    tx_name: &str,
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("delete-transaction");
     req.params.insert("tx-name".to_string(), json!(tx_name));
     let response = walletd_call(api, req).await;
@@ -328,7 +548,10 @@ pub async fn delete_transaction(api: &Factom, tx_name: &str) -> Result<ApiRespon
 ///   assert!(response.success());
 /// }
 /// ```
-pub async fn new_transaction(api: &Factom, tx_name: &str) -> Result<ApiResponse<NewTx>> {
+pub async fn new_transaction(
+    api: &Factom,
+    tx_name: &str,
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("new-transaction");
     req.params.insert("tx-name".to_string(), json!(tx_name));
     let response = walletd_call(api, req).await;
@@ -338,7 +561,10 @@ pub async fn new_transaction(api: &Factom, tx_name: &str) -> Result<ApiResponse<
 ///
 /// sign_transaction is used in the transaction process, the full process can be
 /// found in the examples folder
-pub async fn sign_transaction(api: &Factom, tx_name: &str) -> Result<ApiResponse<Tx>> {
+pub async fn sign_transaction(
+    api: &Factom,
+    tx_name: &str,
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("sign-transaction");
     req.params.insert("tx-name".to_string(), json!(tx_name));
     let response = walletd_call(api, req).await;
@@ -356,7 +582,11 @@ pub async fn sign_transaction(api: &Factom, tx_name: &str) -> Result<ApiResponse
 ///
 /// sub_fee is used in the transaction process, the full process and an example of
 /// this function being used can be found in the examples folder
-pub async fn sub_fee(api: &Factom, tx_name: &str, address: &str) -> Result<ApiResponse<Tx>> {
+pub async fn sub_fee(
+    api: &Factom,
+    tx_name: &str,
+    address: &str,
+) -> Result<ApiResponse<WalletTransaction>> {
     let mut req = ApiRequest::new("sub-fee");
     req.params.insert("tx-name".to_string(), json!(tx_name));
     req.params.insert("address".to_string(), json!(address));
@@ -399,7 +629,7 @@ pub async fn tmp_transactions(api: &Factom) -> Result<ApiResponse<TmpTransaction
 ///
 /// ### By Address
 /// Retrieves all transactions that involve a particular address.
-pub async fn transactions(api: &Factom, filter: SearchBy) -> Result<ApiResponse<Transactions>> {
+pub async fn transactions(api: &Factom, filter: SearchBy<'_>) -> Result<ApiResponse<Transactions>> {
     let mut req = ApiRequest::new("transactions");
     match filter {
         SearchBy::Txid(txid) => {
@@ -419,21 +649,152 @@ pub async fn transactions(api: &Factom, filter: SearchBy) -> Result<ApiResponse<
     parse(response).await
 }
 
+/// Sends factoshis from a factoid address in the wallet to another factoid
+/// address, running the whole new-transaction, add-input, add-output, add-fee,
+/// sign, compose and factoid-submit sequence. The fee is paid by the sending
+/// address on top of the amount.
+///
+/// The working transaction is deleted from the wallet once submitted, or as
+/// soon as any step fails, in which case the error of that step is returned.
+/// # Example
+/// ```no_run
+/// use factom::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::testnet_node();
+///   let from = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+///   let to = "FA3cih2o2tjEUsnnFR4jX1tQXPpSXFwsp3rhVp6odL5PNCHWvZV1";
+///   let response = tx::send_fct(&client, from, to, 100_000_000).await.unwrap();
+///   dbg!(&response.result.txid);
+/// }
+/// ```
+pub async fn send_fct(
+    api: &Factom,
+    from: &str,
+    to: &str,
+    amount: u64,
+) -> Result<ApiResponse<FctSubmit>> {
+    let tx_name = tmp_tx_name("send-fct");
+    let output = Payee::Factoid(to);
+    submit_with_rollback(api, &tx_name, from, amount, output).await
+}
+
+/// Buys entry credits for an entry credit address with factoids from an
+/// address in the wallet. The amount is the number of entry credits, converted
+/// to factoshis at the current entry credit rate. The transaction is built,
+/// submitted and rolled back on failure like [send_fct](fn.send_fct.html).
+/// # Example
+/// ```no_run
+/// use factom::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::testnet_node();
+///   let from = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+///   let ec = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+///   let response = tx::buy_ec(&client, from, ec, 100).await.unwrap();
+///   dbg!(&response.result.txid);
+/// }
+/// ```
+pub async fn buy_ec(
+    api: &Factom,
+    from: &str,
+    ec_address: &str,
+    amount: u64,
+) -> Result<ApiResponse<FctSubmit>> {
+    let rate = factomd::entry_credit_rate(api).await?.into_result()?.rate;
+    let factoshis = amount
+        .checked_mul(rate as u64)
+        .ok_or("EC purchase amount overflows")?;
+    let tx_name = tmp_tx_name("buy-ec");
+    let output = Payee::EntryCredit(ec_address);
+    submit_with_rollback(api, &tx_name, from, factoshis, output).await
+}
+
+/// The receiving side of a transaction built by send_fct or buy_ec
+#[derive(Debug, Copy, Clone)]
+enum Payee<'a> {
+    Factoid(&'a str),
+    EntryCredit(&'a str),
+}
+
+/// Working transaction names unlikely to collide with the user's own
+fn tmp_tx_name(prefix: &str) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("{}-{}", prefix, now.as_nanos())
+}
+
+async fn submit_with_rollback(
+    api: &Factom,
+    tx_name: &str,
+    from: &str,
+    amount: u64,
+    output: Payee<'_>,
+) -> Result<ApiResponse<FctSubmit>> {
+    new_transaction(api, tx_name).await?.into_result()?;
+    let submitted = build_and_submit(api, tx_name, from, amount, output).await;
+    // The working transaction is removed whether or not it was sent, a failed
+    // rollback doesn't hide the error of the failed step
+    let _ = delete_transaction(api, tx_name).await;
+    submitted
+}
+
+async fn build_and_submit(
+    api: &Factom,
+    tx_name: &str,
+    from: &str,
+    amount: u64,
+    output: Payee<'_>,
+) -> Result<ApiResponse<FctSubmit>> {
+    add_input(api, tx_name, from, amount).await?.into_result()?;
+    match output {
+        Payee::Factoid(to) => add_output(api, tx_name, to, amount).await?,
+        Payee::EntryCredit(ec) => add_ec_output(api, tx_name, ec, amount).await?,
+    }
+    .into_result()?;
+    add_fee(api, tx_name, from).await?.into_result()?;
+    sign_transaction(api, tx_name).await?.into_result()?;
+    let composed = compose::compose_transaction(api, tx_name)
+        .await?
+        .into_result()?;
+    let response = factoid_submit(api, &composed.params.transaction).await?;
+    if response.is_err() {
+        return Err(Box::new(response.error));
+    }
+    Ok(response)
+}
+
 /// Search options for the transactions function
 /// * Range(usize, usize)
-/// * Txid(&str)
-/// * Address(&str)
-pub enum SearchBy {
+/// * Txid(String or &str)
+/// * Address(String or &str)
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchBy<'a> {
     Range(usize, usize),
-    Txid(&'static str),
-    Address(&'static str),
+    Txid(Cow<'a, str>),
+    Address(Cow<'a, str>),
+}
+
+impl<'a> SearchBy<'a> {
+    /// Search by transaction id
+    pub fn txid(txid: impl Into<Cow<'a, str>>) -> Self {
+        SearchBy::Txid(txid.into())
+    }
+
+    /// Search for the transactions involving an address
+    pub fn address(address: impl Into<Cow<'a, str>>) -> Self {
+        SearchBy::Address(address.into())
+    }
 }
 
 /// factoid-submit function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FctSubmit {
     pub message: String,
-    pub txid: String,
+    pub txid: TxId,
 }
 
 /// transaction function
@@ -491,7 +852,7 @@ pub struct PendingTxs {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PendingTx {
-    pub transactionid: String,
+    pub transactionid: TxId,
     pub status: String,
     pub inputs: Option<Vec<Input>>,
     pub outputs: Option<Vec<Output>>,
@@ -501,98 +862,117 @@ pub struct PendingTx {
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EntryAck {
-    pub committxid: String,
-    pub entryhash: String,
+    pub committxid: TxId,
+    pub entryhash: EntryHash,
     pub commitdata: Commitdata,
     pub entrydata: Entrydata,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FactoidAck {
-    txid: String,
-    transactiondate: i64,
-    transactiondatestring: String,
-    blockdate: i64,
-    blockdatestring: String,
-    status: String,
+    pub txid: TxId,
+    #[serde(default)]
+    pub transactiondate: i64,
+    #[serde(default)]
+    pub transactiondatestring: String,
+    #[serde(default)]
+    pub blockdate: i64,
+    #[serde(default)]
+    pub blockdatestring: String,
+    pub status: AckStatus,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Commitdata {
-    pub status: String,
+    pub status: AckStatus,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Entrydata {
-    pub status: String,
+    pub status: AckStatus,
 }
 
-/// new-transaction and add-ec-output functions
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct NewTx {
-    #[serde(default)]
-    pub feesrequired: usize,
-    pub signed: bool,
-    pub name: String,
-    pub timestamp: i64,
-    pub totalecoutputs: i64,
-    pub totalinputs: i64,
-    pub totaloutputs: i64,
-    pub inputs: Option<Vec<TxInput>>,
-    pub outputs: Option<Vec<TxOutput>>,
-    pub ecoutputs: Option<Vec<Ecoutput>>,
-    pub txid: String,
+/// Status returned by the ack functions, ordered from least to most confirmed
+/// so statuses can be compared with each other.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub enum AckStatus {
+    /// Not found anywhere, also used for any status this crate doesn't know
+    #[default]
+    Unknown,
+    /// Found on the local node, but not in network (Holding Map)
+    NotConfirmed,
+    /// Found in network, but not written to the blockchain yet (ProcessList)
+    #[serde(rename = "TransactionACK")]
+    TransactionAck,
+    /// Found in the blockchain
+    DBlockConfirmed,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct TxInput {
-    pub address: String,
-    pub amount: i64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct TxOutput {
-    pub address: String,
-    pub amount: i64,
-}
-
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Ecoutput {
-    pub address: String,
-    pub amount: i64,
+/// Unknown statuses are read as Unknown rather than failing the whole ack
+impl<'de> Deserialize<'de> for AckStatus {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let status = String::deserialize(deserializer)?;
+        Ok(match status.as_str() {
+            "NotConfirmed" => AckStatus::NotConfirmed,
+            "TransactionACK" => AckStatus::TransactionAck,
+            "DBlockConfirmed" => AckStatus::DBlockConfirmed,
+            _ => AckStatus::Unknown,
+        })
+    }
 }
 
-/// add-input, add-output, add-fee, sub-fee, sign-transaction functions
+/// A transaction being built in the wallet, returned by the new-transaction,
+/// add-input, add-output, add-ec-output, add-fee, sub-fee, sign-transaction and
+/// delete-transaction functions and listed by the transactions function. Fields
+/// a call doesn't return are left at their defaults.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Tx {
-    pub feespaid: i64,
-    pub feesrequired: i64,
-    pub signed: bool,
+#[serde(default)]
+pub struct WalletTransaction {
     pub name: String,
+    /// The transaction id, in flux until the transaction is signed
+    pub txid: TxId,
+    /// Only set by the transactions function
+    pub blockheight: u64,
+    pub signed: bool,
     pub timestamp: i64,
-    pub totalecoutputs: i64,
-    pub totalinputs: i64,
-    pub totaloutputs: i64,
-    pub inputs: Vec<TxInput>,
-    pub outputs: Vec<TxOutput>,
-    pub ecoutputs: Vec<Ecoutput>,
-    pub txid: String,
+    pub totalinputs: u64,
+    pub totaloutputs: u64,
+    pub totalecoutputs: u64,
+    pub feespaid: u64,
+    pub feesrequired: u64,
+    #[serde(deserialize_with = "null_as_empty")]
+    pub inputs: Vec<TxAddress>,
+    #[serde(deserialize_with = "null_as_empty")]
+    pub outputs: Vec<TxAddress>,
+    #[serde(deserialize_with = "null_as_empty")]
+    pub ecoutputs: Vec<TxAddress>,
 }
 
-/// delete-transaction function
+/// An input or output of a wallet transaction, amounts are in factoshis
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct DeleteTx {
-    pub signed: bool,
-    pub name: String,
-    pub timestamp: i64,
-    pub totalecoutputs: i64,
-    pub totalinputs: i64,
-    pub totaloutputs: i64,
-    pub inputs: ::serde_json::Value,
-    pub outputs: ::serde_json::Value,
-    pub ecoutputs: ::serde_json::Value,
+pub struct TxAddress {
+    pub address: String,
+    pub amount: u64,
 }
 
+#[deprecated(note = "use WalletTransaction")]
+pub type NewTx = WalletTransaction;
+#[deprecated(note = "use WalletTransaction")]
+pub type Tx = WalletTransaction;
+#[deprecated(note = "use WalletTransaction")]
+pub type DeleteTx = WalletTransaction;
+#[deprecated(note = "use WalletTransaction")]
+pub type Txs = WalletTransaction;
+#[deprecated(note = "use TxAddress")]
+pub type TxInput = TxAddress;
+#[deprecated(note = "use TxAddress")]
+pub type TxOutput = TxAddress;
+#[deprecated(note = "use TxAddress")]
+pub type Ecoutput = TxAddress;
+
 /// tmp-transactions function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TmpTransactions {
@@ -603,30 +983,147 @@ pub struct TmpTransactions {
 pub struct TmpTransaction {
     #[serde(rename = "tx-name")]
     pub tx_name: String,
-    pub txid: String,
-    pub totalinputs: i64,
-    pub totaloutputs: i64,
-    pub totalecoutputs: i64,
+    pub txid: TxId,
+    pub totalinputs: u64,
+    pub totaloutputs: u64,
+    pub totalecoutputs: u64,
 }
 
 /// transactions function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Transactions {
-    pub transactions: Vec<Txs>,
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub transactions: Vec<WalletTransaction>,
 }
 
-/// Individual transactions from the transactions function
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct Txs {
-    pub blockheight: i64,
-    pub feespaid: i64,
-    pub signed: bool,
-    pub timestamp: i64,
-    pub totalecoutputs: i64,
-    pub totalinputs: i64,
-    pub totaloutputs: i64,
-    pub inputs: Vec<Input>,
-    pub outputs: ::serde_json::Value,
-    pub ecoutputs: Vec<Ecoutput>,
-    pub txid: String,
+/// walletd returns null rather than an empty list
+fn null_as_empty<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockNode;
+    use futures::executor::block_on;
+
+    #[test]
+    fn ack_status_changes() {
+        let mut statuses = vec![
+            AckStatus::DBlockConfirmed,
+            AckStatus::TransactionAck,
+            AckStatus::TransactionAck,
+            AckStatus::Unknown,
+        ];
+        let options = WaitOptions {
+            interval: Duration::from_millis(1),
+            timeout: None,
+        };
+        let stream = poll_ack(options, move || {
+            let status = statuses.pop().unwrap();
+            async move { Ok(status) }
+        });
+        let seen: Vec<_> = block_on(stream.map(|s| s.unwrap()).collect());
+        assert_eq!(
+            seen,
+            vec![
+                AckStatus::Unknown,
+                AckStatus::TransactionAck,
+                AckStatus::DBlockConfirmed
+            ]
+        );
+    }
+
+    #[test]
+    fn ack_timeout() {
+        let options = WaitOptions {
+            interval: Duration::from_millis(1),
+            timeout: Some(Duration::from_millis(5)),
+        };
+        let stream = poll_ack(options, || async { Ok(AckStatus::NotConfirmed) });
+        let seen: Vec<_> = block_on(stream.collect());
+        assert_eq!(seen.len(), 2);
+        assert!(seen[1].is_err());
+    }
+
+    #[test]
+    fn unknown_ack_status() {
+        let commit: Commitdata =
+            serde_json::from_value(json!({"status": "RequestFailed"})).unwrap();
+        assert_eq!(commit.status, AckStatus::Unknown);
+        let entry: Entrydata = serde_json::from_value(json!({"status": "TransactionACK"})).unwrap();
+        assert_eq!(entry.status, AckStatus::TransactionAck);
+        assert_eq!(json!(entry.status), "TransactionACK");
+    }
+
+    #[test]
+    fn wallet_transaction_nulls() {
+        let json = r#"{"signed": false, "name": "tx", "timestamp": 1, "totalecoutputs": 0,
+            "totalinputs": 0, "totaloutputs": 0, "inputs": null, "outputs": null,
+            "ecoutputs": null}"#;
+        let tx: WalletTransaction = serde_json::from_str(json).unwrap();
+        assert!(tx.inputs.is_empty());
+        assert_eq!(tx.feesrequired, 0);
+    }
+
+    #[test]
+    fn send_fct_sequence() {
+        let node = MockNode::new();
+        node.respond(
+            "compose-transaction",
+            json!({"params": {"transaction": "0201"}}),
+        );
+        let client = node.client();
+        let from = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+        let to = "FA3cih2o2tjEUsnnFR4jX1tQXPpSXFwsp3rhVp6odL5PNCHWvZV1";
+        block_on(send_fct(&client, from, to, 5)).unwrap();
+        let methods: Vec<_> = node.requests().into_iter().map(|r| r.method).collect();
+        let expected = vec![
+            "new-transaction",
+            "add-input",
+            "add-output",
+            "add-fee",
+            "sign-transaction",
+            "compose-transaction",
+            "factoid-submit",
+            "delete-transaction",
+        ];
+        assert_eq!(methods, expected);
+        let submit = node.requests_for("factoid-submit");
+        assert_eq!(submit[0].params["transaction"], "0201");
+    }
+
+    #[test]
+    fn buy_ec_rolls_back() {
+        let node = MockNode::new();
+        node.respond("entry-credit-rate", json!({"rate": 1000}))
+            .respond_error("add-fee", -32603, "Inputs and outputs don't add up");
+        let client = node.client();
+        let from = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+        let ec = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+        let error = block_on(buy_ec(&client, from, ec, 10)).unwrap_err();
+        assert!(error.to_string().contains("don't add up"));
+        let output = &node.requests_for("add-ec-output")[0];
+        assert_eq!(output.params["amount"], 10_000);
+        let name = &output.params["tx-name"];
+        let deleted = node.requests_for("delete-transaction");
+        assert_eq!(&deleted[0].params["tx-name"], name);
+        assert!(node.requests_for("factoid-submit").is_empty());
+    }
+
+    #[test]
+    fn buy_ec_overflow() {
+        let node = MockNode::new();
+        node.respond("entry-credit-rate", json!({"rate": 1000}));
+        let client = node.client();
+        let from = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+        let ec = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
+        let error = block_on(buy_ec(&client, from, ec, u64::MAX / 10)).unwrap_err();
+        assert_eq!(error.to_string(), "EC purchase amount overflows");
+        assert!(node.requests_for("new-transaction").is_empty());
+    }
 }
@@ -1,10 +1,61 @@
 //! General purpose helper functions
 use super::*;
-use crate::chain::RevealChain;
-use crate::entry::{Entry, RevealEntry};
-use std::{thread, time};
+use crate::compose::Compose;
+use crate::entry::Entry;
+use crate::tx::AckStatus;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use futures_timer::Delay;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::time::{Duration, Instant};
 
-/// Creates a chain going through the entire compose, commit, reveal workflow
+/// Length of the signed ledger portion of an entry commit message, the commit
+/// txid is the hash of these bytes
+const ENTRY_COMMIT_LEN: usize = 40;
+/// Length of the signed ledger portion of a chain commit message
+const CHAIN_COMMIT_LEN: usize = 104;
+
+/// Options for the commit, acknowledge, reveal workflow used by submit_entry
+/// and submit_chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubmitOptions {
+    /// Number of times a call is retried after a network error
+    pub retries: usize,
+    /// Pause before retrying a failed call
+    pub retry_delay: Duration,
+    /// Pause between polls of the commit acknowledgement
+    pub ack_interval: Duration,
+    /// Maximum time to wait for the commit to be acknowledged
+    pub ack_timeout: Duration,
+}
+
+impl Default for SubmitOptions {
+    fn default() -> Self {
+        SubmitOptions {
+            retries: 3,
+            retry_delay: Duration::from_secs(1),
+            ack_interval: Duration::from_millis(500),
+            ack_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// The outcome of a successful commit and reveal
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Submission {
+    /// Transaction id of the commit, computed from the commit message
+    pub committxid: TxId,
+    pub entryhash: EntryHash,
+    pub chainid: ChainId,
+    /// Factomd already had a commit for this entry, in which case the reveal
+    /// was sent without waiting for the commit to be acknowledged
+    pub repeated_commit: bool,
+    /// Status of the revealed entry when the workflow finished
+    pub status: AckStatus,
+}
+
+/// Creates an identity chain going through the entire compose, commit, reveal
+/// workflow. See submit_chain for details.
 ///
 /// # Example
 ///
@@ -19,8 +70,10 @@ use std::{thread, time};
 ///                 "idpub3fXRj21gXveTk6RKYrpJniWV2pAanQktekEt62yhJUQXyPdvwL",
 ///                 "idpub2GU1Pcax2PibH8hHZg58fKRiSJKQWQkWYkpmt7VH1jCXBgqp9w"];
 /// let ec_pub = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
-/// let response = utils::create_id_chain(&client, name, pubkeys, ec_pub).await;
-/// dbg!(response);
+/// let submission = utils::create_id_chain(&client, name, pubkeys, ec_pub)
+///                         .await
+///                         .expect("Creating identity chain");
+/// dbg!(submission);
 /// }
 /// ```
 pub async fn create_id_chain(
@@ -28,21 +81,15 @@ pub async fn create_id_chain(
     name: Vec<&str>,
     pubkeys: Vec<&str>,
     ec_pub: &str,
-) -> ApiResponse<RevealChain> {
-    let compose_query = compose::compose_id_chain(&client, name, pubkeys, ec_pub, false);
-    let compose_response = compose_query.await.expect("Composing Identity Chain");
-    let commit = compose_response.result.commit.params.message;
-    let commit_query = chain::commit_chain(&client, &commit);
-    commit_query.await.expect("Committing Identity Chain");
-    // Short pause for reveal
-    thread::sleep(time::Duration::from_secs(1));
-    let reveal = compose_response.result.reveal.params.entry;
-    let reveal_query = chain::reveal_chain(&client, &reveal);
-    let reveal_response = reveal_query.await.expect("Revealing Identity Chain");
-    reveal_response
-}
-
-/// Creates an entry going through the entire compose, commit, reveal workflow
+) -> Result<Submission> {
+    let compose = compose::compose_id_chain(client, name, pubkeys, ec_pub, false)
+        .await?
+        .into_result()?;
+    submit_chain(client, &compose, &SubmitOptions::default()).await
+}
+
+/// Creates an entry going through the entire compose, commit, reveal workflow.
+/// See submit_entry for details.
 ///
 /// # Example
 /// ```no_run
@@ -55,30 +102,31 @@ pub async fn create_id_chain(
 /// let ext_ids = vec!["Api Client", "Test Chain"];
 /// let content = "Testing";
 /// let ec_pub = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
-/// let response = utils::create_entry(&client,chainid, ext_ids, content, ec_pub).await;
-/// dbg!(response);
+/// let submission = utils::create_entry(&client,chainid, ext_ids, content, ec_pub)
+///                         .await
+///                         .expect("Creating entry");
+/// dbg!(submission);
 /// }
 /// ```
-pub async fn create_entry(
+pub async fn create_entry<E, C>(
     client: &Factom,
-    chainid: &str,
-    ext_ids: Vec<&str>,
-    content: &str,
+    chainid: impl IntoHash<ChainId>,
+    ext_ids: Vec<E>,
+    content: C,
     ec_pub: &str,
-) -> ApiResponse<RevealEntry> {
-    let compose_query = compose::compose_entry(&client, chainid, ext_ids, &content, ec_pub);
-    let compose_response = compose_query.await.expect("Compose Entry");
-    let commit = compose_response.result.commit.params.message;
-    let commit_query = entry::commit_entry(&client, &commit);
-    commit_query.await.expect("Commit Entry");
-    thread::sleep(time::Duration::from_millis(300));
-    let reveal = compose_response.result.reveal.params.entry;
-    let reveal_query = entry::reveal_entry(&client, &reveal);
-    let reveal_response = reveal_query.await.expect("Reveal Entry");
-    reveal_response
-}
-
-/// Creates a chain going through the entire compose, commit, reveal workflow
+) -> Result<Submission>
+where
+    E: AsRef<[u8]>,
+    C: AsRef<[u8]>,
+{
+    let compose = compose::compose_entry(client, chainid, ext_ids, content, ec_pub)
+        .await?
+        .into_result()?;
+    submit_entry(client, &compose, &SubmitOptions::default()).await
+}
+
+/// Creates a chain going through the entire compose, commit, reveal workflow.
+/// See submit_chain for details.
 ///
 /// # Example
 /// ```no_run
@@ -89,33 +137,163 @@ pub async fn create_entry(
 /// let ext_ids = vec!["Api Client", "Test Chain"];
 /// let content = "Testing";
 /// let ec_pub = "EC2MJzCcHqYJyujnPzjitEaHhtEPVBhmEWUKkv4SVaaKeYcq3fqK";
-/// let response = utils::create_chain(&client, ext_ids, content, ec_pub).await;
-/// dbg!(response);
+/// let submission = utils::create_chain(&client, ext_ids, content, ec_pub)
+///                         .await
+///                         .expect("Creating chain");
+/// dbg!(submission);
 /// }
 /// ```
-pub async fn create_chain(
+pub async fn create_chain<E, C>(
     client: &Factom,
-    ext_ids: Vec<&str>,
-    content: &str,
+    ext_ids: Vec<E>,
+    content: C,
     ec_pub: &str,
-) -> ApiResponse<RevealChain> {
-    let compose_query = compose::compose_chain(&client, ext_ids, content, ec_pub);
-    let compose_response = compose_query.await.expect("Composing Chain");
-
-    let commit = compose_response.result.commit.params.message;
-    let commit_query = chain::commit_chain(&client, &commit);
-    commit_query.await.expect("Committing Chain");
-    // Short pause for reveal
-    thread::sleep(time::Duration::from_secs(1));
-    let reveal = compose_response.result.reveal.params.entry;
-    let reveal_query = chain::reveal_chain(&client, &reveal);
-    let reveal_response = reveal_query.await.expect("Revealing Chain");
-    reveal_response
-}
-
-/// Traverses a chain from the head to root returning all entries
-/// Can specify a depth of blocks to go back from the chainhead
-/// A depth of 0 will traverse the whole chain.
+) -> Result<Submission>
+where
+    E: AsRef<[u8]>,
+    C: AsRef<[u8]>,
+{
+    let compose = compose::compose_chain(client, ext_ids, content, ec_pub)
+        .await?
+        .into_result()?;
+    submit_chain(client, &compose, &SubmitOptions::default()).await
+}
+
+/// Commits and reveals a composed entry.
+///
+/// The commit is sent first and the ack function is polled until the commit
+/// reaches at least TransactionACK before the entry is revealed. A repeated
+/// commit error from factomd means the commit already exists, so the entry is
+/// revealed straight away. Calls failing with a network error are retried as
+/// configured in the options, api errors are returned as is.
+pub async fn submit_entry(
+    client: &Factom,
+    compose: &Compose,
+    options: &SubmitOptions,
+) -> Result<Submission> {
+    let commit = &compose.commit.params.message;
+    let (committxid, _) = commit_ids(commit, ENTRY_COMMIT_LEN)?;
+    let response = retry(options, || entry::commit_entry(client, commit)).await?;
+    let repeated_commit = commit_accepted(response)?;
+    if !repeated_commit {
+        wait_for_commit(client, committxid, options).await?;
+    }
+    let reveal = &compose.reveal.params.entry;
+    let reveal = retry(options, || entry::reveal_entry(client, reveal))
+        .await?
+        .into_result()?;
+    let status = entry_status(client, reveal.entryhash, reveal.chainid, options).await?;
+    Ok(Submission {
+        committxid,
+        entryhash: reveal.entryhash,
+        chainid: reveal.chainid,
+        repeated_commit,
+        status,
+    })
+}
+
+/// Commits and reveals a composed chain, following the same workflow as
+/// submit_entry.
+pub async fn submit_chain(
+    client: &Factom,
+    compose: &Compose,
+    options: &SubmitOptions,
+) -> Result<Submission> {
+    let commit = &compose.commit.params.message;
+    let (committxid, _) = commit_ids(commit, CHAIN_COMMIT_LEN)?;
+    let response = retry(options, || chain::commit_chain(client, commit)).await?;
+    let repeated_commit = commit_accepted(response)?;
+    if !repeated_commit {
+        wait_for_commit(client, committxid, options).await?;
+    }
+    let reveal = &compose.reveal.params.entry;
+    let reveal = retry(options, || chain::reveal_chain(client, reveal))
+        .await?
+        .into_result()?;
+    let status = entry_status(client, reveal.entryhash, reveal.chainid, options).await?;
+    Ok(Submission {
+        committxid,
+        entryhash: reveal.entryhash,
+        chainid: reveal.chainid,
+        repeated_commit,
+        status,
+    })
+}
+
+/// Reads the commit txid and entry hash from a hex encoded commit message
+fn commit_ids(message: &str, ledger_len: usize) -> Result<(TxId, EntryHash)> {
+    let bytes = hash::decode_hex(message)?;
+    if bytes.len() < ledger_len {
+        return Err("Commit message is too short".into());
+    }
+    let ledger = &bytes[..ledger_len];
+    // The entry hash is followed by the single byte entry credit count
+    let mut entryhash = [0u8; 32];
+    entryhash.copy_from_slice(&ledger[ledger_len - 33..ledger_len - 1]);
+    let txid: [u8; 32] = Sha256::digest(ledger).into();
+    Ok((TxId(txid), EntryHash(entryhash)))
+}
+
+/// Returns whether the commit was a repeat, any other api error is returned
+fn commit_accepted<T: Default>(response: ApiResponse<T>) -> Result<bool> {
+    if response.error.code == REPEATED_COMMIT {
+        return Ok(true);
+    }
+    response.into_result().map(|_| false)
+}
+
+/// Polls the commit acknowledgement until it reaches at least TransactionACK
+async fn wait_for_commit(client: &Factom, txid: TxId, options: &SubmitOptions) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        let ack = retry(options, || tx::commit_ack(client, txid, None))
+            .await?
+            .into_result()?;
+        if ack.commitdata.status >= AckStatus::TransactionAck {
+            return Ok(());
+        }
+        if start.elapsed() >= options.ack_timeout {
+            return Err(format!("Timed out waiting for commit {} to be acknowledged", txid).into());
+        }
+        Delay::new(options.ack_interval).await;
+    }
+}
+
+/// Fetches the current status of a revealed entry
+async fn entry_status(
+    client: &Factom,
+    entryhash: EntryHash,
+    chainid: ChainId,
+    options: &SubmitOptions,
+) -> Result<AckStatus> {
+    let ack = retry(options, || tx::ec_ack(client, entryhash, chainid, None))
+        .await?
+        .into_result()?;
+    Ok(ack.entrydata.status)
+}
+
+/// Runs an api call, retrying it after network errors
+async fn retry<T, F, Fut>(options: &SubmitOptions, mut call: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempts = 0;
+    loop {
+        match call().await {
+            Err(_) if attempts < options.retries => {
+                attempts += 1;
+                Delay::new(options.retry_delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Traverses a chain from the head towards the genesis block returning its
+/// entries newest first. A depth limits the number of entry blocks read, None
+/// reads the whole chain. See the traverse module for streaming and forward
+/// traversal.
 ///
 /// # Example
 /// ```no_run
@@ -125,35 +303,29 @@ pub async fn create_chain(
 /// async fn main() {
 /// let client = Factom::open_node();
 /// let chain = "843dbee7a49a9b9510d399759fbce24b1f700268c94508085abce352d70ed1f6";
-/// let response = utils::traverse_chain(&client, chain, 1).await;
-/// dbg!(response);
+/// let entries = utils::traverse_chain(&client, chain, Some(1)).await;
+/// dbg!(entries);
 /// }
 /// ```
-pub async fn traverse_chain(client: &Factom, chainid: &str, depth: usize) -> Vec<Entry> {
-    let chainhead_response = chain::chain_head(client, chainid)
+pub async fn traverse_chain(
+    client: &Factom,
+    chainid: impl IntoHash<ChainId>,
+    depth: Option<usize>,
+) -> Result<Vec<Entry>> {
+    let options = traverse::TraverseOptions::default();
+    let concurrency = options.concurrency;
+    let blocks: Vec<_> = traverse::entry_blocks(client, chainid, options)?
+        .take(depth.unwrap_or(usize::MAX))
+        .try_collect()
+        .await?;
+    let fetches = blocks
+        .into_iter()
+        .flat_map(|block| traverse::block_entries(client, block, false));
+    stream::iter(fetches)
+        .buffered(concurrency)
+        .map_ok(|entry| entry.entry)
+        .try_collect()
         .await
-        .expect("Fetching ChainHead");
-    let mut keymr = chainhead_response.result.chainhead;
-    let mut entries = Vec::new();
-    let mut blocks = 0;
-    while keymr != NULL_KEYMR {
-        let response = block::entry_block(&client, &keymr)
-            .await
-            .expect("Fetching EntryBlock");
-        keymr = response.result.header.prevkeymr;
-        let entrylist = response.result.entrylist;
-        for e in entrylist {
-            let response = entry::entry(&client, &e.entryhash)
-                .await
-                .expect("Fetching Entry");
-            entries.push(response.result);
-        }
-        blocks += 1;
-        if blocks == depth {
-            break;
-        }
-    }
-    entries
 }
 
 /// Converts Factoshis to Factoids
@@ -165,3 +337,111 @@ pub fn factoshis_to_fct(factoshis: usize) -> f64 {
 pub fn fct_to_factoshis(factoids: f64) -> f64 {
     factoids * 100_000_000f64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{MockNode, MockReply};
+    use futures::executor::block_on;
+
+    fn options() -> SubmitOptions {
+        SubmitOptions {
+            retries: 2,
+            retry_delay: Duration::from_millis(1),
+            ack_interval: Duration::from_millis(1),
+            ack_timeout: Duration::from_millis(20),
+        }
+    }
+
+    const CHAINID: [u8; 32] = [0xcd; 32];
+    const ENTRYHASH: [u8; 32] = [0xab; 32];
+
+    fn compose() -> Compose {
+        let mut compose = Compose::default();
+        compose.commit.params.message = hash::encode_hex(&[0xab; 136]);
+        // Version, chain id and an empty external id list
+        compose.reveal.params.entry = format!("00{}0000", hash::encode_hex(&CHAINID));
+        compose
+    }
+
+    #[test]
+    fn repeated_commit_is_revealed() {
+        let node = MockNode::new();
+        node.respond_error("commit-entry", REPEATED_COMMIT, "Repeated Commit")
+            .respond(
+                "reveal-entry",
+                json!({"entryhash": EntryHash(ENTRYHASH), "chainid": ChainId(CHAINID)}),
+            )
+            .respond("ack", json!({"entrydata": {"status": "TransactionACK"}}));
+        let submission = block_on(submit_entry(&node.client(), &compose(), &options())).unwrap();
+        assert!(submission.repeated_commit);
+        assert_eq!(submission.status, AckStatus::TransactionAck);
+        // The commit isn't polled, only the revealed entry
+        let acks = node.requests_for("ack");
+        assert_eq!(acks.len(), 1);
+        assert_eq!(acks[0].params["chainid"], json!(ChainId(CHAINID)));
+        assert_eq!(acks[0].params["hash"], json!(EntryHash(ENTRYHASH)));
+        assert_eq!(submission.entryhash, EntryHash(ENTRYHASH));
+        assert_eq!(node.requests_for("reveal-entry").len(), 1);
+    }
+
+    #[test]
+    fn network_errors_are_retried() {
+        let node = MockNode::new();
+        let reset = || MockReply::Fail("connection reset".to_string());
+        node.respond_once("commit-entry", reset())
+            .respond_once("commit-entry", reset())
+            .respond(
+                "ack",
+                json!({
+                    "commitdata": {"status": "TransactionACK"},
+                    "entrydata": {"status": "DBlockConfirmed"}
+                }),
+            );
+        let client = node.client();
+        let submission = block_on(submit_entry(&client, &compose(), &options())).unwrap();
+        assert!(!submission.repeated_commit);
+        assert_eq!(submission.status, AckStatus::DBlockConfirmed);
+        assert_eq!(node.requests_for("commit-entry").len(), 3);
+        assert_eq!(node.requests_for("ack")[0].params["chainid"], "c");
+
+        // Out of retries the network error is returned
+        node.fail("reveal-entry", "connection reset");
+        let err = block_on(submit_entry(&client, &compose(), &options())).unwrap_err();
+        assert_eq!(err.to_string(), "connection reset");
+        assert_eq!(node.requests_for("reveal-entry").len(), 1 + 3);
+    }
+
+    #[test]
+    fn api_errors_are_not_retried() {
+        let node = MockNode::new();
+        node.respond_error("commit-entry", -32602, "Invalid params");
+        let err = block_on(submit_entry(&node.client(), &compose(), &options())).unwrap_err();
+        assert!(err.to_string().contains("Invalid params"));
+        assert_eq!(node.requests_for("commit-entry").len(), 1);
+        assert!(node.requests_for("reveal-entry").is_empty());
+    }
+
+    #[test]
+    fn commit_ack_timeout() {
+        let node = MockNode::new();
+        node.respond("ack", json!({"commitdata": {"status": "NotConfirmed"}}));
+        let err = block_on(submit_entry(&node.client(), &compose(), &options())).unwrap_err();
+        assert!(err.to_string().starts_with("Timed out waiting for commit"));
+        assert!(node.requests_for("ack").len() > 1);
+        assert!(node.requests_for("reveal-entry").is_empty());
+    }
+
+    #[test]
+    fn entry_commit_ids() {
+        let entryhash = [0xab; 32];
+        let mut message = vec![0x00, 1, 2, 3, 4, 5, 6];
+        message.extend_from_slice(&entryhash);
+        message.push(0x01);
+        message.extend_from_slice(&[0u8; 96]);
+        let (txid, hash) = commit_ids(&hash::encode_hex(&message), ENTRY_COMMIT_LEN).unwrap();
+        assert_eq!(hash, EntryHash(entryhash));
+        assert_eq!(txid.as_bytes()[..], Sha256::digest(&message[..40])[..]);
+        assert!(commit_ids("00", ENTRY_COMMIT_LEN).is_err());
+    }
+}
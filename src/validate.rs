@@ -0,0 +1,468 @@
+//! Light client validation of the directory block header chain.
+use super::*;
+use crate::block::{Ablock, DblockHeight, EcBlockResult, Fblock};
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fmt;
+
+/// A trusted point in the header chain from which validation proceeds.
+///
+/// The checkpoint holds everything needed to check the linkage of the next
+/// height. It is advanced as each height is validated and can be serialized to
+/// persist progress and resume validation at a later time.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub height: u32,
+    pub keymr: KeyMr,
+    pub fullhash: hash::Hash,
+    pub ablock_backrefhash: hash::Hash,
+    pub ecblock_headerhash: KeyMr,
+    pub fblock_keymr: KeyMr,
+}
+
+impl Checkpoint {
+    /// Builds a checkpoint from the directory block at the given height. The
+    /// keymr of the block must match the trusted keymr provided, everything else
+    /// is taken from the node.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use factom::*;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///   let client = Factom::open_node();
+    ///   let keymr = "90b344dabe065bcb38c90948cde8ab388e22364257f811da7e7a7e8102efc33f";
+    ///   let checkpoint = validate::Checkpoint::trusted(&client, 220000, keymr)
+    ///                             .await
+    ///                             .expect("Trusted checkpoint");
+    ///   assert_eq!(checkpoint.height, 220000);
+    /// }
+    /// ```
+    pub async fn trusted(api: &Factom, height: u32, keymr: &str) -> Result<Checkpoint> {
+        let dblock = block::dblock_by_height(api, height)
+            .await?
+            .into_result()?
+            .dblock;
+        if dblock.keymr != keymr {
+            return Err(Box::new(Inconsistency {
+                height,
+                link: Link::TrustedKeyMr,
+                expected: keymr.to_string(),
+                found: dblock.keymr.to_string(),
+            }));
+        }
+        let ablock_keymr = dbentry(&dblock, height, ADMIN_CHAINID, Link::ABlockInclusion)?;
+        let ablock = block::admin_block(api, &ablock_keymr)
+            .await?
+            .into_result()?
+            .ablock;
+        Ok(Checkpoint {
+            height,
+            keymr: dblock.keymr,
+            fullhash: dblock.dbhash,
+            ablock_backrefhash: ablock.backreferencehash,
+            ecblock_headerhash: dbentry(&dblock, height, EC_CHAINID, Link::ECBlockInclusion)?,
+            fblock_keymr: dbentry(&dblock, height, FACTOID_CHAINID, Link::FBlockInclusion)?,
+        })
+    }
+}
+
+/// The link in the header chain that failed to validate
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Link {
+    /// The directory block keymr did not match the trusted keymr of the
+    /// checkpoint
+    TrustedKeyMr,
+    /// The directory block was not at the requested height
+    Height,
+    /// The directory block prevkeymr did not match the previous keymr
+    PrevKeyMr,
+    /// The directory block prevfullhash did not match the previous full hash
+    PrevFullHash,
+    /// The admin block referenced in the directory block was missing or mismatched
+    ABlockInclusion,
+    /// The entry credit block referenced in the directory block was missing or
+    /// mismatched
+    ECBlockInclusion,
+    /// The factoid block referenced in the directory block was missing or
+    /// mismatched
+    FBlockInclusion,
+    /// The admin block prevbackrefhash did not match the previous admin block
+    ABlockBackRef,
+    /// The entry credit block prevheaderhash did not match the previous entry
+    /// credit block
+    ECBlockPrevHeader,
+    /// The factoid block prevkeymr did not match the previous factoid block
+    FBlockPrevKeyMr,
+}
+
+/// Describes the first inconsistency found while validating the header chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Inconsistency {
+    pub height: u32,
+    pub link: Link,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} inconsistency at height {}: expected {}, found {}",
+            self.link, self.height, self.expected, self.found
+        )
+    }
+}
+
+impl Error for Inconsistency {}
+
+/// Walks the directory blocks from the height after the checkpoint up to and
+/// including to_height, checking that every block links to the one before it.
+///
+/// For each height the directory block prevkeymr and prevfullhash are compared
+/// with the checkpoint, the admin, entry credit and factoid blocks are fetched
+/// by the keymrs listed in the directory block entries and their back
+/// references are checked against the previous height. The admin and factoid
+/// blocks must report the keymr listed for them, the entry credit block doesn't
+/// report its own so its header hash is computed from the raw data instead.
+///
+/// The checkpoint is advanced after every validated height so after an error it
+/// holds the last good height and can be persisted to resume from. An
+/// [Inconsistency](struct.Inconsistency.html) is returned as the error for the
+/// first broken link, network and api errors are returned as is.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::open_node();
+///   let keymr = "90b344dabe065bcb38c90948cde8ab388e22364257f811da7e7a7e8102efc33f";
+///   let mut checkpoint = validate::Checkpoint::trusted(&client, 220000, keymr)
+///                             .await
+///                             .expect("Trusted checkpoint");
+///   validate::validate_headers(&client, &mut checkpoint, 220010)
+///                             .await
+///                             .expect("Validating headers");
+///   assert_eq!(checkpoint.height, 220010);
+/// }
+/// ```
+pub async fn validate_headers(
+    api: &Factom,
+    checkpoint: &mut Checkpoint,
+    to_height: u32,
+) -> Result<()> {
+    while checkpoint.height < to_height {
+        let height = checkpoint.height + 1;
+        let dblock = block::dblock_by_height(api, height)
+            .await?
+            .into_result()?
+            .dblock;
+        let ablock_keymr = dbentry(&dblock, height, ADMIN_CHAINID, Link::ABlockInclusion)?;
+        let ecblock_keymr = dbentry(&dblock, height, EC_CHAINID, Link::ECBlockInclusion)?;
+        let fblock_keymr = dbentry(&dblock, height, FACTOID_CHAINID, Link::FBlockInclusion)?;
+        let (ablock, ecblock, fblock) = futures::join!(
+            block::admin_block(api, &ablock_keymr),
+            block::entry_credit_block(api, &ecblock_keymr),
+            block::factoid_block(api, &fblock_keymr)
+        );
+        *checkpoint = check_height(
+            checkpoint,
+            &dblock,
+            &ablock?.into_result()?.ablock,
+            &ecblock?.into_result()?,
+            &fblock?.into_result()?.fblock,
+        )?;
+    }
+    Ok(())
+}
+
+/// Checks a single height against the checkpoint before it, returning the
+/// checkpoint for that height if every link is consistent.
+pub fn check_height(
+    checkpoint: &Checkpoint,
+    dblock: &DblockHeight,
+    ablock: &Ablock,
+    ecblock: &EcBlockResult,
+    fblock: &Fblock,
+) -> std::result::Result<Checkpoint, Inconsistency> {
+    let height = checkpoint.height + 1;
+    let check_height = |link, found: usize| check(height, link, &(height as usize), &found);
+    let header = &dblock.header;
+    check_height(Link::Height, header.dbheight)?;
+    check(
+        height,
+        Link::PrevKeyMr,
+        &checkpoint.keymr,
+        &header.prevkeymr,
+    )?;
+    check(
+        height,
+        Link::PrevFullHash,
+        &checkpoint.fullhash,
+        &header.prevfullhash,
+    )?;
+
+    let ablock_keymr = dbentry(dblock, height, ADMIN_CHAINID, Link::ABlockInclusion)?;
+    let ecblock_keymr = dbentry(dblock, height, EC_CHAINID, Link::ECBlockInclusion)?;
+    let fblock_keymr = dbentry(dblock, height, FACTOID_CHAINID, Link::FBlockInclusion)?;
+
+    check(
+        height,
+        Link::ABlockInclusion,
+        &ablock_keymr,
+        &ablock.lookuphash,
+    )?;
+    check_height(Link::ABlockInclusion, ablock.header.dbheight)?;
+    let ecblock_headerhash = ecblock_headerhash(&ecblock.rawdata).unwrap_or_default();
+    check(
+        height,
+        Link::ECBlockInclusion,
+        &ecblock_keymr,
+        &ecblock_headerhash,
+    )?;
+    let ecblock = &ecblock.ecblock;
+    check_height(Link::ECBlockInclusion, ecblock.header.dbheight)?;
+    check(height, Link::FBlockInclusion, &fblock_keymr, &fblock.keymr)?;
+    check_height(Link::FBlockInclusion, fblock.dbheight)?;
+
+    check(
+        height,
+        Link::ABlockBackRef,
+        &checkpoint.ablock_backrefhash,
+        &ablock.header.prevbackrefhash,
+    )?;
+    check(
+        height,
+        Link::ECBlockPrevHeader,
+        &checkpoint.ecblock_headerhash,
+        &ecblock.header.prevheaderhash,
+    )?;
+    check(
+        height,
+        Link::FBlockPrevKeyMr,
+        &checkpoint.fblock_keymr,
+        &fblock.prevkeymr,
+    )?;
+
+    Ok(Checkpoint {
+        height,
+        keymr: dblock.keymr,
+        fullhash: dblock.dbhash,
+        ablock_backrefhash: ablock.backreferencehash,
+        ecblock_headerhash: ecblock_keymr,
+        fblock_keymr,
+    })
+}
+
+/// Compares one link of the header chain
+fn check<T: PartialEq + fmt::Display>(
+    height: u32,
+    link: Link,
+    expected: &T,
+    found: &T,
+) -> std::result::Result<(), Inconsistency> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(Inconsistency {
+            height,
+            link,
+            expected: expected.to_string(),
+            found: found.to_string(),
+        })
+    }
+}
+
+/// Hashes the header of an entry credit block, which is the keymr listed for
+/// it in the directory block, returns None if the raw data is malformed
+fn ecblock_headerhash(rawdata: &str) -> Option<KeyMr> {
+    let raw = hash::decode_hex(rawdata).ok()?;
+    // Chain id, body hash, previous header hash, previous full hash and height
+    let mut len = 4 * 32 + 4;
+    // Followed by the varint length of the header expansion area
+    let mut expansion = 0usize;
+    loop {
+        let byte = *raw.get(len)?;
+        len += 1;
+        expansion = expansion
+            .checked_mul(128)?
+            .checked_add((byte & 0x7f) as usize)?;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    // Then the expansion area, object count and body size
+    len = len.checked_add(expansion)?.checked_add(16)?;
+    let header = raw.get(..len)?;
+    Some(KeyMr(Sha256::digest(header).into()))
+}
+
+/// Finds the keymr listed in the directory block for the given chain
+fn dbentry(
+    dblock: &DblockHeight,
+    height: u32,
+    chainid: &str,
+    link: Link,
+) -> std::result::Result<KeyMr, Inconsistency> {
+    dblock
+        .dbentries
+        .iter()
+        .find(|entry| entry.chainid == chainid)
+        .map(|entry| entry.keymr)
+        .ok_or_else(|| Inconsistency {
+            height,
+            link,
+            expected: chainid.to_string(),
+            found: String::new(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{DBlockHeightHeader, Dbentry, EcBlockHeader, Ecblock, Header};
+
+    fn keymr(byte: u8) -> KeyMr {
+        KeyMr([byte; 32])
+    }
+
+    fn full(byte: u8) -> hash::Hash {
+        hash::Hash([byte; 32])
+    }
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint {
+            height: 9,
+            keymr: keymr(0xd9),
+            fullhash: full(0xf9),
+            ablock_backrefhash: full(0xa9),
+            ecblock_headerhash: keymr(0xe9),
+            fblock_keymr: keymr(0xf9),
+        }
+    }
+
+    /// Raw entry credit block header at height 10 and its hash
+    fn ecblock_header() -> (Vec<u8>, KeyMr) {
+        let mut raw = hash::decode_hex(EC_CHAINID).unwrap();
+        raw.extend_from_slice(&[0; 32]);
+        raw.extend_from_slice(&[0xe9; 32]);
+        raw.extend_from_slice(&[0; 32]);
+        raw.extend_from_slice(&10u32.to_be_bytes());
+        // Two byte expansion area
+        raw.extend_from_slice(&[2, 0xaa, 0xbb]);
+        raw.extend_from_slice(&[0; 16]);
+        let hash = KeyMr(Sha256::digest(&raw).into());
+        (raw, hash)
+    }
+
+    fn blocks() -> (DblockHeight, Ablock, EcBlockResult, Fblock) {
+        let (mut rawdata, ecblock_keymr) = ecblock_header();
+        // The body isn't part of the header hash
+        rawdata.extend_from_slice(&[0x01; 8]);
+        let entry = |chainid: &str, keymr: KeyMr| Dbentry {
+            chainid: chainid.parse().unwrap(),
+            keymr,
+        };
+        let dblock = DblockHeight {
+            header: DBlockHeightHeader {
+                prevkeymr: keymr(0xd9),
+                prevfullhash: full(0xf9),
+                dbheight: 10,
+                ..Default::default()
+            },
+            dbentries: vec![
+                entry(ADMIN_CHAINID, keymr(0xa0)),
+                entry(EC_CHAINID, ecblock_keymr),
+                entry(FACTOID_CHAINID, keymr(0xf0)),
+            ],
+            dbhash: full(0xf1),
+            keymr: keymr(0xd0),
+        };
+        let ablock = Ablock {
+            header: Header {
+                prevbackrefhash: full(0xa9),
+                dbheight: 10,
+                ..Default::default()
+            },
+            backreferencehash: full(0xa1),
+            lookuphash: keymr(0xa0),
+            ..Default::default()
+        };
+        let ecblock = EcBlockResult {
+            ecblock: Ecblock {
+                header: EcBlockHeader {
+                    prevheaderhash: keymr(0xe9),
+                    dbheight: 10,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            rawdata: hash::encode_hex(&rawdata),
+        };
+        let fblock = Fblock {
+            prevkeymr: keymr(0xf9),
+            keymr: keymr(0xf0),
+            dbheight: 10,
+            ..Default::default()
+        };
+        (dblock, ablock, ecblock, fblock)
+    }
+
+    #[test]
+    fn consistent_height_advances_checkpoint() {
+        let (dblock, ablock, ecblock, fblock) = blocks();
+        let next = check_height(&checkpoint(), &dblock, &ablock, &ecblock, &fblock)
+            .expect("Consistent height");
+        assert_eq!(next.height, 10);
+        assert_eq!(next.keymr, keymr(0xd0));
+        assert_eq!(next.ecblock_headerhash, ecblock_header().1);
+        assert_eq!(next.fblock_keymr, keymr(0xf0));
+    }
+
+    #[test]
+    fn broken_link_is_reported() {
+        let (mut dblock, ablock, ecblock, mut fblock) = blocks();
+        fblock.prevkeymr = keymr(0xbb);
+        let err = check_height(&checkpoint(), &dblock, &ablock, &ecblock, &fblock).unwrap_err();
+        assert_eq!(err.link, Link::FBlockPrevKeyMr);
+        assert_eq!(err.height, 10);
+        assert_eq!(err.found, keymr(0xbb).to_string());
+
+        dblock.dbentries.retain(|e| e.chainid != EC_CHAINID);
+        let err = check_height(&checkpoint(), &dblock, &ablock, &ecblock, &fblock).unwrap_err();
+        assert_eq!(err.link, Link::ECBlockInclusion);
+    }
+
+    #[test]
+    fn mismatched_ecblock_is_reported() {
+        let (dblock, ablock, mut ecblock, fblock) = blocks();
+        let (mut raw, keymr) = ecblock_header();
+        raw[40] ^= 1;
+        ecblock.rawdata = hash::encode_hex(&raw);
+        let err = check_height(&checkpoint(), &dblock, &ablock, &ecblock, &fblock).unwrap_err();
+        assert_eq!(err.link, Link::ECBlockInclusion);
+        assert_eq!(err.expected, keymr.to_string());
+
+        // A header cut short doesn't hash
+        ecblock.rawdata = hash::encode_hex(&raw[..100]);
+        let err = check_height(&checkpoint(), &dblock, &ablock, &ecblock, &fblock).unwrap_err();
+        assert_eq!(err.link, Link::ECBlockInclusion);
+    }
+
+    #[test]
+    fn untrusted_keymr_is_reported() {
+        let (dblock, _, _, _) = blocks();
+        let node = crate::testing::MockNode::new();
+        node.respond("dblock-by-height", json!({ "dblock": dblock }));
+        let trusted = keymr(0xd1).to_string();
+        let err = futures::executor::block_on(Checkpoint::trusted(&node.client(), 10, &trusted))
+            .unwrap_err();
+        let err = err.downcast::<Inconsistency>().unwrap();
+        assert_eq!(err.link, Link::TrustedKeyMr);
+        assert_eq!(err.found, keymr(0xd0).to_string());
+    }
+}
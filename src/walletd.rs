@@ -1,5 +1,9 @@
 //! General utility functions relating to factom-walletd
 use super::*;
+use crate::secret::Secret;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use std::convert::TryInto;
 
 /// Return the wallet seed and all addresses in the wallet for backup and offline
 /// storage.
@@ -92,6 +96,38 @@ pub async fn wallet_height(api: &Factom) -> Result<ApiResponse<Height>> {
     parse(response).await
 }
 
+/// Signs arbitrary data with a key held by the wallet, the signer being a
+/// factoid address, entry credit address or public identity key. The wallet
+/// signs the data with the ed25519 private key of the signer and returns the
+/// public key and signature, both base64 encoded, so attestations can be
+/// produced without exporting the private key. Signatures are checked locally
+/// with [SignData::verify](struct.SignData.html#method.verify).
+/// # Example
+/// ```no_run
+/// use factom::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::new();
+///   let signer = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+///   let data = b"Hello Factom";
+///   let response = walletd::sign_data(&client, signer, data).await.unwrap();
+///   assert!(response.result.verify(data).unwrap());
+/// }
+/// ```
+pub async fn sign_data(
+    api: &Factom,
+    signer: &str,
+    data: impl AsRef<[u8]>,
+) -> Result<ApiResponse<SignData>> {
+    let mut req = ApiRequest::new("sign-data");
+    req.params.insert("signer".to_string(), json!(signer));
+    req.params
+        .insert("data".to_string(), json!(STANDARD.encode(data)));
+    let response = walletd_call(api, req).await;
+    parse(response).await
+}
+
 /// Retrieve current properties of factom-walletd, including the wallet and wallet
 /// API versions.
 /// # Example
@@ -123,14 +159,14 @@ pub struct UnlockWallet {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WalletBackup {
     #[serde(rename = "wallet-seed")]
-    pub wallet_seed: String,
+    pub wallet_seed: Secret,
     pub addresses: Vec<Address>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Address {
     pub public: String,
-    pub secret: String,
+    pub secret: Secret,
 }
 
 /// wallet-balances function
@@ -159,6 +195,30 @@ pub struct SignData {
     pub signature: String,
 }
 
+impl SignData {
+    /// Checks the signature against the data that was signed, returns an error
+    /// if the public key or signature are malformed
+    pub fn verify(&self, data: &[u8]) -> Result<bool> {
+        verify_signature(&self.pubkey, data, &self.signature)
+    }
+}
+
+/// Verifies a base64 encoded ed25519 signature of the data, as returned by
+/// [sign_data](fn.sign_data.html), with the base64 encoded public key
+pub fn verify_signature(pubkey: &str, data: &[u8], signature: &str) -> Result<bool> {
+    let pubkey: [u8; 32] = STANDARD
+        .decode(pubkey)?
+        .try_into()
+        .map_err(|_| "Public key must be 32 bytes")?;
+    let signature: [u8; 64] = STANDARD
+        .decode(signature)?
+        .try_into()
+        .map_err(|_| "Signature must be 64 bytes")?;
+    let key = VerifyingKey::from_bytes(&pubkey)?;
+    let signature = Signature::from_bytes(&signature);
+    Ok(key.verify(data, &signature).is_ok())
+}
+
 /// wallet-properties function
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Properties {
@@ -171,3 +231,39 @@ pub struct Properties {
 pub struct Height {
     pub height: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockNode;
+    use ed25519_dalek::{Signer, SigningKey};
+    use futures::executor::block_on;
+
+    #[test]
+    fn sign_and_verify() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let data = b"attestation";
+        let node = MockNode::new();
+        node.respond(
+            "sign-data",
+            json!({
+                "pubkey": STANDARD.encode(key.verifying_key().as_bytes()),
+                "signature": STANDARD.encode(key.sign(data).to_bytes()),
+            }),
+        );
+        let client = node.client();
+        let signer = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+        let response = block_on(sign_data(&client, signer, data)).unwrap();
+        assert_eq!(
+            node.requests_for("sign-data")[0].params["data"],
+            STANDARD.encode(data)
+        );
+        assert!(response.result.verify(data).unwrap());
+        assert!(!response.result.verify(b"forgery").unwrap());
+        let truncated = SignData {
+            signature: STANDARD.encode([0; 10]),
+            ..response.result
+        };
+        assert!(truncated.verify(data).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+//! Watches factoid addresses for incoming and outgoing transactions.
+use super::*;
+use crate::follow::poll_interval;
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use futures_timer::Delay;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+/// Stream of events returned by watch_addresses
+pub type AddressEvents = LocalBoxStream<'static, Result<AddressEvent>>;
+
+/// Options for watch_addresses
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchOptions {
+    /// Fixed pause between polls, None to poll after each minute boundary
+    pub interval: Option<Duration>,
+    /// Emit transactions seen in the pending transactions before they are
+    /// confirmed
+    pub pending: bool,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            interval: None,
+            pending: true,
+        }
+    }
+}
+
+/// Whether a watched address received or spent factoids
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Activity {
+    /// The address was an output of the transaction
+    Credited,
+    /// The address was an input of the transaction
+    Debited,
+}
+
+/// Confirmation state of the transaction behind an event
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Confirmation {
+    /// Found in the pending transactions, not yet in a factoid block
+    Pending,
+    /// Recorded in the factoid block at the given directory block height
+    Confirmed(u32),
+}
+
+/// A transaction input or output touching a watched address
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AddressEvent {
+    /// The watched FA address
+    pub address: String,
+    pub txid: TxId,
+    pub activity: Activity,
+    /// Amount in factoshis
+    pub amount: usize,
+    pub confirmation: Confirmation,
+}
+
+struct Watcher {
+    api: Factom,
+    addresses: HashSet<String>,
+    options: WatchOptions,
+    next: Option<u32>,
+    /// Pending transactions already emitted, or confirmed while still pending
+    known: HashSet<TxId>,
+    queue: VecDeque<Result<AddressEvent>>,
+    polled: bool,
+}
+
+/// Watches a set of factoid addresses, yielding an event for every transaction
+/// input or output touching one of them.
+///
+/// Each new factoid block is scanned once it is saved, with a start height the
+/// blocks from that height are scanned first to backfill past activity. If
+/// enabled the pending transactions are scanned on every poll, each pending
+/// transaction is emitted once and again when confirmed. Errors are yielded
+/// without ending the stream, the next poll carries on from the same height.
+///
+/// # Example
+/// ```no_run
+/// use factom::*;
+/// use factom::watch::*;
+/// use futures::stream::StreamExt;
+///
+/// #[tokio::main]
+/// async fn main() {
+///   let client = Factom::open_node();
+///   let addresses = vec!["FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q"];
+///   let mut events = watch_addresses(&client, addresses, None, WatchOptions::default());
+///   while let Some(event) = events.next().await {
+///     let event = event.expect("Watching addresses");
+///     if event.activity == Activity::Credited {
+///       println!("{} received {} factoshis", event.address, event.amount);
+///     }
+///   }
+/// }
+/// ```
+pub fn watch_addresses<S: Into<String>>(
+    api: &Factom,
+    addresses: impl IntoIterator<Item = S>,
+    start: Option<u32>,
+    options: WatchOptions,
+) -> AddressEvents {
+    let watcher = Watcher {
+        api: api.clone(),
+        addresses: addresses.into_iter().map(Into::into).collect(),
+        options,
+        next: start,
+        known: HashSet::new(),
+        queue: VecDeque::new(),
+        polled: false,
+    };
+    stream::unfold(watcher, |mut watcher| async move {
+        loop {
+            if let Some(event) = watcher.queue.pop_front() {
+                return Some((event, watcher));
+            }
+            if watcher.polled {
+                let pause = poll_interval(&watcher.api, watcher.options.interval).await;
+                Delay::new(pause).await;
+            }
+            watcher.polled = true;
+            watcher.poll().await;
+        }
+    })
+    .boxed_local()
+}
+
+impl Watcher {
+    /// Queues the events found since the last poll
+    async fn poll(&mut self) {
+        if let Err(e) = self.confirmed().await {
+            self.queue.push_back(Err(e));
+        }
+        if self.options.pending {
+            if let Err(e) = self.pending().await {
+                self.queue.push_back(Err(e));
+            }
+        }
+    }
+
+    /// Scans the factoid blocks saved since the last poll
+    async fn confirmed(&mut self) -> Result<()> {
+        let minute = factomd::current_minute(&self.api).await?.into_result()?;
+        let saved = minute.directoryblockheight as u32;
+        let next = self.next.get_or_insert(saved + 1);
+        while *next <= saved {
+            let fblock = block::fblock_by_height(&self.api, *next)
+                .await?
+                .into_result()?
+                .fblock;
+            for tx in fblock.transactions {
+                let inputs = tx.inputs.iter().map(|i| (&i.useraddress, i.amount));
+                let outputs = tx.outputs.iter().map(|o| (&o.useraddress, o.amount));
+                let confirmation = Confirmation::Confirmed(*next);
+                let events = matches(&self.addresses, tx.txid, inputs, outputs, confirmation);
+                // Only needed to skip it in the pending transactions, which
+                // prune it
+                if !events.is_empty() && self.options.pending {
+                    self.known.insert(tx.txid);
+                }
+                self.queue.extend(events.into_iter().map(Ok));
+            }
+            *next += 1;
+        }
+        Ok(())
+    }
+
+    /// Scans the pending transactions for ones not emitted yet
+    async fn pending(&mut self) -> Result<()> {
+        let pending = tx::pending_transactions(&self.api, None)
+            .await?
+            .into_result()?;
+        for tx in &pending {
+            if self.known.contains(&tx.transactionid) {
+                continue;
+            }
+            let inputs = tx.inputs.iter().flatten();
+            let outputs = tx.outputs.iter().flatten();
+            let events = matches(
+                &self.addresses,
+                tx.transactionid,
+                inputs.map(|i| (&i.useraddress, i.amount)),
+                outputs.map(|o| (&o.useraddress, o.amount)),
+                Confirmation::Pending,
+            );
+            if !events.is_empty() {
+                self.known.insert(tx.transactionid);
+            }
+            self.queue.extend(events.into_iter().map(Ok));
+        }
+        // Forget transactions once they leave the pending list
+        self.known
+            .retain(|txid| pending.iter().any(|tx| tx.transactionid == *txid));
+        Ok(())
+    }
+}
+
+/// Events for the inputs and outputs of a transaction paying from or to a
+/// watched address
+fn matches<'a>(
+    addresses: &HashSet<String>,
+    txid: TxId,
+    inputs: impl Iterator<Item = (&'a String, usize)>,
+    outputs: impl Iterator<Item = (&'a String, usize)>,
+    confirmation: Confirmation,
+) -> Vec<AddressEvent> {
+    let inputs = inputs.map(|(address, amount)| (address, amount, Activity::Debited));
+    let outputs = outputs.map(|(address, amount)| (address, amount, Activity::Credited));
+    inputs
+        .chain(outputs)
+        .filter(|(address, _, _)| addresses.contains(*address))
+        .map(|(address, amount, activity)| AddressEvent {
+            address: address.clone(),
+            txid,
+            activity,
+            amount,
+            confirmation,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Fblock, Transaction};
+    use crate::testing::{MockNode, MockReply};
+    use futures::executor::block_on;
+    use futures::stream::TryStreamExt;
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const WATCHED: &str = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q";
+    const OTHER: &str = "FA3EPZYqodgyEGXNMbiZKE5TS2x2J9wF8J9MvPZb52iGR78xMgCb";
+
+    /// Each factoid block holds one transaction paying the watched address
+    /// as many factoshis as its height, with the height as txid
+    fn fblock_by_height(params: &Value) -> MockReply {
+        let height = params["height"].as_u64().unwrap() as u8;
+        let tx = Transaction {
+            txid: TxId([height; 32]),
+            inputs: vec![block::Input {
+                amount: height as usize,
+                useraddress: OTHER.to_string(),
+                ..block::Input::default()
+            }],
+            outputs: vec![block::Output {
+                amount: height as usize,
+                useraddress: WATCHED.to_string(),
+                ..block::Output::default()
+            }],
+            ..Transaction::default()
+        };
+        let fblock = Fblock {
+            transactions: vec![tx],
+            ..Fblock::default()
+        };
+        MockReply::Result(json!({ "fblock": fblock }))
+    }
+
+    fn options(pending: bool) -> WatchOptions {
+        WatchOptions {
+            interval: Some(Duration::from_millis(1)),
+            pending,
+        }
+    }
+
+    fn summary(events: &[AddressEvent]) -> Vec<(TxId, Confirmation)> {
+        events.iter().map(|e| (e.txid, e.confirmation)).collect()
+    }
+
+    fn fetched(node: &MockNode) -> Vec<u64> {
+        node.requests_for("fblock-by-height")
+            .iter()
+            .map(|request| request.params["height"].as_u64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn backfills_from_start() {
+        let node = MockNode::new();
+        node.respond("current-minute", json!({"directoryblockheight": 3}))
+            .respond_with("fblock-by-height", fblock_by_height);
+        let events = watch_addresses(&node.client(), vec![WATCHED], Some(1), options(false));
+        let events: Vec<_> = block_on(events.take(3).try_collect()).unwrap();
+        assert_eq!(
+            summary(&events),
+            vec![
+                (TxId([1; 32]), Confirmation::Confirmed(1)),
+                (TxId([2; 32]), Confirmation::Confirmed(2)),
+                (TxId([3; 32]), Confirmation::Confirmed(3)),
+            ]
+        );
+        assert!(events.iter().all(|e| e.activity == Activity::Credited));
+        assert_eq!(events[2].amount, 3);
+        assert!(node.requests_for("pending-transactions").is_empty());
+    }
+
+    #[test]
+    fn pending_then_confirmed_once() {
+        let node = MockNode::new();
+        let polls = AtomicUsize::new(0);
+        node.respond_with("current-minute", move |_| {
+            let saved = [1, 1, 2, 2, 3][polls.fetch_add(1, Ordering::SeqCst).min(4)];
+            MockReply::Result(json!({ "directoryblockheight": saved }))
+        })
+        .respond_with("fblock-by-height", fblock_by_height);
+        let calls = AtomicUsize::new(0);
+        node.respond_with("pending-transactions", move |_| {
+            // Still listed as pending for a poll after being confirmed
+            if calls.fetch_add(1, Ordering::SeqCst) > 3 {
+                return MockReply::Result(json!([]));
+            }
+            MockReply::Result(json!([{
+                "transactionid": TxId([2; 32]),
+                "status": "TransactionACK",
+                "inputs": [{"amount": 2, "address": "", "useraddress": OTHER}],
+                "outputs": [{"amount": 2, "address": "", "useraddress": WATCHED}],
+                "fees": 0,
+            }]))
+        });
+        let events = watch_addresses(&node.client(), vec![WATCHED], None, options(true));
+        let events: Vec<_> = block_on(events.take(3).try_collect()).unwrap();
+        assert_eq!(
+            summary(&events),
+            vec![
+                (TxId([2; 32]), Confirmation::Pending),
+                (TxId([2; 32]), Confirmation::Confirmed(2)),
+                (TxId([3; 32]), Confirmation::Confirmed(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn resumes_after_error() {
+        let node = MockNode::new();
+        node.respond("current-minute", json!({"directoryblockheight": 2}))
+            .respond_with("fblock-by-height", fblock_by_height)
+            .respond_once("fblock-by-height", MockReply::Fail("timed out".to_string()));
+        let events = watch_addresses(&node.client(), vec![WATCHED], Some(1), options(false));
+        let events: Vec<_> = block_on(events.take(3).collect());
+        assert!(events[0].is_err());
+        let confirmed: Vec<_> = events[1..]
+            .iter()
+            .map(|e| e.as_ref().unwrap().confirmation)
+            .collect();
+        assert_eq!(
+            confirmed,
+            vec![Confirmation::Confirmed(1), Confirmation::Confirmed(2)]
+        );
+        assert_eq!(fetched(&node), vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn confirmed_forgotten_without_pending() {
+        let node = MockNode::new();
+        node.respond("current-minute", json!({"directoryblockheight": 2}))
+            .respond_with("fblock-by-height", fblock_by_height);
+        let mut watcher = Watcher {
+            api: node.client(),
+            addresses: vec![WATCHED.to_string()].into_iter().collect(),
+            options: options(false),
+            next: Some(1),
+            known: HashSet::new(),
+            queue: VecDeque::new(),
+            polled: false,
+        };
+        block_on(watcher.poll());
+        assert_eq!(watcher.queue.len(), 2);
+        assert!(watcher.known.is_empty());
+    }
+
+    #[test]
+    fn matching_inputs_and_outputs() {
+        let watched = "FA2jK2HcLnRdS94dEcU27rF3meoJfpUcZPSinpb7AwQvPRY6RL1Q".to_string();
+        let other = "FA3EPZYqodgyEGXNMbiZKE5TS2x2J9wF8J9MvPZb52iGR78xMgCb".to_string();
+        let addresses: HashSet<_> = vec![watched.clone()].into_iter().collect();
+        let inputs = vec![(&other, 1200)];
+        let outputs = vec![(&watched, 1000), (&other, 100)];
+        let events = matches(
+            &addresses,
+            TxId::default(),
+            inputs.into_iter(),
+            outputs.into_iter(),
+            Confirmation::Confirmed(10),
+        );
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].address, watched);
+        assert_eq!(events[0].activity, Activity::Credited);
+        assert_eq!(events[0].amount, 1000);
+    }
+}
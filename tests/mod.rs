@@ -48,6 +48,34 @@ fn random_string(len: usize) -> String {
         .collect()
 }
 
+/// Client for tests with fixed expectations, replayed from the cassette in
+/// tests/fixtures so they don't query the network. Set FACTOM_RECORD to record
+/// the cassettes from the open node, a missing cassette fails the test rather
+/// than falling back to the network.
+fn fixture_client(name: &str) -> Factom {
+    use ::factom::cassette::Cassette;
+    use std::env;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    let client = Factom::open_node();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{}.json", name));
+    if env::var_os("FACTOM_RECORD").is_some() {
+        let cassette = Cassette::record(path, Rc::clone(&client.transport));
+        return client.with_transport(cassette);
+    }
+    let cassette = Cassette::replay(&path).unwrap_or_else(|e| {
+        panic!(
+            "No cassette at {}, record it with FACTOM_RECORD=1: {}",
+            path.display(),
+            e
+        )
+    });
+    client.with_transport(cassette)
+}
+
 // Address module
 #[test]
 fn address() {
@@ -57,7 +85,7 @@ fn address() {
     let query = factom::address::address(&client, FCT_PUB);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response);
-    assert_eq!(response.result.secret, FCT_PRIV);
+    assert_eq!(response.result.secret.expose_secret(), FCT_PRIV);
 }
 
 #[test]
@@ -129,7 +157,7 @@ fn multiple_ec_balances() {
 // Block module
 #[test]
 fn ablock_height() {
-    let client = Factom::open_node();
+    let client = fixture_client("ablock_height");
     let query = block::ablock_by_height(&client, ABLOCK_HEIGHT);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response);
@@ -141,7 +169,7 @@ fn ablock_height() {
 
 #[test]
 fn admin_block() {
-    let client = Factom::open_node();
+    let client = fixture_client("admin_block");
     let query = block::admin_block(&client, ABLOCK_KEYMR);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response);
@@ -161,7 +189,7 @@ fn anchors() {
 
 #[test]
 fn dblock_height() {
-    let client = Factom::open_node();
+    let client = fixture_client("dblock_height");
     let query = block::dblock_by_height(&client, ABLOCK_HEIGHT);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response.result.dblock.header);
@@ -173,7 +201,7 @@ fn dblock_height() {
 
 #[test]
 fn directory_block() {
-    let client = Factom::open_node();
+    let client = fixture_client("directory_block");
     let query = block::directory_block(&client, DBLOCK_KEYMR);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response.result.header);
@@ -191,7 +219,7 @@ fn directory_block_head() {
 
 #[test]
 fn ecblock_height() {
-    let client = Factom::open_node();
+    let client = fixture_client("ecblock_height");
     let query = block::ecblock_by_height(&client, 218668);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response.result.ecblock.header);
@@ -200,7 +228,7 @@ fn ecblock_height() {
 
 #[test]
 fn ec_block() {
-    let client = Factom::open_node();
+    let client = fixture_client("ec_block");
     let query = block::entry_credit_block(&client, ECBLOCK_KEYMR);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response.result.ecblock.header);
@@ -209,7 +237,7 @@ fn ec_block() {
 
 #[test]
 fn fct_block() {
-    let client = Factom::open_node();
+    let client = fixture_client("fct_block");
     let query = block::factoid_block(&client, FBLOCK_KEYMR);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response.result.fblock);
@@ -218,7 +246,7 @@ fn fct_block() {
 
 #[test]
 fn fctblock_height() {
-    let client = Factom::open_node();
+    let client = fixture_client("fctblock_height");
     let query = block::fblock_by_height(&client, FBLOCK_HEIGHT);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response.result.fblock);
@@ -439,7 +467,7 @@ fn create_chain_and_make_entry() {
 // Entry Module
 #[test]
 fn entry() {
-    let client = Factom::open_node();
+    let client = fixture_client("entry");
     let query = entry::entry(&client, ENTRY_HASH);
     let response = fetch(query).expect("Fetching Query");
     dbg!(&response);
@@ -448,7 +476,7 @@ fn entry() {
 
 #[test]
 fn raw_data() {
-    let client = Factom::open_node();
+    let client = fixture_client("raw_data");
     let query = entry::raw_data(&client, RAW_DATA_HASH);
     let response = fetch(query).expect("Fectching Query");
     dbg!(&response);